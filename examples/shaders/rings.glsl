@@ -0,0 +1,29 @@
+#version 450
+
+#include "lib/in_out.glsl"
+
+#include "structs/pattern.glsl"
+
+#include "uniforms/aspect_ratio.glsl"
+#include "uniforms/bass.glsl"
+#include "uniforms/dft.glsl"
+#include "uniforms/palette.glsl"
+
+layout(set = 0, binding = 10) uniform RingsParameters {
+    Pattern pattern;
+    vec2 center;
+    float ring_count;
+    float gain;
+    bool invert;
+};
+
+void main() {
+    vec2 offset = (UV - center) * vec2(aspect_ratio, 1.0);
+    float radius = length(offset);
+    float magnitude = smooth_magnitude(clamp(radius, 0.0, 1.0) * (BIN_COUNT - 1)) * gain;
+    float ring = step(0.5, fract(radius * ring_count - chrono + magnitude));
+    if (invert) {
+        ring = 1.0 - ring;
+    }
+    COLOR = vec4(get_palette_color(pattern, UV, aspect_ratio, chrono), ring);
+}
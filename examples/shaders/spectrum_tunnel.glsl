@@ -0,0 +1,17 @@
+// A Shadertoy image shader: paste-compatible with shadertoy.com when iChannel0 is set to audio.
+
+void mainImage(out vec4 fragColor, in vec2 fragCoord) {
+    vec2 uv = (fragCoord - 0.5 * iResolution.xy) / iResolution.y;
+    float radius = length(uv);
+    float angle = atan(uv.y, uv.x) / 6.2831853 + 0.5;
+
+    // the first row of iChannel0 is the spectrum, the second the waveform
+    float level = texture(iChannel0, vec2(abs(angle * 2.0 - 1.0), 0.25)).x;
+    float wave = texture(iChannel0, vec2(angle, 0.75)).x - 0.5;
+
+    float ring = fract(radius * 4.0 - iTime * 0.5);
+    float glow = smoothstep(0.1, 0.0, abs(ring - 0.5 - wave * 0.5)) * level;
+
+    vec3 color = 0.5 + 0.5 * cos(iTime + radius * 6.0 + vec3(0.0, 2.0, 4.0));
+    fragColor = vec4(color * (glow + level * 0.2 / max(radius, 0.05)), 1.0);
+}
@@ -26,6 +26,7 @@ rustPlatform.buildRustPackage rec {
   ];
 
   buildInputs = [
+    shaderc
     vulkan-loader
     libxkbcommon
     wayland
@@ -1,16 +1,24 @@
-use std::sync::Arc;
+use std::{path::Path, sync::Arc};
 
-use crate::{audio::AudioEngine, config::Config, stats::FrameTimer, video::VideoEngine};
+use crate::{
+    audio::AudioEngine,
+    config::{Config, ConfigWatcher},
+    error::LavaError,
+    stats::FrameTimer,
+    video::VideoEngine,
+};
 
+use glam::vec2;
 use winit::{
     application::ApplicationHandler,
-    event::WindowEvent,
+    event::{ElementState, WindowEvent},
     event_loop::{ActiveEventLoop, EventLoop},
     window::{Window, WindowAttributes, WindowId},
 };
 
 pub struct App {
     config: Config,
+    config_watcher: Option<ConfigWatcher>,
 
     audio_engine: AudioEngine,
     video_engine: VideoEngine,
@@ -18,36 +26,138 @@ pub struct App {
     window: Option<Arc<Box<dyn Window>>>,
 
     frame_timer: FrameTimer,
+    benchmark: Option<Benchmark>,
+
+    error: Option<LavaError>,
+}
+
+/// Frames to draw before timing any, while shaders are compiled and buffers first filled.
+const WARM_UP_FRAMES: usize = 60;
+
+/// The frames left to draw untimed and the frames to time, see [`App::benchmark`].
+struct Benchmark {
+    warm_up: usize,
+    frames: usize,
 }
 
 impl App {
-    pub fn new(event_loop: &EventLoop, config: &Config) -> Self {
-        Self {
+    pub fn new(event_loop: &EventLoop, config: &Config) -> Result<Self, LavaError> {
+        Ok(Self {
             config: config.clone(),
-            audio_engine: AudioEngine::new(config),
-            video_engine: VideoEngine::new(event_loop, config),
+            config_watcher: None,
+            audio_engine: AudioEngine::new(config)?,
+            video_engine: VideoEngine::new(event_loop)?,
             window: None,
             frame_timer: FrameTimer::new(),
+            benchmark: None,
+            error: None,
+        })
+    }
+
+    /// Applies changes to the config file at `path` while running.
+    pub fn watch_config(&mut self, path: &Path) -> Result<(), LavaError> {
+        self.config_watcher = Some(ConfigWatcher::new(path)?);
+        Ok(())
+    }
+
+    /// Compiles the built-in shaders from `directory` and swaps them in when they change.
+    pub fn use_shader_sources(&mut self, directory: &Path) {
+        self.video_engine.use_shader_sources(directory);
+    }
+
+    /// Times `frames` frames drawn as fast as the GPU can, prints the frame times like
+    /// `frame_times` does and exits.
+    pub fn benchmark(&mut self, frames: usize) {
+        self.benchmark = Some(Benchmark {
+            warm_up: WARM_UP_FRAMES,
+            frames,
+        });
+        self.video_engine.uncap_frame_rate();
+    }
+
+    fn timing_frames(&self) -> bool {
+        self.config.frame_times || self.benchmark.is_some()
+    }
+
+    fn reload_config(&mut self, config: Config) {
+        // open the new audio stream before touching the video side, so a failure on either side
+        // leaves the previous config running as a whole
+        let audio_engine = if config.same_audio_settings(&self.config) {
+            None
+        } else {
+            match AudioEngine::new(&config) {
+                Ok(audio_engine) => Some(audio_engine),
+                Err(e) => {
+                    eprintln!("{e}");
+                    eprintln!("keeping the previous config");
+                    return;
+                }
+            }
+        };
+
+        if let Err(e) = self.video_engine.reload(&self.config, &config) {
+            eprintln!("{e}");
+            eprintln!("keeping the previous config");
+            return;
+        }
+
+        if let Some(audio_engine) = audio_engine {
+            self.audio_engine = audio_engine;
+        }
+        if config.window_size != self.config.window_size {
+            if let Some(window) = &self.window {
+                let _ = window.request_surface_size(config.window_size.into());
+            }
+        }
+
+        println!("Config reloaded");
+        self.config = config;
+        self.update_dft();
+    }
+
+    /// Computes the DFT on the GPU when the config asks for it and the GPU can, on the CPU
+    /// otherwise.
+    fn update_dft(&mut self) {
+        match self.video_engine.create_gpu_dft(&self.config) {
+            Ok(()) if self.config.gpu_dft => self.audio_engine.expect_gpu_dft(),
+            Ok(()) => self.audio_engine.use_cpu_dft(),
+            Err(e) => {
+                eprintln!("{e}");
+                eprintln!("computing the DFT on the CPU");
+                self.audio_engine.use_cpu_dft();
+            }
         }
     }
+
+    /// The error that stopped the event loop, if any.
+    pub fn take_error(&mut self) -> Option<LavaError> {
+        self.error.take()
+    }
+
+    fn fail(&mut self, event_loop: &dyn ActiveEventLoop, error: LavaError) {
+        self.error = Some(error);
+        event_loop.exit();
+    }
 }
 
 impl ApplicationHandler for App {
     fn can_create_surfaces(&mut self, event_loop: &dyn ActiveEventLoop) {
-        let window = Arc::new(
-            event_loop
-                .create_window(
-                    WindowAttributes::default()
-                        .with_title("lava visualizer")
-                        .with_decorations(false)
-                        .with_resizable(false)
-                        .with_surface_size(self.config.window_size),
-                )
-                .unwrap(),
-        );
+        let window = match event_loop.create_window(
+            WindowAttributes::default()
+                .with_title("lava visualizer")
+                .with_decorations(false)
+                .with_resizable(false)
+                .with_surface_size(self.config.window_size),
+        ) {
+            Ok(window) => Arc::new(window),
+            Err(e) => return self.fail(event_loop, LavaError::gpu("creating the window", e)),
+        };
 
         self.window = Some(window.clone());
-        self.video_engine.init(&window, &self.config);
+        if let Err(e) = self.video_engine.init(&window, &self.config) {
+            return self.fail(event_loop, e);
+        }
+        self.update_dft();
     }
 
     fn window_event(
@@ -58,7 +168,7 @@ impl ApplicationHandler for App {
     ) {
         match event {
             WindowEvent::CloseRequested => {
-                if self.config.frame_times {
+                if self.config.frame_times && self.frame_timer.frame_count() > 0 {
                     self.frame_timer.print_results();
                 }
                 event_loop.exit();
@@ -66,20 +176,68 @@ impl ApplicationHandler for App {
             WindowEvent::SurfaceResized(_) => {
                 self.video_engine.resize();
             }
+            WindowEvent::PointerMoved {
+                position,
+                primary: true,
+                ..
+            } => {
+                if let Some(mouse) = self.video_engine.mouse() {
+                    mouse.move_to(vec2(position.x as f32, position.y as f32));
+                }
+            }
+            WindowEvent::PointerButton {
+                state,
+                primary: true,
+                ..
+            } => {
+                if let Some(mouse) = self.video_engine.mouse() {
+                    match state {
+                        ElementState::Pressed => mouse.press(),
+                        ElementState::Released => mouse.release(),
+                    }
+                }
+            }
             WindowEvent::RedrawRequested => {
-                if self.config.frame_times {
+                // after a failed startup the event loop is exiting, with nothing to draw with
+                let Some(window) = self.window.clone() else {
+                    return;
+                };
+                if self.error.is_some() || !self.video_engine.is_initialized() {
+                    return;
+                }
+
+                if let Some(config) = self.config_watcher.as_mut().and_then(ConfigWatcher::poll) {
+                    self.reload_config(config);
+                }
+                self.video_engine.update_shaders(&self.config);
+
+                if self.timing_frames() {
                     self.frame_timer.start_frame();
                 }
 
-                self.video_engine.redraw(
-                    &self.window.as_mut().unwrap().surface_size(),
-                    &self.audio_engine.update(),
-                );
+                if let Err(e) = self
+                    .video_engine
+                    .redraw(&window.surface_size(), &self.audio_engine.update())
+                {
+                    return self.fail(event_loop, e);
+                }
 
-                if self.config.frame_times {
+                if self.timing_frames() {
                     self.frame_timer.end_frame();
                 }
-                self.window.as_ref().unwrap().request_redraw();
+                if let Some(benchmark) = &mut self.benchmark {
+                    if benchmark.warm_up > 0 {
+                        benchmark.warm_up -= 1;
+                        self.frame_timer.clear_frame_times();
+                    } else if self.frame_timer.frame_count() >= benchmark.frames {
+                        self.frame_timer.print_results();
+                        return event_loop.exit();
+                    }
+                }
+                if let Some((dft, position)) = self.video_engine.take_gpu_dft() {
+                    self.audio_engine.use_gpu_dft(dft, position);
+                }
+                window.request_redraw();
             }
             _ => {}
         }
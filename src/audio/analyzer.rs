@@ -1,5 +1,5 @@
 use crate::{
-    audio::CircularBuffer,
+    audio::{CircularBuffer, DftLut, LevelMeter, Levels},
     video::shaders::{Bass, Stabilization},
 };
 use glam::Vec2;
@@ -10,20 +10,100 @@ struct Consts {
     exp_bins: f32,
 }
 
-#[derive(Clone)]
-struct BinData {
-    window_start: usize,
-    window_weights: Vec<f32>,
-    complex_exponentials: Vec<Vec2>,
-    total_window: f32,
+/// The beats detected in the bass so far.
+#[derive(Clone, Copy, Default)]
+pub struct Beats {
+    pub count: u32,
+    /// Seconds since the last beat.
+    pub since_last: f32,
+    /// Seconds between the recent beats, 0 until two beats were close enough to tell.
+    pub interval: f32,
+}
+
+/// How the DFT bins map to frequencies.
+#[derive(Clone, Copy)]
+pub struct BinScale {
+    lowest_frequency: f32,
+    exp_bins: f32,
+}
+
+impl BinScale {
+    pub fn new(buffer_size: usize, bin_count: usize, sample_rate: u32) -> Self {
+        let buffer_size_f = buffer_size as f32;
+        Self {
+            lowest_frequency: sample_rate as f32 / buffer_size_f,
+            exp_bins: (bin_count as f32 / (buffer_size_f / 2.0).log2()).floor(),
+        }
+    }
+
+    /// The fractional bin of `frequency`, may be out of the DFT's range.
+    pub fn bin(&self, frequency: f32) -> f32 {
+        Analyzer::bin(self.exp_bins, self.lowest_frequency, frequency)
+    }
+
+    pub fn frequency(&self, bin: f32) -> f32 {
+        Analyzer::frequency(self.exp_bins, self.lowest_frequency, bin)
+    }
 }
 
 #[derive(Clone)]
 pub struct AudioData {
     pub samples: CircularBuffer<f32>,
+    pub sample_rate: u32,
     pub dft: Vec<Vec2>,
+    pub bin_scale: BinScale,
+    /// The samples pushed so far, to tell how old an analysis of them is.
+    pub position: u64,
     pub stabilization: Stabilization,
     pub bass: Bass,
+    pub beats: Beats,
+    pub levels: Levels,
+}
+
+/// Made-up audio data for tests, built from the analysis of silence.
+#[cfg(test)]
+impl AudioData {
+    pub fn silence(buffer_size: usize, bin_count: usize, sample_rate: u32) -> Self {
+        Analyzer::new(buffer_size, bin_count, sample_rate).analyze()
+    }
+
+    /// With `samples` as they are, without the automatic gain.
+    pub fn with_samples(mut self, samples: impl IntoIterator<Item = f32>) -> Self {
+        let data: Vec<_> = samples.into_iter().collect();
+        self.samples = CircularBuffer {
+            start: 0,
+            size: data.len(),
+            data,
+        };
+        self
+    }
+
+    /// With a spectrum of `magnitudes` at their bins and nothing in the others.
+    pub fn with_magnitudes(mut self, magnitudes: &[(usize, f32)]) -> Self {
+        self.dft.fill(Vec2::ZERO);
+        for &(bin, magnitude) in magnitudes {
+            self.dft[bin] = Vec2::new(magnitude, 0.0);
+        }
+        self
+    }
+
+    pub fn with_focus(mut self, focus: f32) -> Self {
+        self.stabilization.focus = focus;
+        self
+    }
+
+    /// With the tempo of beats `interval` seconds apart.
+    pub fn with_beat_interval(mut self, interval: f32) -> Self {
+        self.beats.interval = interval;
+        self
+    }
+}
+
+/// Where the analyzed DFT comes from.
+enum DftSource {
+    Cpu,
+    /// The latest DFT computed on the GPU and the position of its samples, if there is one yet.
+    Gpu(Option<(Vec<Vec2>, u64)>),
 }
 
 pub struct Analyzer {
@@ -35,26 +115,24 @@ pub struct Analyzer {
     exp_bins: f32,
 
     buffer: CircularBuffer<f32>,
-    dft_lut: Vec<BinData>,
+    position: u64,
+    dft_lut: DftLut,
+    dft_source: DftSource,
 
     gain: f32,
     since_last_analysis: u64,
 
     focus: f32,
     chrono: u64,
+    bass_average: f32,
+    previous_bass: f32,
+    since_beat: u64,
+    beats: Beats,
+    level_meter: LevelMeter,
     audio_data: Option<AudioData>,
 }
 
 impl Analyzer {
-    fn window(x: f32) -> f32 {
-        if x < -1.0 || x > 1.0 {
-            0.0
-        } else {
-            const A: f32 = 10.0;
-            (A * (1.0 - x * x).max(0.0).sqrt()).exp() * (-A).exp()
-        }
-    }
-
     fn bin(exp_bins: f32, lowest_frequency: f32, frequency: f32) -> f32 {
         exp_bins * (frequency / lowest_frequency).log2()
     }
@@ -64,59 +142,10 @@ impl Analyzer {
     }
 
     pub fn new(buffer_size: usize, bin_count: usize, sample_rate: u32) -> Self {
-        let mut dft_lut = vec![
-            BinData {
-                window_start: 0,
-                window_weights: Vec::new(),
-                complex_exponentials: Vec::new(),
-                total_window: 0.0,
-            };
-            bin_count
-        ];
-
-        let buffer_size_f = buffer_size as f32;
-        let bin_count_f = bin_count as f32;
-        let sample_rate_f = sample_rate as f32;
-
-        let lowest_frequency = sample_rate_f / buffer_size_f;
-        let exp_bins = (bin_count_f / (buffer_size_f / 2.0).log2()).floor();
-
-        for bin in 0..bin_count {
-            let frequency = Self::frequency(exp_bins, lowest_frequency, bin as f32);
-            let sample_period = sample_rate_f / frequency;
-            let phase_delta = PI * 2.0 / sample_period;
-            let window_size = (8.0 * sample_period).min(buffer_size_f);
-            let window_start_f = ((buffer_size_f - window_size) * 0.5).floor();
-            let window_end_f = ((buffer_size_f + window_size) * 0.5).ceil();
-
-            let window_start = window_start_f as usize;
-            let window_end = window_end_f as usize;
-            let window_len = window_end - window_start;
-
-            let mut window_weights = Vec::with_capacity(window_len);
-            let mut complex_exponentials = Vec::with_capacity(window_len);
-            let mut total_window = 0.0;
-
-            let initial_phase = phase_delta * window_start_f;
-
-            for i in 0..window_len {
-                let sample_index = window_start + i;
-                let cur_window =
-                    Self::window((sample_index as f32 * 2.0 - buffer_size_f) / window_size);
-                window_weights.push(cur_window);
-                total_window += cur_window;
-
-                let phase = initial_phase + phase_delta * i as f32;
-                complex_exponentials.push(Vec2::new(phase.cos(), phase.sin()));
-            }
-
-            dft_lut[bin] = BinData {
-                window_start,
-                window_weights,
-                complex_exponentials,
-                total_window,
-            };
-        }
+        let BinScale {
+            lowest_frequency,
+            exp_bins,
+        } = BinScale::new(buffer_size, bin_count, sample_rate);
 
         Self {
             buffer_size,
@@ -125,11 +154,18 @@ impl Analyzer {
             lowest_frequency,
             exp_bins,
             buffer: CircularBuffer::new(buffer_size, 0.0),
-            dft_lut,
+            position: 0,
+            dft_lut: DftLut::new(buffer_size, bin_count, sample_rate),
+            dft_source: DftSource::Cpu,
             gain: 1.0,
             since_last_analysis: 0,
             focus: 0.5,
             chrono: 0,
+            bass_average: 0.0,
+            previous_bass: 0.0,
+            since_beat: 0,
+            beats: Beats::default(),
+            level_meter: LevelMeter::new(sample_rate),
             audio_data: None,
         }
     }
@@ -143,12 +179,16 @@ impl Analyzer {
     }
 
     pub fn push(&mut self, new_sample: &f32) {
+        // the levels are of the input as it is, for calibrated meters
+        self.level_meter.push(*new_sample);
+
         self.gain += 0.00001;
         let volume = (new_sample * self.gain).abs();
         if volume > 1.0 {
             self.gain /= volume;
         }
         self.buffer.push(&(new_sample * self.gain));
+        self.position += 1;
         self.since_last_analysis += 1;
 
         self.audio_data = None;
@@ -158,11 +198,63 @@ impl Analyzer {
         &self.buffer
     }
 
+    /// Stops computing the DFT until [`use_cpu_dft`](Self::use_cpu_dft), the GPU computes it and
+    /// hands it over with [`use_gpu_dft`](Self::use_gpu_dft). It is still computed on the CPU
+    /// until the first one arrives.
+    pub fn expect_gpu_dft(&mut self) {
+        if let DftSource::Cpu = self.dft_source {
+            self.dft_source = DftSource::Gpu(None);
+            self.audio_data = None;
+        }
+    }
+
+    /// Analyzes `dft`, computed on the GPU from the samples up to `position`, instead of computing
+    /// the DFT until [`use_cpu_dft`](Self::use_cpu_dft).
+    pub fn use_gpu_dft(&mut self, dft: Vec<Vec2>, position: u64) {
+        if dft.len() == self.bin_count {
+            self.dft_source = DftSource::Gpu(Some((dft, position)));
+            self.audio_data = None;
+        }
+    }
+
+    pub fn use_cpu_dft(&mut self) {
+        self.dft_source = DftSource::Cpu;
+        self.audio_data = None;
+    }
+
     fn get_bass_eq(&self, bin: f32) -> f32 {
         let frequency = self.get_frequency(bin);
         (1.0 - frequency / 200.0).max(0.0)
     }
 
+    /// Counts a beat when the bass rises well above its average of the last half second, at
+    /// most four times a second.
+    fn detect_beat(&mut self, bass: f32) {
+        let sample_rate_f = self.sample_rate as f32;
+        self.since_beat += self.since_last_analysis;
+        let since_beat = self.since_beat as f32 / sample_rate_f;
+
+        let is_beat =
+            bass > self.bass_average * 1.4 + 0.05 && bass > self.previous_bass && since_beat > 0.25;
+        let rate = (self.since_last_analysis as f32 / (sample_rate_f * 0.5)).min(1.0);
+        self.bass_average += (bass - self.bass_average) * rate;
+        self.previous_bass = bass;
+
+        if is_beat {
+            // longer gaps are breaks in the music rather than its tempo
+            if since_beat < 2.0 {
+                self.beats.interval = if self.beats.interval == 0.0 {
+                    since_beat
+                } else {
+                    self.beats.interval * 0.8 + since_beat * 0.2
+                };
+            }
+            self.beats.count += 1;
+            self.since_beat = 0;
+        }
+        self.beats.since_last = self.since_beat as f32 / sample_rate_f;
+    }
+
     pub fn analyze(&mut self) -> AudioData {
         match &self.audio_data {
             Some(info) => info.clone(),
@@ -171,7 +263,16 @@ impl Analyzer {
                 let bin_count_f = self.bin_count as f32;
                 let sample_rate_f = self.sample_rate as f32;
 
-                let mut dft = vec![Vec2::new(0.0, 0.0); self.bin_count];
+                // the samples pushed since those the DFT was computed from
+                let (dft, behind) = match &self.dft_source {
+                    // on the CPU until the GPU has finished its first DFT
+                    DftSource::Cpu | DftSource::Gpu(None) => {
+                        (self.dft_lut.compute(&self.buffer), 0)
+                    }
+                    DftSource::Gpu(Some((dft, position))) => {
+                        (dft.clone(), self.position.saturating_sub(*position))
+                    }
+                };
 
                 let mut mx = 0.0;
                 let mut max_bin = 1;
@@ -184,16 +285,6 @@ impl Analyzer {
 
                 for bin in 0..self.bin_count {
                     let bin_f = bin as f32;
-
-                    let bin_data = &self.dft_lut[bin];
-                    let mut amplitude = Vec2::new(0.0, 0.0);
-
-                    for i in 0..bin_data.window_weights.len() {
-                        let sample_index = bin_data.window_start + i;
-                        let mult = self.buffer[sample_index] * bin_data.window_weights[i];
-                        amplitude += bin_data.complex_exponentials[i] * mult;
-                    }
-                    dft[bin] = amplitude / bin_data.total_window;
                     cur = dft[bin].length();
 
                     let bass_eq = self.get_bass_eq(bin_f);
@@ -214,17 +305,25 @@ impl Analyzer {
 
                 let bass = (bass_sum / bass_total * 10.0).clamp(0.0, 1.0);
                 self.chrono += ((self.since_last_analysis as f32) * bass) as u64;
+                self.detect_beat(bass);
                 self.since_last_analysis = 0;
 
                 let frequency = self.get_frequency(max_bin as f32);
                 let period = sample_rate_f / frequency;
                 let phase = dft[max_bin];
-                let angle = (phase.y.atan2(phase.x)) / (PI * 2.0) - 0.25;
+                // a steady tone has turned on since the samples of the DFT
+                let angle = (phase.y.atan2(phase.x)) / (PI * 2.0) - 0.25 - behind as f32 / period;
                 let center_sample = (angle + (buffer_size_f * self.focus / period).ceil()) * period;
 
                 let ans = AudioData {
                     samples: self.buffer.clone(),
+                    sample_rate: self.sample_rate,
                     dft,
+                    bin_scale: BinScale {
+                        lowest_frequency: self.lowest_frequency,
+                        exp_bins: self.exp_bins,
+                    },
+                    position: self.position,
                     stabilization: Stabilization {
                         period,
                         focus: self.focus,
@@ -234,6 +333,8 @@ impl Analyzer {
                         bass,
                         chrono: (self.chrono as f32) / sample_rate_f,
                     },
+                    beats: self.beats,
+                    levels: self.level_meter.take_levels(),
                 };
 
                 self.audio_data = Some(ans.clone());
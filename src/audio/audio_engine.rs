@@ -1,24 +1,27 @@
 use crate::{
     audio::{Analyzer, AudioData, Stream},
     config::Config,
+    error::LavaError,
 };
 
+use glam::Vec2;
+
 pub struct AudioEngine {
     stream: Stream,
     analyzer: Analyzer,
 }
 
 impl AudioEngine {
-    pub fn new(config: &Config) -> Self {
-        Self {
+    pub fn new(config: &Config) -> Result<Self, LavaError> {
+        Ok(Self {
             stream: Stream::new(
                 config.sample_rate,
                 config.channels,
                 config.fetch_buffer_size,
                 config.store_buffer_size,
-            ),
+            )?,
             analyzer: Analyzer::new(config.sample_count, config.bin_count, config.sample_rate),
-        }
+        })
     }
 
     pub fn update(&mut self) -> AudioData {
@@ -28,4 +31,18 @@ impl AudioEngine {
         }
         self.analyzer.analyze()
     }
+
+    /// See [`Analyzer::expect_gpu_dft`].
+    pub fn expect_gpu_dft(&mut self) {
+        self.analyzer.expect_gpu_dft();
+    }
+
+    /// See [`Analyzer::use_gpu_dft`].
+    pub fn use_gpu_dft(&mut self, dft: Vec<Vec2>, position: u64) {
+        self.analyzer.use_gpu_dft(dft, position);
+    }
+
+    pub fn use_cpu_dft(&mut self) {
+        self.analyzer.use_cpu_dft();
+    }
 }
@@ -0,0 +1,92 @@
+use crate::audio::{BinScale, CircularBuffer};
+use glam::Vec2;
+use std::f32::consts::PI;
+
+/// The samples a bin of the DFT sums over.
+#[derive(Clone)]
+pub struct BinData {
+    pub window_start: usize,
+    pub window_weights: Vec<f32>,
+    pub complex_exponentials: Vec<Vec2>,
+    pub total_window: f32,
+}
+
+/// The windowed log-frequency DFT of the analyzer's samples, with the windows and complex
+/// exponentials of every bin computed once.
+pub struct DftLut {
+    pub bins: Vec<BinData>,
+}
+
+impl DftLut {
+    fn window(x: f32) -> f32 {
+        if x < -1.0 || x > 1.0 {
+            0.0
+        } else {
+            const A: f32 = 10.0;
+            (A * (1.0 - x * x).max(0.0).sqrt()).exp() * (-A).exp()
+        }
+    }
+
+    pub fn new(buffer_size: usize, bin_count: usize, sample_rate: u32) -> Self {
+        let buffer_size_f = buffer_size as f32;
+        let sample_rate_f = sample_rate as f32;
+        let bin_scale = BinScale::new(buffer_size, bin_count, sample_rate);
+
+        let bins = (0..bin_count)
+            .map(|bin| {
+                let frequency = bin_scale.frequency(bin as f32);
+                let sample_period = sample_rate_f / frequency;
+                let phase_delta = PI * 2.0 / sample_period;
+                let window_size = (8.0 * sample_period).min(buffer_size_f);
+                let window_start_f = ((buffer_size_f - window_size) * 0.5).floor();
+                let window_end_f = ((buffer_size_f + window_size) * 0.5).ceil();
+
+                let window_start = window_start_f as usize;
+                let window_end = window_end_f as usize;
+                let window_len = window_end - window_start;
+
+                let mut window_weights = Vec::with_capacity(window_len);
+                let mut complex_exponentials = Vec::with_capacity(window_len);
+                let mut total_window = 0.0;
+
+                let initial_phase = phase_delta * window_start_f;
+
+                for i in 0..window_len {
+                    let sample_index = window_start + i;
+                    let cur_window =
+                        Self::window((sample_index as f32 * 2.0 - buffer_size_f) / window_size);
+                    window_weights.push(cur_window);
+                    total_window += cur_window;
+
+                    let phase = initial_phase + phase_delta * i as f32;
+                    complex_exponentials.push(Vec2::new(phase.cos(), phase.sin()));
+                }
+
+                BinData {
+                    window_start,
+                    window_weights,
+                    complex_exponentials,
+                    total_window,
+                }
+            })
+            .collect();
+
+        Self { bins }
+    }
+
+    /// The DFT of `samples` on the CPU.
+    pub fn compute(&self, samples: &CircularBuffer<f32>) -> Vec<Vec2> {
+        self.bins
+            .iter()
+            .map(|bin_data| {
+                let mut amplitude = Vec2::new(0.0, 0.0);
+                for i in 0..bin_data.window_weights.len() {
+                    let sample_index = bin_data.window_start + i;
+                    let mult = samples[sample_index] * bin_data.window_weights[i];
+                    amplitude += bin_data.complex_exponentials[i] * mult;
+                }
+                amplitude / bin_data.total_window
+            })
+            .collect()
+    }
+}
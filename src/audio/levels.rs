@@ -0,0 +1,226 @@
+use std::{collections::VecDeque, f64::consts::PI};
+
+/// The levels of the input before the automatic gain, in dBFS as in AES17 so that a full scale
+/// sine reads 0 dB whatever the ballistics, and its loudness in LUFS.
+#[derive(Clone, Copy)]
+pub struct Levels {
+    /// The highest sample since the previous analysis.
+    pub peak: f32,
+    /// Averaged over about 300 ms.
+    pub rms: f32,
+    /// Reads the RMS level of a sine, with the slow rise and fall of a VU meter.
+    pub vu: f32,
+    /// Rises within a few milliseconds and falls 20 dB in 1.5 s, like a type I PPM.
+    pub ppm: f32,
+    /// Over the last 400 ms, K-weighted as in ITU-R BS.1770.
+    pub lufs_momentary: f32,
+    /// Over the last 3 s.
+    pub lufs_short_term: f32,
+}
+
+/// The quietest level measured, in dB.
+const FLOOR: f32 = -100.0;
+
+fn amplitude_to_db(amplitude: f32) -> f32 {
+    (20.0 * amplitude.log10()).max(FLOOR)
+}
+
+fn mean_square_to_db(mean_square: f64) -> f32 {
+    ((10.0 * mean_square.log10()) as f32).max(FLOOR)
+}
+
+/// The rate of a one-pole filter with the time constant `tau` in seconds.
+fn one_pole_rate(tau: f32, sample_rate: f32) -> f32 {
+    1.0 - (-1.0 / (tau * sample_rate)).exp()
+}
+
+#[derive(Clone, Copy)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    state: [f64; 2],
+}
+
+impl Biquad {
+    fn new(b: [f64; 3], a: [f64; 2]) -> Self {
+        Self {
+            b,
+            a,
+            state: [0.0; 2],
+        }
+    }
+
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b[0] * x + self.state[0];
+        self.state[0] = self.b[1] * x - self.a[0] * y + self.state[1];
+        self.state[1] = self.b[2] * x - self.a[1] * y;
+        y
+    }
+}
+
+/// The K-weighting of BS.1770 at any sample rate: a high shelf for the head followed by a
+/// high pass.
+fn k_weighting(sample_rate: f64) -> [Biquad; 2] {
+    let f0 = 1681.974450955533;
+    let gain = 3.999843853973347;
+    let q = 0.7071752369554196;
+    let k = (PI * f0 / sample_rate).tan();
+    let vh = 10f64.powf(gain / 20.0);
+    let vb = vh.powf(0.4996667741545416);
+    let a0 = 1.0 + k / q + k * k;
+    let shelf = Biquad::new(
+        [
+            (vh + vb * k / q + k * k) / a0,
+            2.0 * (k * k - vh) / a0,
+            (vh - vb * k / q + k * k) / a0,
+        ],
+        [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+    );
+
+    let f0 = 38.13547087602444;
+    let q = 0.5003270373238773;
+    let k = (PI * f0 / sample_rate).tan();
+    let a0 = 1.0 + k / q + k * k;
+    let high_pass = Biquad::new(
+        [1.0, -2.0, 1.0],
+        [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+    );
+
+    [shelf, high_pass]
+}
+
+/// The mean squares of the K-weighted input are kept in blocks of 100 ms, the step of BS.1770.
+const BLOCK_SECONDS: f32 = 0.1;
+const MOMENTARY_BLOCKS: usize = 4;
+const SHORT_TERM_BLOCKS: usize = 30;
+
+/// Measures the levels of the input sample by sample.
+pub struct LevelMeter {
+    peak: f32,
+    mean_square: f32,
+    rms_rate: f32,
+    rectified: f32,
+    vu_rate: f32,
+    ppm: f32,
+    ppm_attack: f32,
+    ppm_release: f32,
+
+    k_weighting: [Biquad; 2],
+    block_sum: f64,
+    block_samples: usize,
+    block_length: usize,
+    /// The mean squares of the last blocks, newest last.
+    blocks: VecDeque<f64>,
+}
+
+impl LevelMeter {
+    pub fn new(sample_rate: u32) -> Self {
+        let sample_rate_f = sample_rate as f32;
+        Self {
+            peak: 0.0,
+            mean_square: 0.0,
+            rms_rate: one_pole_rate(0.1, sample_rate_f),
+            rectified: 0.0,
+            // 99% of a step in 300 ms
+            vu_rate: one_pole_rate(0.065, sample_rate_f),
+            ppm: 0.0,
+            ppm_attack: one_pole_rate(0.002, sample_rate_f),
+            ppm_release: 10f32.powf(-20.0 / 1.5 / 20.0 / sample_rate_f),
+            k_weighting: k_weighting(sample_rate as f64),
+            block_sum: 0.0,
+            block_samples: 0,
+            block_length: (BLOCK_SECONDS * sample_rate_f) as usize,
+            blocks: VecDeque::with_capacity(SHORT_TERM_BLOCKS),
+        }
+    }
+
+    pub fn push(&mut self, sample: f32) {
+        let amplitude = sample.abs();
+        self.peak = self.peak.max(amplitude);
+        self.mean_square += (sample * sample - self.mean_square) * self.rms_rate;
+        self.rectified += (amplitude - self.rectified) * self.vu_rate;
+        self.ppm = if amplitude > self.ppm {
+            self.ppm + (amplitude - self.ppm) * self.ppm_attack
+        } else {
+            self.ppm * self.ppm_release
+        };
+
+        let weighted = self
+            .k_weighting
+            .iter_mut()
+            .fold(sample as f64, |x, filter| filter.process(x));
+        self.block_sum += weighted * weighted;
+        self.block_samples += 1;
+        if self.block_samples >= self.block_length {
+            if self.blocks.len() == SHORT_TERM_BLOCKS {
+                self.blocks.pop_front();
+            }
+            self.blocks
+                .push_back(self.block_sum / self.block_samples as f64);
+            self.block_sum = 0.0;
+            self.block_samples = 0;
+        }
+    }
+
+    fn loudness(&self, blocks: usize) -> f32 {
+        let recent = self.blocks.iter().rev().take(blocks);
+        let count = recent.len().max(1) as f64;
+        -0.691 + mean_square_to_db(recent.sum::<f64>() / count)
+    }
+
+    /// The levels so far, starting a new peak.
+    pub fn take_levels(&mut self) -> Levels {
+        let levels = Levels {
+            peak: amplitude_to_db(self.peak),
+            // the mean square of a full scale sine is 1/2
+            rms: mean_square_to_db(2.0 * self.mean_square as f64),
+            // and its average rectified value 2/π
+            vu: amplitude_to_db(self.rectified * std::f32::consts::FRAC_PI_2),
+            ppm: amplitude_to_db(self.ppm),
+            lufs_momentary: self.loudness(MOMENTARY_BLOCKS),
+            lufs_short_term: self.loudness(SHORT_TERM_BLOCKS),
+        };
+        self.peak = 0.0;
+        levels
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::assert_near;
+
+    const SAMPLE_RATE: u32 = 48000;
+
+    fn push_seconds(meter: &mut LevelMeter, seconds: f32, signal: impl Fn(f32) -> f32) {
+        for i in 0..(seconds * SAMPLE_RATE as f32) as usize {
+            meter.push(signal(i as f32 / SAMPLE_RATE as f32));
+        }
+    }
+
+    #[test]
+    fn measures_a_sine() {
+        let mut meter = LevelMeter::new(SAMPLE_RATE);
+        // -20 dBFS at the frequency of the BS.1770 calibration
+        push_seconds(&mut meter, 2.0, |t| {
+            0.1 * (std::f32::consts::TAU * 997.0 * t).sin()
+        });
+        let levels = meter.take_levels();
+        assert_near(levels.peak, -20.0, 0.01);
+        assert_near(levels.rms, -20.0, 0.05);
+        assert_near(levels.vu, -20.0, 0.05);
+        assert_near(levels.lufs_momentary, -23.0, 0.05);
+        assert_near(levels.lufs_short_term, -23.0, 0.05);
+    }
+
+    #[test]
+    fn ppm_falls_20_db_in_1_5_s() {
+        let mut meter = LevelMeter::new(SAMPLE_RATE);
+        push_seconds(&mut meter, 0.1, |_| 0.5);
+        let before = meter.take_levels().ppm;
+        assert_near(before, amplitude_to_db(0.5), 0.01);
+
+        push_seconds(&mut meter, 1.5, |_| 0.0);
+        assert_near(meter.take_levels().ppm, before - 20.0, 0.05);
+    }
+}
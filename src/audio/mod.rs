@@ -1,9 +1,13 @@
 pub mod analyzer;
 pub mod circular_buffer;
+pub mod dft;
+pub mod levels;
 pub mod stream;
 pub mod audio_engine;
 
-pub use analyzer::{Analyzer, AudioData};
+pub use analyzer::{Analyzer, AudioData, Beats, BinScale};
 pub use circular_buffer::CircularBuffer;
+pub use dft::{BinData, DftLut};
+pub use levels::{LevelMeter, Levels};
 pub use stream::Stream;
 pub use audio_engine::AudioEngine;
@@ -1,4 +1,5 @@
 use super::CircularBuffer;
+use crate::error::LavaError;
 
 use cpal::{
     BufferSize::Fixed,
@@ -18,15 +19,18 @@ impl Stream {
         channels: u16,
         fetch_buffer_size: u32,
         store_buffer_size: usize,
-    ) -> Self {
-        let device = default_host()
-            .default_input_device()
-            .expect("No audio input devices available");
+    ) -> Result<Self, LavaError> {
+        let device = default_host().default_input_device().ok_or_else(|| {
+            LavaError::audio(
+                "opening the default input device",
+                "no audio input devices available",
+            )
+        })?;
 
-        println!(
-            "Using audio device: {}",
-            device.description().unwrap().name()
-        );
+        let description = device
+            .description()
+            .map_err(|e| LavaError::audio("querying the input device", e))?;
+        println!("Using audio device: {}", description.name());
 
         let config = StreamConfig {
             channels,
@@ -50,14 +54,21 @@ impl Stream {
                 |err| eprintln!("Stream error: {}", err),
                 None,
             )
-            .expect("failed to build stream");
+            .map_err(|e| {
+                LavaError::audio(
+                    format!("opening a {channels} channel input stream at {sample_rate} Hz"),
+                    e,
+                )
+            })?;
 
-        stream.play().expect("error playing stream");
+        stream
+            .play()
+            .map_err(|e| LavaError::audio("starting the input stream", e))?;
 
-        Self {
+        Ok(Self {
             buffer,
             _stream: stream,
-        }
+        })
     }
 
     pub fn get_samples(&mut self) -> Vec<f32> {
@@ -1,16 +1,40 @@
-use crate::video::{
-    Panel, PanelMaterial::Waveform, PanelTransform, shader_types::WaveformParameters,
+pub mod de;
+pub mod jsonc;
+pub mod validation;
+pub mod watcher;
+
+pub use watcher::ConfigWatcher;
+
+use crate::{
+    error::{ConfigIssue, LavaError},
+    video::{
+        Feedback, Palette, Panel, PanelMaterial, PanelTransform, PostEffect, TextureConfig,
+        Tonemap, material::registry, shader_types::WaveformParameters,
+    },
 };
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::{
+    collections::BTreeMap,
     fs,
     path::{Path, PathBuf},
 };
 use winit::dpi::LogicalSize;
 
-#[derive(Clone, Serialize, Deserialize)]
-#[serde(default)]
+#[derive(JsonSchema)]
+#[schemars(rename = "WindowSize")]
+struct WindowSizeSchema {
+    width: i32,
+    height: i32,
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
 pub struct Config {
+    /// Lets editors find the schema printed by `lava schema`, ignored by lava itself.
+    #[serde(rename = "$schema", skip_serializing_if = "Option::is_none")]
+    pub schema: Option<String>,
+
     pub channels: u16,
     pub fetch_buffer_size: u32,
     pub store_buffer_size: usize,
@@ -19,16 +43,30 @@ pub struct Config {
     pub bin_count: usize,
     pub sample_rate: u32,
 
+    #[schemars(with = "WindowSizeSchema")]
     pub window_size: LogicalSize<i32>,
     pub panels: Vec<Panel>,
     pub image_path: Option<PathBuf>,
+    /// Images that panels can pick by name instead of the one at `image_path`.
+    pub textures: BTreeMap<String, TextureConfig>,
+    /// Draws everything over the previous frames.
+    pub feedback: Option<Feedback>,
+    /// Full screen effects applied in order to everything drawn.
+    pub post: Vec<PostEffect>,
+    /// Brings the colors above 1 that the panels and effects draw into the range of the screen.
+    pub tonemap: Tonemap,
+    /// The colors of every rainbow pattern, a preset or OkLab color stops.
+    pub palette: Option<Palette>,
 
     pub frame_times: bool,
+    /// Computes the spectrum in a compute shader instead of on the CPU, when the GPU can.
+    pub gpu_dft: bool,
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
+            schema: None,
             channels: 1,
             fetch_buffer_size: 512,
             store_buffer_size: 2048,
@@ -37,32 +75,142 @@ impl Default for Config {
             sample_rate: 48000,
             window_size: LogicalSize::new(1920, 1080),
             panels: vec![Panel {
-                material: Waveform(WaveformParameters {
+                material: PanelMaterial::new(WaveformParameters {
                     gain: 0.75,
                     ..Default::default()
                 }),
                 transform: PanelTransform::FULLSCREEN,
+                ..Default::default()
             }],
             image_path: None,
+            textures: BTreeMap::new(),
+            feedback: None,
+            post: Vec::new(),
+            tonemap: Tonemap::default(),
+            palette: None,
             frame_times: false,
+            gpu_dft: false,
         }
     }
 }
 
 impl Config {
-    pub fn from_jsonc(path: &Path) -> Self {
-        let text = fs::read_to_string(path).unwrap();
+    /// Loads and validates a config, reporting every problem with its line and column.
+    pub fn from_jsonc(path: &Path) -> Result<Self, LavaError> {
+        let text = fs::read_to_string(path).map_err(|source| LavaError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
 
-        let parsed = json5::from_str(&text).unwrap();
+        let invalid = |issues| LavaError::Config {
+            path: path.to_path_buf(),
+            issues,
+        };
 
-        let mut config: Config = serde_json::from_value(parsed).unwrap();
+        let root = jsonc::parse(&text).map_err(|e| {
+            invalid(vec![ConfigIssue {
+                json_path: None,
+                location: Some((e.position.line, e.position.column)),
+                message: e.message,
+            }])
+        })?;
+
+        let mut config: Config = serde_path_to_error::deserialize(&root).map_err(|e| {
+            let json_path = e.path().to_string();
+            let inner = e.into_inner();
+            // materials are parsed without their positions, parse the failed one again to
+            // locate the error inside it
+            let material = json_path
+                .ends_with(".material")
+                .then(|| registry().parse_node(root.find(&json_path)).err())
+                .flatten();
+            invalid(vec![match material {
+                Some((inner_path, position, message)) => ConfigIssue {
+                    json_path: Some(if inner_path.is_empty() {
+                        json_path
+                    } else {
+                        format!("{json_path}.{inner_path}")
+                    }),
+                    location: position.map(|p| (p.line, p.column)),
+                    message,
+                },
+                None => ConfigIssue {
+                    json_path: (json_path != ".").then_some(json_path),
+                    location: inner.position.map(|p| (p.line, p.column)),
+                    message: inner.message,
+                },
+            }])
+        })?;
+
+        let directory = path.parent().unwrap_or(Path::new("."));
         if let Some(image_path) = config.image_path {
-            config.image_path = Some(path.to_path_buf().parent().unwrap().join(image_path));
+            config.image_path = Some(directory.join(image_path));
+        }
+        for texture in config.textures.values_mut() {
+            texture.path = directory.join(&texture.path);
+        }
+        for panel in &mut config.panels {
+            panel.material.resolve_paths(directory);
+        }
+
+        let mut issues = validation::validate(&config);
+        if let Some(image_path) = &config.image_path {
+            if !image_path.is_file() {
+                issues.push((
+                    "image_path".into(),
+                    format!("{} does not exist", image_path.display()),
+                ));
+            }
+        }
+        for (name, texture) in &config.textures {
+            if !texture.path.exists() {
+                issues.push((
+                    format!("textures.{name}.path"),
+                    format!("{} does not exist", texture.path.display()),
+                ));
+            }
+        }
+
+        if issues.is_empty() {
+            Ok(config)
+        } else {
+            Err(invalid(
+                issues
+                    .into_iter()
+                    .map(|(json_path, message)| {
+                        let position = root.find(&json_path).position;
+                        ConfigIssue {
+                            json_path: Some(json_path),
+                            location: Some((position.line, position.column)),
+                            message,
+                        }
+                    })
+                    .collect(),
+            ))
         }
-        config
     }
 
     pub fn to_jsonc(&self) -> String {
         serde_json::to_string(self).unwrap()
     }
+
+    /// Whether `other` can keep using an audio engine created for `self`.
+    pub fn same_audio_settings(&self, other: &Config) -> bool {
+        self.channels == other.channels
+            && self.fetch_buffer_size == other.fetch_buffer_size
+            && self.store_buffer_size == other.store_buffer_size
+            && self.same_analysis_settings(other)
+    }
+
+    /// Whether `other` can keep using shaders specialized for `self`.
+    pub fn same_analysis_settings(&self, other: &Config) -> bool {
+        self.sample_count == other.sample_count
+            && self.bin_count == other.bin_count
+            && self.sample_rate == other.sample_rate
+    }
+
+    /// The JSON Schema of the config format, with the defaults filled in.
+    pub fn schema() -> String {
+        serde_json::to_string_pretty(&schemars::schema_for!(Config)).unwrap()
+    }
 }
@@ -0,0 +1,292 @@
+use crate::config::jsonc::{Key, Node, Position, Value};
+
+use serde::de::{self, DeserializeSeed, IntoDeserializer, Visitor, value::StrDeserializer};
+use std::{fmt, slice};
+
+/// A deserialization error pointing at the innermost config value it was raised for.
+#[derive(Debug)]
+pub struct DeError {
+    pub message: String,
+    pub position: Option<Position>,
+}
+
+impl DeError {
+    fn at(mut self, position: Position) -> Self {
+        self.position.get_or_insert(position);
+        self
+    }
+}
+
+impl fmt::Display for DeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for DeError {}
+
+fn closest<'a>(name: &str, candidates: &[&'a str]) -> Option<&'a str> {
+    candidates
+        .iter()
+        .map(|candidate| (strsim::damerau_levenshtein(name, candidate), *candidate))
+        .filter(|(distance, _)| *distance <= (name.len() / 3).max(1))
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+pub(crate) fn unknown(what: &str, name: &str, expected: &[&str]) -> String {
+    match closest(name, expected) {
+        Some(suggestion) => format!("unknown {what} `{name}`, did you mean `{suggestion}`?"),
+        None if expected.is_empty() => format!("unknown {what} `{name}`, there are none"),
+        None => format!(
+            "unknown {what} `{name}`, expected one of {}",
+            expected
+                .iter()
+                .map(|e| format!("`{e}`"))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    }
+}
+
+impl de::Error for DeError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Self {
+            message: msg.to_string(),
+            position: None,
+        }
+    }
+
+    fn unknown_field(field: &str, expected: &'static [&'static str]) -> Self {
+        Self::custom(unknown("field", field, expected))
+    }
+
+    fn unknown_variant(variant: &str, expected: &'static [&'static str]) -> Self {
+        Self::custom(unknown("type", variant, expected))
+    }
+}
+
+impl<'de> de::Deserializer<'de> for &'de Node {
+    type Error = DeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        match &self.value {
+            Value::Null => visitor.visit_unit(),
+            Value::Bool(value) => visitor.visit_bool(*value),
+            Value::Integer(value) if *value >= 0 => visitor.visit_u64(*value as u64),
+            Value::Integer(value) => visitor.visit_i64(*value),
+            Value::Float(value) => visitor.visit_f64(*value),
+            Value::String(value) => visitor.visit_borrowed_str(value),
+            Value::Array(items) => {
+                let mut seq = SeqAccess {
+                    items: items.iter(),
+                };
+                visitor
+                    .visit_seq(&mut seq)
+                    .and_then(|value| match seq.items.len() {
+                        0 => Ok(value),
+                        remaining => Err(de::Error::invalid_length(
+                            items.len(),
+                            &format!("{} elements", items.len() - remaining).as_str(),
+                        )),
+                    })
+            }
+            Value::Object(entries) => visitor.visit_map(MapAccess {
+                entries: entries.iter(),
+                value: None,
+            }),
+        }
+        .map_err(|e| e.at(self.position))
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        match &self.value {
+            Value::Null => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+        .map_err(|e| e.at(self.position))
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, DeError> {
+        visitor
+            .visit_newtype_struct(self)
+            .map_err(|e| e.at(self.position))
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, DeError> {
+        match &self.value {
+            Value::String(variant) => {
+                let variant: StrDeserializer<DeError> = variant.as_str().into_deserializer();
+                visitor.visit_enum(variant)
+            }
+            _ => self.deserialize_any(visitor),
+        }
+        .map_err(|e: DeError| e.at(self.position))
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map struct
+        identifier ignored_any
+    }
+}
+
+impl<'de> de::Deserializer<'de> for &'de Key {
+    type Error = DeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        visitor
+            .visit_borrowed_str(&self.name)
+            .map_err(|e: DeError| e.at(self.position))
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+struct SeqAccess<'de> {
+    items: slice::Iter<'de, Node>,
+}
+
+impl<'de> de::SeqAccess<'de> for SeqAccess<'de> {
+    type Error = DeError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, DeError> {
+        match self.items.next() {
+            Some(node) => seed.deserialize(node).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.items.len())
+    }
+}
+
+struct MapAccess<'de> {
+    entries: slice::Iter<'de, (Key, Node)>,
+    value: Option<&'de Node>,
+}
+
+impl<'de> de::MapAccess<'de> for MapAccess<'de> {
+    type Error = DeError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, DeError> {
+        match self.entries.next() {
+            Some((key, node)) => {
+                self.value = Some(node);
+                seed.deserialize(key).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, DeError> {
+        seed.deserialize(self.value.take().expect("value requested before its key"))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.entries.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::jsonc::parse;
+
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize)]
+    #[serde(deny_unknown_fields)]
+    struct Parameters {
+        gain: f32,
+        #[serde(default)]
+        trigger: Trigger,
+    }
+
+    #[derive(Debug, Default, PartialEq, Deserialize)]
+    #[serde(rename_all = "snake_case")]
+    enum Trigger {
+        #[default]
+        Rising,
+        Falling,
+    }
+
+    fn deserialize_error(text: &str) -> (String, Option<(usize, usize)>) {
+        let root = parse(text).unwrap();
+        let e = Parameters::deserialize(&root).expect_err("the text should not deserialize");
+        (e.message, e.position.map(|p| (p.line, p.column)))
+    }
+
+    #[test]
+    fn suggests_the_closest_name() {
+        assert_eq!(
+            unknown("field", "gian", &["gain", "trigger"]),
+            "unknown field `gian`, did you mean `gain`?"
+        );
+        assert_eq!(
+            unknown("type", "sphere", &["bars", "meter"]),
+            "unknown type `sphere`, expected one of `bars`, `meter`"
+        );
+        assert_eq!(
+            unknown("field", "gain", &[]),
+            "unknown field `gain`, there are none"
+        );
+    }
+
+    #[test]
+    fn deserializes_values() {
+        let root = parse("{ gain: 2, trigger: 'falling' }").unwrap();
+        let parameters = Parameters::deserialize(&root).unwrap();
+        assert_eq!(parameters.gain, 2.0);
+        assert_eq!(parameters.trigger, Trigger::Falling);
+    }
+
+    #[test]
+    fn locates_errors() {
+        assert_eq!(
+            deserialize_error("{\n  gian: 1,\n}"),
+            (
+                "unknown field `gian`, did you mean `gain`?".into(),
+                Some((2, 3))
+            )
+        );
+        assert_eq!(
+            deserialize_error("{ gain: 1, trigger: \"rsing\" }"),
+            (
+                "unknown type `rsing`, did you mean `rising`?".into(),
+                Some((1, 21))
+            )
+        );
+        assert_eq!(
+            deserialize_error("{\n  gain: \"loud\",\n}"),
+            (
+                "invalid type: string \"loud\", expected f32".into(),
+                Some((2, 9))
+            )
+        );
+        assert_eq!(
+            deserialize_error("{}"),
+            ("missing field `gain`".into(), Some((1, 1)))
+        );
+    }
+}
@@ -0,0 +1,566 @@
+use std::{fmt, iter::Peekable, str::Chars};
+
+/// A line and column in the config source, both starting at 1.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+#[derive(Debug)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Integer(i64),
+    Float(f64),
+    String(String),
+    Array(Vec<Node>),
+    Object(Vec<(Key, Node)>),
+}
+
+#[derive(Debug)]
+pub struct Key {
+    pub name: String,
+    pub position: Position,
+}
+
+/// A parsed JSONC value that remembers where it was written.
+#[derive(Debug)]
+pub struct Node {
+    pub value: Value,
+    pub position: Position,
+}
+
+#[derive(Debug)]
+pub struct SyntaxError {
+    pub message: String,
+    pub position: Position,
+}
+
+impl fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Node {
+    /// Finds the deepest node along a path like `panels[0].material.parameters`.
+    ///
+    /// Stops at the last node that exists, so values filled in by defaults resolve to their
+    /// closest written parent.
+    pub fn find(&self, json_path: &str) -> &Node {
+        let mut node = self;
+        for segment in json_path.split('.').filter(|s| !s.is_empty()) {
+            let (name, indices) = match segment.find('[') {
+                Some(i) => (&segment[..i], &segment[i..]),
+                None => (segment, ""),
+            };
+            if !name.is_empty() {
+                match node.get(name) {
+                    Some(child) => node = child,
+                    None => return node,
+                }
+            }
+            for index in indices
+                .split(['[', ']'])
+                .filter_map(|i| i.parse::<usize>().ok())
+            {
+                match &node.value {
+                    Value::Array(items) if index < items.len() => node = &items[index],
+                    _ => return node,
+                }
+            }
+        }
+        node
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Node> {
+        match &self.value {
+            Value::Object(entries) => entries
+                .iter()
+                .find(|(k, _)| k.name == key)
+                .map(|(_, node)| node),
+            _ => None,
+        }
+    }
+}
+
+/// Parses JSON with the JSON5 extensions used in configs: comments, trailing commas,
+/// unquoted keys, single-quoted strings, hexadecimal and signed numbers.
+pub fn parse(text: &str) -> Result<Node, SyntaxError> {
+    let mut parser = Parser {
+        chars: text.chars().peekable(),
+        position: Position { line: 1, column: 1 },
+    };
+    let node = parser.value()?;
+    parser.skip_whitespace()?;
+    match parser.chars.peek().copied() {
+        None => Ok(node),
+        Some(c) => Err(parser.error(format!("unexpected `{c}` after the end of the config"))),
+    }
+}
+
+struct Parser<'a> {
+    chars: Peekable<Chars<'a>>,
+    position: Position,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: impl Into<String>) -> SyntaxError {
+        SyntaxError {
+            message: message.into(),
+            position: self.position,
+        }
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.chars.next()?;
+        if c == '\n' {
+            self.position.line += 1;
+            self.position.column = 1;
+        } else {
+            self.position.column += 1;
+        }
+        Some(c)
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), SyntaxError> {
+        match self.chars.peek() {
+            Some(&c) if c == expected => {
+                self.next();
+                Ok(())
+            }
+            Some(&c) => Err(self.error(format!("expected `{expected}`, found `{c}`"))),
+            None => Err(self.error(format!("expected `{expected}`, found end of file"))),
+        }
+    }
+
+    fn skip_whitespace(&mut self) -> Result<(), SyntaxError> {
+        loop {
+            match self.chars.peek() {
+                Some(c) if c.is_whitespace() => {
+                    self.next();
+                }
+                Some('/') => {
+                    let start = self.position;
+                    self.next();
+                    match self.next() {
+                        Some('/') => while self.next().is_some_and(|c| c != '\n') {},
+                        Some('*') => {
+                            let mut previous = ' ';
+                            loop {
+                                match self.next() {
+                                    Some('/') if previous == '*' => break,
+                                    Some(c) => previous = c,
+                                    None => {
+                                        return Err(SyntaxError {
+                                            message: "unterminated block comment".into(),
+                                            position: start,
+                                        });
+                                    }
+                                }
+                            }
+                        }
+                        _ => {
+                            return Err(SyntaxError {
+                                message: "expected a comment after `/`".into(),
+                                position: start,
+                            });
+                        }
+                    }
+                }
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    fn value(&mut self) -> Result<Node, SyntaxError> {
+        self.skip_whitespace()?;
+        let position = self.position;
+        let value = match self.chars.peek().copied() {
+            Some('{') => self.object()?,
+            Some('[') => self.array()?,
+            Some('"') | Some('\'') => Value::String(self.string()?),
+            Some(c) if c.is_ascii_digit() || matches!(c, '-' | '+' | '.') => self.number()?,
+            Some(c) if is_identifier_start(c) => match self.identifier().as_str() {
+                "null" => Value::Null,
+                "true" => Value::Bool(true),
+                "false" => Value::Bool(false),
+                "Infinity" | "NaN" => {
+                    return Err(SyntaxError {
+                        message: "non-finite numbers are not supported".into(),
+                        position,
+                    });
+                }
+                other => {
+                    return Err(SyntaxError {
+                        message: format!("unexpected `{other}`, expected a value"),
+                        position,
+                    });
+                }
+            },
+            Some(c) => return Err(self.error(format!("unexpected `{c}`, expected a value"))),
+            None => return Err(self.error("unexpected end of file, expected a value")),
+        };
+        Ok(Node { value, position })
+    }
+
+    fn object(&mut self) -> Result<Value, SyntaxError> {
+        self.expect('{')?;
+        let mut entries = Vec::new();
+        loop {
+            self.skip_whitespace()?;
+            let position = self.position;
+            let name = match self.chars.peek().copied() {
+                Some('}') => break,
+                Some('"') | Some('\'') => self.string()?,
+                Some(c) if is_identifier_start(c) => self.identifier(),
+                Some(c) => return Err(self.error(format!("unexpected `{c}`, expected a key"))),
+                None => return Err(self.error("unexpected end of file, expected a key")),
+            };
+            self.skip_whitespace()?;
+            self.expect(':')?;
+            let node = self.value()?;
+            entries.push((Key { name, position }, node));
+
+            self.skip_whitespace()?;
+            match self.chars.peek() {
+                Some(',') => {
+                    self.next();
+                }
+                _ => break,
+            }
+        }
+        self.skip_whitespace()?;
+        self.expect('}')?;
+        Ok(Value::Object(entries))
+    }
+
+    fn array(&mut self) -> Result<Value, SyntaxError> {
+        self.expect('[')?;
+        let mut items = Vec::new();
+        loop {
+            self.skip_whitespace()?;
+            if self.chars.peek() == Some(&']') {
+                break;
+            }
+            items.push(self.value()?);
+
+            self.skip_whitespace()?;
+            match self.chars.peek() {
+                Some(',') => {
+                    self.next();
+                }
+                _ => break,
+            }
+        }
+        self.skip_whitespace()?;
+        self.expect(']')?;
+        Ok(Value::Array(items))
+    }
+
+    fn string(&mut self) -> Result<String, SyntaxError> {
+        let start = self.position;
+        let Some(quote) = self.next() else {
+            return Err(self.error("unexpected end of file, expected a string"));
+        };
+        let mut result = String::new();
+        loop {
+            match self.next() {
+                Some(c) if c == quote => return Ok(result),
+                Some('\\') => {
+                    let escape = self.position;
+                    match self.next() {
+                        Some('n') => result.push('\n'),
+                        Some('t') => result.push('\t'),
+                        Some('r') => result.push('\r'),
+                        Some('b') => result.push('\u{8}'),
+                        Some('f') => result.push('\u{c}'),
+                        Some('v') => result.push('\u{b}'),
+                        Some('0') => result.push('\0'),
+                        Some('\n') => {}
+                        Some('u') => result.push(self.unicode_escape(escape)?),
+                        Some(c) => result.push(c),
+                        None => break,
+                    }
+                }
+                Some('\n') => {
+                    return Err(SyntaxError {
+                        message: "unterminated string".into(),
+                        position: start,
+                    });
+                }
+                Some(c) => result.push(c),
+                None => break,
+            }
+        }
+        Err(SyntaxError {
+            message: "unterminated string".into(),
+            position: start,
+        })
+    }
+
+    /// The character of a `\u` escape starting at `escape`, whose `u` was just read.
+    fn unicode_escape(&mut self, escape: Position) -> Result<char, SyntaxError> {
+        let invalid = || SyntaxError {
+            message: "invalid unicode escape".into(),
+            position: escape,
+        };
+        let high = self.hex_digits().ok_or_else(invalid)?;
+        let code = if (0xd800..0xdc00).contains(&high) {
+            // characters outside the basic plane are escaped as a UTF-16 surrogate pair
+            if self.next() != Some('\\') || self.next() != Some('u') {
+                return Err(invalid());
+            }
+            let low = self
+                .hex_digits()
+                .filter(|low| (0xdc00..0xe000).contains(low))
+                .ok_or_else(invalid)?;
+            0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00)
+        } else {
+            high
+        };
+        char::from_u32(code).ok_or_else(invalid)
+    }
+
+    fn hex_digits(&mut self) -> Option<u32> {
+        let hex = (0..4).filter_map(|_| self.next()).collect::<String>();
+        if hex.len() == 4 && hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            u32::from_str_radix(&hex, 16).ok()
+        } else {
+            None
+        }
+    }
+
+    fn identifier(&mut self) -> String {
+        let mut result = String::new();
+        while let Some(&c) = self.chars.peek() {
+            if !(is_identifier_start(c) || c.is_ascii_digit()) {
+                break;
+            }
+            result.push(c);
+            self.next();
+        }
+        result
+    }
+
+    fn number(&mut self) -> Result<Value, SyntaxError> {
+        let position = self.position;
+        let mut text = String::new();
+        while let Some(&c) = self.chars.peek() {
+            if !(c.is_ascii_alphanumeric() || matches!(c, '-' | '+' | '.')) {
+                break;
+            }
+            text.push(c);
+            self.next();
+        }
+
+        let invalid = || SyntaxError {
+            message: format!("invalid number `{text}`"),
+            position,
+        };
+        let (negative, digits) = match text.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, text.strip_prefix('+').unwrap_or(&text)),
+        };
+
+        if let Some(hex) = digits
+            .strip_prefix("0x")
+            .or_else(|| digits.strip_prefix("0X"))
+        {
+            let value = i64::from_str_radix(hex, 16).map_err(|_| invalid())?;
+            return Ok(Value::Integer(if negative { -value } else { value }));
+        }
+        if digits == "Infinity" || digits == "NaN" {
+            return Err(SyntaxError {
+                message: "non-finite numbers are not supported".into(),
+                position,
+            });
+        }
+        if !digits.contains(['.', 'e', 'E'])
+            && let Ok(value) = digits.parse::<i64>()
+        {
+            return Ok(Value::Integer(if negative { -value } else { value }));
+        }
+        let value = digits.parse::<f64>().map_err(|_| invalid())?;
+        Ok(Value::Float(if negative { -value } else { value }))
+    }
+}
+
+fn is_identifier_start(c: char) -> bool {
+    c.is_alphabetic() || c == '_' || c == '$'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn position(line: usize, column: usize) -> Position {
+        Position { line, column }
+    }
+
+    fn error(text: &str) -> SyntaxError {
+        parse(text).expect_err("the text should not parse")
+    }
+
+    fn integer(node: &Node) -> i64 {
+        match node.value {
+            Value::Integer(value) => value,
+            ref other => panic!("expected an integer, got {other:?}"),
+        }
+    }
+
+    fn float(node: &Node) -> f64 {
+        match node.value {
+            Value::Float(value) => value,
+            ref other => panic!("expected a float, got {other:?}"),
+        }
+    }
+
+    fn string(node: &Node) -> &str {
+        match &node.value {
+            Value::String(value) => value,
+            other => panic!("expected a string, got {other:?}"),
+        }
+    }
+
+    fn items(node: &Node) -> &[Node] {
+        match &node.value {
+            Value::Array(items) => items,
+            other => panic!("expected an array, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn skips_comments() {
+        let root = parse(
+            "// a line comment\n{\n  /* a block\n  comment */ \"a\": 1, // after a value\n  b: /**/ 2\n}\n// at the end",
+        )
+        .unwrap();
+        assert_eq!(integer(root.get("a").unwrap()), 1);
+        assert_eq!(integer(root.get("b").unwrap()), 2);
+        assert_eq!(root.get("b").unwrap().position, position(5, 11));
+    }
+
+    #[test]
+    fn accepts_trailing_commas() {
+        let root = parse("{ \"a\": [1, 2, ], \"b\": { \"c\": 3, }, }").unwrap();
+        assert_eq!(items(root.get("a").unwrap()).len(), 2);
+        assert_eq!(integer(root.get("b").unwrap().get("c").unwrap()), 3);
+    }
+
+    #[test]
+    fn parses_numbers() {
+        let root = parse("[-5, +3, 0x1F, -0x10, 1.5e3, 2E-2, -.5, 7e+1, 42]").unwrap();
+        let items = items(&root);
+        assert_eq!(integer(&items[0]), -5);
+        assert_eq!(integer(&items[1]), 3);
+        assert_eq!(integer(&items[2]), 31);
+        assert_eq!(integer(&items[3]), -16);
+        assert_eq!(float(&items[4]), 1500.0);
+        assert_eq!(float(&items[5]), 0.02);
+        assert_eq!(float(&items[6]), -0.5);
+        assert_eq!(float(&items[7]), 70.0);
+        assert_eq!(integer(&items[8]), 42);
+    }
+
+    #[test]
+    fn rejects_invalid_numbers() {
+        let e = error("{ \"a\": 1.2.3 }");
+        assert_eq!(e.message, "invalid number `1.2.3`");
+        assert_eq!(e.position, position(1, 8));
+        assert_eq!(
+            error("[-Infinity]").message,
+            "non-finite numbers are not supported"
+        );
+    }
+
+    #[test]
+    fn unescapes_strings() {
+        let root = parse(
+            r#"["a\nb\t\"c\\", 'it\'s', "é😀", "line\
+continued"]"#,
+        )
+        .unwrap();
+        let items = items(&root);
+        assert_eq!(string(&items[0]), "a\nb\t\"c\\");
+        assert_eq!(string(&items[1]), "it's");
+        assert_eq!(string(&items[2]), "é😀");
+        assert_eq!(string(&items[3]), "linecontinued");
+    }
+
+    #[test]
+    fn rejects_invalid_unicode_escapes() {
+        for text in [r#""\ud83d""#, r#""\ud83dA""#, r#""\udc00""#, r#""\u00g0""#] {
+            let e = error(text);
+            assert_eq!(e.message, "invalid unicode escape", "{text}");
+            assert_eq!(e.position, position(1, 3), "{text}");
+        }
+    }
+
+    #[test]
+    fn reports_error_positions() {
+        let e = error("{\n  \"a\": 1,\n  \"b\": tru\n}");
+        assert_eq!(e.message, "unexpected `tru`, expected a value");
+        assert_eq!(e.position, position(3, 8));
+
+        let e = error("{\n  \"a\": 1\n  \"b\": 2\n}");
+        assert_eq!(e.message, "expected `}`, found `\"`");
+        assert_eq!(e.position, position(3, 3));
+
+        let e = error("{ \"a\": \"open\n}");
+        assert_eq!(e.message, "unterminated string");
+        assert_eq!(e.position, position(1, 8));
+
+        let e = error("[1] /* never closed");
+        assert_eq!(e.message, "unterminated block comment");
+        assert_eq!(e.position, position(1, 5));
+
+        let e = error("[1] 2");
+        assert_eq!(e.message, "unexpected `2` after the end of the config");
+        assert_eq!(e.position, position(1, 5));
+
+        let e = error("{ \"a\": ");
+        assert_eq!(e.message, "unexpected end of file, expected a value");
+        assert_eq!(e.position, position(1, 8));
+    }
+
+    #[test]
+    fn finds_nested_paths() {
+        let root = parse(
+            r#"{
+  "panels": [
+    { "material": { "type": "meter" } },
+    {
+      "material": {
+        "type": "waveform",
+        "parameters": { "gain": 0.5, "pattern": { "scale": 2 } },
+      },
+    },
+  ],
+}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            root.find("panels[1].material.parameters.gain").position,
+            position(7, 33)
+        );
+        assert_eq!(
+            root.find("panels[1].material.parameters.pattern.scale")
+                .position,
+            position(7, 60)
+        );
+        // values that aren't written resolve to their closest written parent
+        assert_eq!(
+            root.find("panels[1].material.parameters.line_width")
+                .position,
+            position(7, 23)
+        );
+        assert_eq!(
+            root.find("panels[0].material.parameters").position,
+            position(3, 19)
+        );
+        assert_eq!(root.find("panels[5]").position, position(2, 13));
+        assert_eq!(root.find(".").position, position(1, 1));
+    }
+}
@@ -0,0 +1,149 @@
+use crate::{
+    config::Config,
+    video::{
+        Feedback, Palette,
+        material::{GlobalInput, MaterialShader},
+    },
+};
+
+/// Checks the values that parse fine but can't work, returning `(json_path, message)` pairs.
+pub fn validate(config: &Config) -> Vec<(String, String)> {
+    let mut issues = Vec::new();
+    let mut issue = |json_path: String, message: String| issues.push((json_path, message));
+
+    if config.channels == 0 {
+        issue("channels".into(), "must be at least 1".into());
+    }
+    if config.fetch_buffer_size == 0 {
+        issue("fetch_buffer_size".into(), "must be at least 1".into());
+    }
+    if config.store_buffer_size < config.fetch_buffer_size as usize {
+        issue(
+            "store_buffer_size".into(),
+            format!(
+                "must be at least `fetch_buffer_size` ({}), otherwise samples are dropped",
+                config.fetch_buffer_size
+            ),
+        );
+    }
+    if config.sample_rate == 0 {
+        issue("sample_rate".into(), "must be at least 1".into());
+    }
+
+    if !config.sample_count.is_power_of_two() || config.sample_count < 4 {
+        issue(
+            "sample_count".into(),
+            format!(
+                "must be a power of two of at least 4, got {} (nearest: {})",
+                config.sample_count,
+                config.sample_count.max(4).next_power_of_two()
+            ),
+        );
+    } else {
+        // the analyzer spreads the bins over the octaves between the lowest frequency and
+        // nyquist, it needs at least one bin per octave and can't resolve more bins than a
+        // linear DFT of the same size
+        let min_bins = (config.sample_count / 2).ilog2() as usize;
+        let max_bins = config.sample_count / 2;
+        if config.bin_count < min_bins || config.bin_count > max_bins {
+            issue(
+                "bin_count".into(),
+                format!(
+                    "must be between {min_bins} and {max_bins} for a sample_count of {}, got {}",
+                    config.sample_count, config.bin_count
+                ),
+            );
+        }
+    }
+
+    if config.window_size.width <= 0 || config.window_size.height <= 0 {
+        issue(
+            "window_size".into(),
+            "width and height must be positive".into(),
+        );
+    }
+
+    for (i, panel) in config.panels.iter().enumerate() {
+        // user shaders only get the image if there is one, whether they read it is checked when
+        // they are compiled
+        let needs_image = panel.material.inputs().contains(&GlobalInput::Image)
+            && !matches!(panel.material.shader(), MaterialShader::File { .. });
+        match panel.material.image() {
+            Some(name) if needs_image && !config.textures.contains_key(name) => issue(
+                format!("panels[{i}].material.parameters.image"),
+                format!("there is no texture named `{name}` in `textures`"),
+            ),
+            None if needs_image && config.image_path.is_none() => issue(
+                format!("panels[{i}].material"),
+                "this material needs `image_path` or an `image` from `textures` to be set".into(),
+            ),
+            _ => {}
+        }
+        for (field, message) in panel.material.validate() {
+            issue(format!("panels[{i}].material.parameters.{field}"), message);
+        }
+        if !(0.0..=1.0).contains(&panel.opacity) {
+            issue(
+                format!("panels[{i}].opacity"),
+                "must be between 0 and 1".into(),
+            );
+        }
+        for (field, message) in panel.feedback.iter().flat_map(Feedback::validate) {
+            issue(format!("panels[{i}].feedback.{field}"), message);
+        }
+        let duplicate_id = panel.id.as_ref().filter(|id| {
+            config.panels[..i]
+                .iter()
+                .any(|other| other.id.as_ref() == Some(*id))
+        });
+        if let Some(id) = duplicate_id {
+            issue(
+                format!("panels[{i}].id"),
+                format!("another panel already has the id `{id}`"),
+            );
+        }
+        if let Some(mask) = &panel.mask {
+            if panel.id.as_ref() == Some(&mask.panel) {
+                issue(
+                    format!("panels[{i}].mask.panel"),
+                    "a panel can't mask itself".into(),
+                );
+            } else if !config
+                .panels
+                .iter()
+                .any(|other| other.id.as_ref() == Some(&mask.panel))
+            {
+                issue(
+                    format!("panels[{i}].mask.panel"),
+                    format!("no panel has the id `{}`", mask.panel),
+                );
+            }
+        }
+    }
+
+    for (name, texture) in &config.textures {
+        for (field, message) in texture.validate() {
+            issue(format!("textures.{name}.{field}"), message);
+        }
+    }
+
+    for (field, message) in config.feedback.iter().flat_map(Feedback::validate) {
+        issue(format!("feedback.{field}"), message);
+    }
+
+    for (field, message) in config.palette.iter().flat_map(Palette::validate) {
+        issue(format!("palette{field}"), message);
+    }
+
+    for (i, effect) in config.post.iter().enumerate() {
+        for (field, message) in effect.validate() {
+            issue(format!("post[{i}].parameters.{field}"), message);
+        }
+    }
+
+    for (field, message) in config.tonemap.validate() {
+        issue(format!("tonemap.{field}"), message);
+    }
+
+    issues
+}
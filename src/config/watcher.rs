@@ -0,0 +1,72 @@
+use crate::{config::Config, error::LavaError};
+
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::{
+    path::{Path, PathBuf},
+    sync::mpsc::{Receiver, channel},
+};
+
+/// Reloads a config file whenever it changes on disk.
+pub struct ConfigWatcher {
+    path: PathBuf,
+    events: Receiver<notify::Result<Event>>,
+    _watcher: RecommendedWatcher,
+}
+
+impl ConfigWatcher {
+    pub fn new(path: &Path) -> Result<Self, LavaError> {
+        let watch_error = |e: notify::Error| LavaError::Watch {
+            path: path.to_path_buf(),
+            message: e.to_string(),
+        };
+
+        let (sender, events) = channel();
+        let mut watcher = notify::recommended_watcher(sender).map_err(watch_error)?;
+
+        // editors often save by replacing the file, so watch the directory instead of the file
+        let directory = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        watcher
+            .watch(directory, RecursiveMode::NonRecursive)
+            .map_err(watch_error)?;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            events,
+            _watcher: watcher,
+        })
+    }
+
+    /// Returns the new config if the file changed since the last call and is still valid.
+    ///
+    /// Invalid edits are printed and skipped, so the caller keeps running the previous config.
+    pub fn poll(&mut self) -> Option<Config> {
+        let mut changed = false;
+        while let Ok(event) = self.events.try_recv() {
+            match event {
+                Ok(event) => {
+                    changed |= matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_))
+                        && event
+                            .paths
+                            .iter()
+                            .any(|p| p.file_name() == self.path.file_name());
+                }
+                Err(e) => eprintln!("could not watch {}: {e}", self.path.display()),
+            }
+        }
+        if !changed {
+            return None;
+        }
+
+        match Config::from_jsonc(&self.path) {
+            Ok(config) => Some(config),
+            Err(e) => {
+                eprintln!("{e}");
+                eprintln!("keeping the previous config");
+                None
+            }
+        }
+    }
+}
@@ -0,0 +1,100 @@
+use std::{error::Error, fmt, io, path::PathBuf};
+
+#[derive(Debug)]
+pub struct ConfigIssue {
+    pub json_path: Option<String>,
+    /// Line and column, both starting at 1.
+    pub location: Option<(usize, usize)>,
+    pub message: String,
+}
+
+#[derive(Debug)]
+pub enum LavaError {
+    /// A file could not be read.
+    Io { path: PathBuf, source: io::Error },
+    /// The config file is not valid JSONC, does not match the config format or has values out
+    /// of range.
+    Config {
+        path: PathBuf,
+        issues: Vec<ConfigIssue>,
+    },
+    /// The audio input could not be opened or started.
+    Audio { action: String, message: String },
+    /// Vulkan initialization or resource creation failed.
+    Gpu { action: String, message: String },
+    /// An image or other file referenced by the config could not be loaded.
+    Asset { path: PathBuf, message: String },
+    /// A file could not be watched for changes.
+    Watch { path: PathBuf, message: String },
+    /// A shader source failed to compile at runtime.
+    Shader { path: PathBuf, message: String },
+}
+
+impl LavaError {
+    pub fn audio(action: impl Into<String>, error: impl fmt::Display) -> Self {
+        Self::Audio {
+            action: action.into(),
+            message: error.to_string(),
+        }
+    }
+
+    pub fn gpu(action: impl Into<String>, error: impl fmt::Display) -> Self {
+        Self::Gpu {
+            action: action.into(),
+            message: error.to_string(),
+        }
+    }
+
+    pub fn asset(path: impl Into<PathBuf>, error: impl fmt::Display) -> Self {
+        Self::Asset {
+            path: path.into(),
+            message: error.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for LavaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io { path, source } => write!(f, "could not read {}: {source}", path.display()),
+            Self::Config { path, issues } => {
+                for (i, issue) in issues.iter().enumerate() {
+                    if i > 0 {
+                        writeln!(f)?;
+                    }
+                    write!(f, "{}", path.display())?;
+                    if let Some((line, column)) = issue.location {
+                        write!(f, ":{line}:{column}")?;
+                    }
+                    if let Some(json_path) = &issue.json_path {
+                        write!(f, ": at `{json_path}`")?;
+                    }
+                    write!(f, ": {}", issue.message)?;
+                }
+                Ok(())
+            }
+            Self::Audio { action, message } => {
+                write!(f, "audio error while {action}: {message}")
+            }
+            Self::Gpu { action, message } => write!(f, "GPU error while {action}: {message}"),
+            Self::Asset { path, message } => {
+                write!(f, "could not load {}: {message}", path.display())
+            }
+            Self::Watch { path, message } => {
+                write!(f, "could not watch {}: {message}", path.display())
+            }
+            Self::Shader { path, message } => {
+                write!(f, "could not compile {}:\n{message}", path.display())
+            }
+        }
+    }
+}
+
+impl Error for LavaError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,14 @@
+pub mod app;
+pub mod audio;
+pub mod config;
+pub mod error;
+pub mod stats;
+#[cfg(test)]
+mod testing;
+pub mod video;
+
+pub use app::App;
+pub use audio::{Analyzer, AudioData, AudioEngine};
+pub use config::Config;
+pub use error::{ConfigIssue, LavaError};
+pub use video::{Panel, PanelMaterial, RenderContext, Renderer};
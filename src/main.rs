@@ -1,31 +1,98 @@
-mod app;
-mod audio;
-mod config;
-mod stats;
-mod video;
-
-use app::App;
-use std::{error::Error, path::Path};
+use lava::{App, Config, LavaError, video::ShaderCompiler};
+use std::{path::Path, process::ExitCode};
 use winit::event_loop::{ControlFlow, EventLoop};
 
-use crate::config::Config;
+fn run(
+    path: Option<&Path>,
+    dev_shaders: bool,
+    bench_frames: Option<usize>,
+) -> Result<(), LavaError> {
+    let config = match path {
+        Some(path) => Config::from_jsonc(path)?,
+        None => Config::default(),
+    };
+
+    let event_loop = EventLoop::new().map_err(|e| LavaError::gpu("creating the event loop", e))?;
+    event_loop.set_control_flow(ControlFlow::Poll);
+
+    let mut app = App::new(&event_loop, &config)?;
+    if let Some(path) = path {
+        app.watch_config(path)?;
+    }
+    if dev_shaders {
+        app.use_shader_sources(Path::new(ShaderCompiler::SOURCE_DIRECTORY));
+    }
+    if let Some(frames) = bench_frames {
+        app.benchmark(frames);
+    }
+
+    event_loop
+        .run_app(&mut app)
+        .map_err(|e| LavaError::gpu("running the event loop", e))?;
 
-fn main() -> Result<(), impl Error> {
-    let config = {
-        let args = std::env::args().collect::<Vec<String>>();
-        match args.len() {
-            1 => Config::default(),
-            2 => Config::from_jsonc(Path::new(args[1].as_str())),
+    match app.take_error() {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
+}
+
+const USAGE: &str = "Usage: lava [--dev-shaders] [--bench-frames N] [path/to/config.jsonc]
+       lava validate path/to/config.jsonc
+       lava schema";
+
+fn main() -> ExitCode {
+    let args = std::env::args().collect::<Vec<String>>();
+    let mut args = args.iter().map(String::as_str).collect::<Vec<&str>>();
+    let dev_shaders = args.contains(&"--dev-shaders");
+    args.retain(|arg| *arg != "--dev-shaders");
+    let bench_frames = match args.iter().position(|arg| *arg == "--bench-frames") {
+        Some(i) => match args
+            .get(i + 1)
+            .and_then(|frames| frames.parse::<usize>().ok())
+        {
+            Some(frames) if frames > 0 => {
+                args.drain(i..i + 2);
+                Some(frames)
+            }
             _ => {
-                panic!("Usage: lava [path/to/config.jsonc]");
+                eprintln!("--bench-frames needs a positive number of frames");
+                eprintln!("{USAGE}");
+                return ExitCode::FAILURE;
             }
-        }
+        },
+        None => None,
     };
 
-    let event_loop = EventLoop::new().unwrap();
-    event_loop.set_control_flow(ControlFlow::Poll);
-
-    let app = App::new(&event_loop, &config);
+    let result = match args[1..] {
+        [] => run(None, dev_shaders, bench_frames),
+        ["validate", path] => {
+            return match Config::from_jsonc(Path::new(path)) {
+                Ok(_) => {
+                    println!("{path} is valid");
+                    ExitCode::SUCCESS
+                }
+                Err(e) => {
+                    eprintln!("{e}");
+                    ExitCode::FAILURE
+                }
+            };
+        }
+        ["schema"] => {
+            println!("{}", Config::schema());
+            return ExitCode::SUCCESS;
+        }
+        [path] => run(Some(Path::new(path)), dev_shaders, bench_frames),
+        _ => {
+            eprintln!("{USAGE}");
+            return ExitCode::FAILURE;
+        }
+    };
 
-    event_loop.run_app(app)
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::FAILURE
+        }
+    }
 }
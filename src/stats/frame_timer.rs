@@ -24,6 +24,10 @@ impl FrameTimer {
             .push(Instant::now().duration_since(self.frame_start));
     }
 
+    pub fn frame_count(&self) -> usize {
+        self.frame_times.len()
+    }
+
     pub fn results(&self) -> (usize, Duration, Duration, Duration) {
         let len = self.frame_times.len();
         let sum = self.frame_times.iter().sum::<Duration>();
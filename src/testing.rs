@@ -0,0 +1,36 @@
+//! Helpers shared by the tests.
+
+use glam::{Vec2, Vec3};
+use std::fmt::Display;
+
+/// Values compared up to a tolerance.
+pub trait Near: Copy + Display {
+    fn is_near(self, other: Self, tolerance: f32) -> bool;
+}
+
+impl Near for f32 {
+    fn is_near(self, other: Self, tolerance: f32) -> bool {
+        (self - other).abs() <= tolerance
+    }
+}
+
+impl Near for Vec2 {
+    fn is_near(self, other: Self, tolerance: f32) -> bool {
+        self.abs_diff_eq(other, tolerance)
+    }
+}
+
+impl Near for Vec3 {
+    fn is_near(self, other: Self, tolerance: f32) -> bool {
+        self.abs_diff_eq(other, tolerance)
+    }
+}
+
+/// Asserts that each component of `value` is within `tolerance` of `expected`.
+#[track_caller]
+pub fn assert_near<T: Near>(value: T, expected: T, tolerance: f32) {
+    assert!(
+        value.is_near(expected, tolerance),
+        "{value} isn't within {tolerance} of {expected}"
+    );
+}
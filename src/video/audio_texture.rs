@@ -0,0 +1,123 @@
+use crate::{audio::AudioData, error::LavaError};
+
+use std::sync::Arc;
+use vulkano::{
+    buffer::{
+        BufferUsage,
+        allocator::{SubbufferAllocator, SubbufferAllocatorCreateInfo},
+    },
+    command_buffer::{AutoCommandBufferBuilder, CopyBufferToImageInfo, PrimaryAutoCommandBuffer},
+    device::DeviceOwned,
+    format::Format,
+    image::{
+        Image, ImageCreateInfo, ImageType, ImageUsage,
+        sampler::{Filter, Sampler, SamplerAddressMode, SamplerCreateInfo},
+        view::ImageView,
+    },
+    memory::allocator::{AllocationCreateInfo, MemoryTypeFilter, StandardMemoryAllocator},
+};
+
+/// Spectrum and waveform as a 512x2 texture, laid out like Shadertoy's audio input: the first
+/// row holds the spectrum from low to high frequencies, the second the latest samples with 0.5 as
+/// silence.
+pub struct AudioTexture {
+    pub image: Arc<Image>,
+    pub image_view: Arc<ImageView>,
+    pub sampler: Arc<Sampler>,
+    upload_buffer_allocator: SubbufferAllocator,
+}
+
+impl AudioTexture {
+    pub const WIDTH: u32 = 512;
+
+    pub fn new(memory_allocator: &Arc<StandardMemoryAllocator>) -> Result<Self, LavaError> {
+        let error = |e| LavaError::gpu("creating the audio texture", e);
+
+        let image = Image::new(
+            memory_allocator.clone(),
+            ImageCreateInfo {
+                image_type: ImageType::Dim2d,
+                format: Format::R8_UNORM,
+                extent: [Self::WIDTH, 2, 1],
+                usage: ImageUsage::TRANSFER_DST | ImageUsage::SAMPLED,
+                ..Default::default()
+            },
+            AllocationCreateInfo::default(),
+        )
+        .map_err(|e| error(e.to_string()))?;
+
+        let image_view = ImageView::new_default(image.clone()).map_err(|e| error(e.to_string()))?;
+
+        let sampler = Sampler::new(
+            memory_allocator.device().clone(),
+            SamplerCreateInfo {
+                mag_filter: Filter::Linear,
+                min_filter: Filter::Linear,
+                address_mode: [SamplerAddressMode::ClampToEdge; 3],
+                ..Default::default()
+            },
+        )
+        .map_err(|e| error(e.to_string()))?;
+
+        let upload_buffer_allocator = SubbufferAllocator::new(
+            memory_allocator.clone(),
+            SubbufferAllocatorCreateInfo {
+                buffer_usage: BufferUsage::TRANSFER_SRC,
+                memory_type_filter: MemoryTypeFilter::PREFER_HOST
+                    | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+                ..Default::default()
+            },
+        );
+
+        Ok(Self {
+            image,
+            image_view,
+            sampler,
+            upload_buffer_allocator,
+        })
+    }
+
+    /// Records the upload of the current spectrum and waveform.
+    pub fn update(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        audio_data: &AudioData,
+    ) {
+        let width = Self::WIDTH as usize;
+        let to_byte = |value: f32| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+
+        let buffer = self
+            .upload_buffer_allocator
+            .allocate_slice::<u8>(2 * width as u64)
+            .unwrap();
+        let mut guard = buffer.write().unwrap();
+        let (spectrum, waveform) = guard.split_at_mut(width);
+
+        let last_bin = audio_data.dft.len().saturating_sub(1) as f32;
+        for (x, texel) in spectrum.iter_mut().enumerate() {
+            let bin = x as f32 / (width - 1) as f32 * last_bin;
+            let low = audio_data.dft[bin.floor() as usize].length();
+            let high = audio_data.dft[bin.ceil() as usize].length();
+            *texel = to_byte(low + (high - low) * bin.fract());
+        }
+
+        let sample_count = audio_data.samples.data.len();
+        let first = sample_count.saturating_sub(width);
+        for (x, texel) in waveform.iter_mut().enumerate() {
+            let sample = if first + x < sample_count {
+                audio_data.samples[first + x]
+            } else {
+                0.0
+            };
+            *texel = to_byte(sample * 0.5 + 0.5);
+        }
+        drop(guard);
+
+        builder
+            .copy_buffer_to_image(CopyBufferToImageInfo::buffer_image(
+                buffer,
+                self.image.clone(),
+            ))
+            .unwrap();
+    }
+}
@@ -0,0 +1,159 @@
+use crate::{
+    audio::AudioData,
+    video::{shader_types::BarsParameters, shaders},
+};
+
+use vulkano::{
+    buffer::allocator::SubbufferAllocator, descriptor_set::WriteDescriptorSet, padded::Padded,
+};
+
+#[derive(Clone, Copy, Default)]
+struct Bar {
+    level: f32,
+    peak: f32,
+    /// Seconds since the peak was reached.
+    held: f32,
+}
+
+/// The levels of a bars panel and their peaks, which fall over time.
+#[derive(Default)]
+pub struct BarsState {
+    bars: Vec<Bar>,
+}
+
+impl BarsState {
+    pub fn update(&mut self, parameters: &BarsParameters, audio_data: &AudioData, time_delta: f32) {
+        let count = parameters.bars as usize;
+        self.bars.resize(count, Bar::default());
+
+        let octaves = (parameters.max_frequency / parameters.min_frequency).log2();
+        let edge = |i: usize| {
+            let frequency = parameters.min_frequency * (octaves * i as f32 / count as f32).exp2();
+            audio_data.bin_scale.bin(frequency)
+        };
+        let magnitudes: Vec<f32> = audio_data.dft.iter().map(|bin| bin.length()).collect();
+        for (i, bar) in self.bars.iter_mut().enumerate() {
+            bar.level = (band_magnitude(&magnitudes, edge(i), edge(i + 1)) * parameters.gain)
+                .clamp(0.0, 1.0);
+
+            if bar.level >= bar.peak {
+                bar.peak = bar.level;
+                bar.held = 0.0;
+            } else {
+                bar.held += time_delta;
+                if bar.held > parameters.peak_hold {
+                    bar.peak = (bar.peak - parameters.peak_fall * time_delta).max(bar.level);
+                }
+            }
+        }
+    }
+
+    pub fn parameters(&self, parameters: &BarsParameters) -> shaders::BarsParameters {
+        shaders::BarsParameters {
+            pattern: Padded(parameters.pattern.clone().into()),
+            bar_count: parameters.bars,
+            gap: parameters.gap,
+            rounded: parameters.rounded as u32,
+            mirrored: parameters.mirrored as u32,
+            peaks: (parameters.peak_hold > 0.0) as u32,
+            segments: parameters.segments,
+            segment_gap: parameters.segment_gap,
+        }
+    }
+
+    /// The write of the levels and peaks of the bars.
+    pub fn writes(&self, storage_buffer_allocator: &SubbufferAllocator) -> Vec<WriteDescriptorSet> {
+        // empty buffers can't be bound
+        let levels = storage_buffer_allocator
+            .allocate_slice::<[f32; 2]>(self.bars.len().max(1) as u64)
+            .unwrap();
+        for (level, bar) in levels.write().unwrap().iter_mut().zip(&self.bars) {
+            *level = [bar.level, bar.peak];
+        }
+        vec![WriteDescriptorSet::buffer(13, levels)]
+    }
+}
+
+/// The loudest bin between the fractional bins `start` and `end`, interpolated at the edges for
+/// bands narrower than a bin.
+fn band_magnitude(magnitudes: &[f32], start: f32, end: f32) -> f32 {
+    let last = magnitudes.len() as f32 - 1.0;
+    let at = |bin: f32| {
+        let bin = bin.clamp(0.0, last);
+        let low = magnitudes[bin.floor() as usize];
+        let high = magnitudes[bin.ceil() as usize];
+        low + (high - low) * bin.fract()
+    };
+    let inner = (start.clamp(0.0, last).ceil() as usize..=end.clamp(0.0, last).floor() as usize)
+        .map(|bin| magnitudes[bin]);
+    inner.fold(at(start).max(at(end)), f32::max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BIN_COUNT: usize = 64;
+
+    /// The audio data of 48 kHz samples in 7 bins per octave from 46.875 Hz, with the spectrum
+    /// replaced by `magnitudes` at their bins.
+    fn audio_data(magnitudes: &[(usize, f32)]) -> AudioData {
+        AudioData::silence(1024, BIN_COUNT, 48000).with_magnitudes(magnitudes)
+    }
+
+    fn levels(state: &BarsState) -> Vec<[f32; 2]> {
+        state.bars.iter().map(|bar| [bar.level, bar.peak]).collect()
+    }
+
+    #[test]
+    fn interpolates_bands_narrower_than_a_bin() {
+        let magnitudes = [0.0, 1.0, 2.0, 3.0];
+        assert_eq!(band_magnitude(&magnitudes, 1.25, 1.5), 1.5);
+        assert_eq!(band_magnitude(&magnitudes, 2.5, 2.75), 2.75);
+        assert_eq!(band_magnitude(&[0.0, 4.0, 1.0, 0.0], 0.5, 2.5), 4.0);
+        // out of the spectrum on both sides
+        assert_eq!(band_magnitude(&magnitudes, -2.0, -1.0), 0.0);
+        assert_eq!(band_magnitude(&magnitudes, 5.0, 6.0), 3.0);
+    }
+
+    #[test]
+    fn the_last_bar_reaches_the_max_frequency() {
+        let parameters = BarsParameters {
+            bars: 4,
+            min_frequency: 100.0,
+            max_frequency: 1600.0,
+            gain: 1.0,
+            ..Default::default()
+        };
+        // 1600 Hz is at bin 35.65, each bar spans an octave of 7 bins
+        let audio_data = audio_data(&[(35, 1.0)]);
+        assert!(audio_data.bin_scale.frequency(35.0) < parameters.max_frequency);
+
+        let mut state = BarsState::default();
+        state.update(&parameters, &audio_data, 0.0);
+        assert_eq!(levels(&state), [[0.0; 2], [0.0; 2], [0.0; 2], [1.0; 2]]);
+    }
+
+    #[test]
+    fn peaks_hold_then_fall() {
+        let parameters = BarsParameters {
+            bars: 1,
+            gain: 1.0,
+            peak_hold: 0.5,
+            peak_fall: 0.5,
+            ..Default::default()
+        };
+        let mut state = BarsState::default();
+        state.update(&parameters, &audio_data(&[(20, 1.0)]), 0.25);
+        assert_eq!(levels(&state), [[1.0, 1.0]]);
+
+        let silence = audio_data(&[]);
+        let mut peaks = Vec::new();
+        for _ in 0..4 {
+            state.update(&parameters, &silence, 0.25);
+            peaks.push(state.bars[0].peak);
+        }
+        assert_eq!(peaks, [1.0, 1.0, 0.875, 0.75]);
+        assert_eq!(state.bars[0].level, 0.0);
+    }
+}
@@ -0,0 +1,201 @@
+use crate::{
+    error::LavaError,
+    video::{
+        PanelTransform, create_pipeline, create_write_descriptor_set, layer::Layer,
+        panel::write_transform, shaders,
+    },
+};
+
+use glam::{Vec2, vec2};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::{f32::consts::PI, sync::Arc};
+use vulkano::{
+    buffer::allocator::SubbufferAllocator,
+    descriptor_set::WriteDescriptorSet,
+    device::{Device, DeviceOwned},
+    image::sampler::{Filter, Sampler, SamplerAddressMode, SamplerCreateInfo},
+    pipeline::GraphicsPipeline,
+    render_pass::RenderPass,
+    shader::EntryPoint,
+};
+use winit::dpi::PhysicalSize;
+
+/// How the previous frame is carried into the next one, MilkDrop style. All the motion is per
+/// frame.
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct Feedback {
+    /// How much of the previous frame is kept, 0 clears it and 1 keeps it forever.
+    pub decay: f32,
+    /// Above 1 the trails grow outwards, below 1 they shrink inwards.
+    pub zoom: f32,
+    /// In degrees, around the center of the panel.
+    pub rotation: f32,
+    /// In fractions of the screen.
+    pub offset: [f32; 2],
+    /// In turns of the OkLab hue.
+    pub hue_shift: f32,
+    /// How much the bass amplifies the zoom, rotation, offset and hue shift.
+    pub bass_response: f32,
+}
+
+impl Default for Feedback {
+    fn default() -> Self {
+        Self {
+            decay: 0.9,
+            zoom: 1.01,
+            rotation: 0.0,
+            offset: [0.0, 0.0],
+            hue_shift: 0.0,
+            bass_response: 0.0,
+        }
+    }
+}
+
+impl Feedback {
+    pub fn validate(&self) -> Vec<(String, String)> {
+        let mut issues = Vec::new();
+        if !(0.0..=1.0).contains(&self.decay) {
+            issues.push(("decay".into(), "must be between 0 and 1".into()));
+        }
+        if self.zoom <= 0.0 {
+            issues.push(("zoom".into(), "must be positive".into()));
+        }
+        issues
+    }
+
+    fn parameters(
+        &self,
+        center: Vec2,
+        screen_size: Vec2,
+        bass: f32,
+    ) -> shaders::FeedbackParameters {
+        let amount = 1.0 + self.bass_response * bass;
+        shaders::FeedbackParameters {
+            center: center.to_array(),
+            offset: (Vec2::from(self.offset) * amount).to_array(),
+            screen_size: screen_size.to_array(),
+            decay: self.decay,
+            zoom: 1.0 + (self.zoom - 1.0) * amount,
+            rotation: self.rotation / 180.0 * PI * amount,
+            hue_shift: self.hue_shift * amount,
+        }
+    }
+}
+
+/// The pass that starts a frame of a feedback layer with its previous frame.
+pub struct FeedbackWarp {
+    shader: EntryPoint,
+    pipeline: Option<Arc<GraphicsPipeline>>,
+    sampler: Arc<Sampler>,
+}
+
+impl FeedbackWarp {
+    pub fn new(device: &Arc<Device>) -> Result<Self, LavaError> {
+        Ok(Self {
+            shader: shaders::load_feedback(device.clone())
+                .map_err(|e| LavaError::gpu("loading the feedback shader", e))?
+                .entry_point("main")
+                .unwrap(),
+            pipeline: None,
+            sampler: Sampler::new(
+                device.clone(),
+                SamplerCreateInfo {
+                    mag_filter: Filter::Linear,
+                    min_filter: Filter::Linear,
+                    // what comes from outside the screen is transparent
+                    address_mode: [SamplerAddressMode::ClampToBorder; 3],
+                    ..Default::default()
+                },
+            )
+            .map_err(|e| LavaError::gpu("creating the feedback sampler", e))?,
+        })
+    }
+
+    /// Rebuilds the pipeline for a new target size.
+    pub fn resize(
+        &mut self,
+        extent: PhysicalSize<u32>,
+        render_pass: &Arc<RenderPass>,
+        vertex_shader: &EntryPoint,
+    ) -> Result<(), LavaError> {
+        self.pipeline =
+            Some(self.create_pipeline(&self.shader, extent, render_pass, vertex_shader)?);
+        Ok(())
+    }
+
+    /// Swaps in `shader` if there is one, and rebuilds the pipeline with it and `vertex_shader`
+    /// once `resize` has been called. Nothing is changed if the pipeline fails to build.
+    pub fn replace_shader(
+        &mut self,
+        shader: Option<EntryPoint>,
+        extent: PhysicalSize<u32>,
+        render_pass: &Arc<RenderPass>,
+        vertex_shader: &EntryPoint,
+    ) -> Result<(), LavaError> {
+        let shader = shader.unwrap_or_else(|| self.shader.clone());
+        if self.pipeline.is_some() {
+            self.pipeline =
+                Some(self.create_pipeline(&shader, extent, render_pass, vertex_shader)?);
+        }
+        self.shader = shader;
+        Ok(())
+    }
+
+    fn create_pipeline(
+        &self,
+        shader: &EntryPoint,
+        extent: PhysicalSize<u32>,
+        render_pass: &Arc<RenderPass>,
+        vertex_shader: &EntryPoint,
+    ) -> Result<Arc<GraphicsPipeline>, LavaError> {
+        // the warp replaces what is in the layer
+        create_pipeline(
+            self.sampler.device(),
+            extent,
+            render_pass,
+            vertex_shader,
+            shader,
+            None,
+        )
+    }
+
+    /// The pipeline and writes drawing `layer`'s previous frame moved by `feedback` over the
+    /// whole target. `transform` is the one of the panel, the motion is around its center.
+    pub fn warp(
+        &self,
+        uniform_buffer_allocator: &SubbufferAllocator,
+        layer: &Layer,
+        feedback: &Feedback,
+        transform: &PanelTransform,
+        screen_size: Vec2,
+        bass: f32,
+    ) -> (Arc<GraphicsPipeline>, Vec<WriteDescriptorSet>) {
+        // the center of the panel in UVs of the screen
+        let center = transform
+            .get_matrix(screen_size)
+            .transform_point2(Vec2::ZERO);
+        let center = (center + vec2(1.0, 1.0)) / 2.0;
+        (
+            self.pipeline.clone().expect("`resize` wasn't called"),
+            vec![
+                write_transform(
+                    uniform_buffer_allocator,
+                    &PanelTransform::FULLSCREEN,
+                    screen_size,
+                ),
+                WriteDescriptorSet::image_view_sampler(
+                    11,
+                    layer.previous().clone(),
+                    self.sampler.clone(),
+                ),
+                create_write_descriptor_set(
+                    uniform_buffer_allocator,
+                    10,
+                    feedback.parameters(center, screen_size, bass),
+                ),
+            ],
+        )
+    }
+}
@@ -0,0 +1,115 @@
+use glam::Vec2;
+use std::{
+    collections::BTreeMap,
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+/// Pointer state in target pixels from the upper-left corner.
+#[derive(Clone, Copy, Default)]
+pub struct Mouse {
+    pub cursor: Vec2,
+    /// Where the pointer was last seen while pressed.
+    pub position: Vec2,
+    /// Where the pointer was last pressed.
+    pub click: Vec2,
+    pub pressed: bool,
+    /// Whether the pointer was pressed since the previous frame.
+    pub clicked: bool,
+}
+
+impl Mouse {
+    pub fn move_to(&mut self, cursor: Vec2) {
+        self.cursor = cursor;
+        if self.pressed {
+            self.position = cursor;
+        }
+    }
+
+    pub fn press(&mut self) {
+        self.pressed = true;
+        self.clicked = true;
+        self.position = self.cursor;
+        self.click = self.cursor;
+    }
+
+    pub fn release(&mut self) {
+        self.pressed = false;
+    }
+}
+
+/// Timing and input state of the frame being drawn.
+pub struct FrameInputs {
+    start: Instant,
+    last_frame: Instant,
+    /// Seconds since the renderer was created.
+    pub time: f32,
+    pub time_delta: f32,
+    pub frame: u32,
+    /// `Bass::chrono` of the frame.
+    pub chrono: f32,
+    pub mouse: Mouse,
+    /// Strings set by the application, shown by text panels in place of `{name}`.
+    pub text: BTreeMap<String, String>,
+}
+
+impl FrameInputs {
+    pub fn new() -> Self {
+        let now = Instant::now();
+        Self {
+            start: now,
+            last_frame: now,
+            time: 0.0,
+            time_delta: 0.0,
+            frame: 0,
+            chrono: 0.0,
+            mouse: Mouse::default(),
+            text: BTreeMap::new(),
+        }
+    }
+
+    /// Updates the timing for a frame about to be drawn.
+    pub fn begin_frame(&mut self, chrono: f32) {
+        let now = Instant::now();
+        self.time = (now - self.start).as_secs_f32();
+        self.time_delta = (now - self.last_frame).as_secs_f32();
+        self.last_frame = now;
+        self.chrono = chrono;
+    }
+
+    pub fn end_frame(&mut self) {
+        self.frame += 1;
+        self.mouse.clicked = false;
+    }
+
+    /// The UTC date as year, month (from 0), day (from 1) and seconds since midnight.
+    pub fn date() -> [f32; 4] {
+        let since_epoch = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let seconds = (since_epoch.as_secs_f64() % 86400.0) as f32;
+
+        // days to civil date, from http://howardhinnant.github.io/date_algorithms.html
+        let z = (since_epoch.as_secs() / 86400) as i64 + 719468;
+        let era = z.div_euclid(146097);
+        let day_of_era = z - era * 146097;
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let shifted_month = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+        let month = if shifted_month < 10 {
+            shifted_month + 3
+        } else {
+            shifted_month - 9
+        };
+        let year = year_of_era + era * 400 + (month <= 2) as i64;
+
+        [year as f32, (month - 1) as f32, day as f32, seconds]
+    }
+}
+
+impl Default for FrameInputs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
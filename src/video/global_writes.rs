@@ -1,11 +1,12 @@
 use crate::{
     audio::analyzer::AudioData,
     video::{
-        Texture, create_write_descriptor_set,
+        AudioTexture, PaletteTexture, Texture, create_write_descriptor_set,
         shaders::{Dft, Samples},
     },
 };
 
+use std::collections::BTreeMap;
 use vulkano::{
     buffer::{Subbuffer, allocator::SubbufferAllocator},
     descriptor_set::WriteDescriptorSet,
@@ -17,33 +18,39 @@ pub struct GlobalWrites {
     pub stabilization: WriteDescriptorSet,
     pub dft: WriteDescriptorSet,
     pub bass: WriteDescriptorSet,
-    pub image: Option<[WriteDescriptorSet; 2]>,
+    /// The image at `image_path`.
+    pub image: Option<[WriteDescriptorSet; 3]>,
+    /// The config's `textures`, by name.
+    pub textures: BTreeMap<String, [WriteDescriptorSet; 3]>,
+    pub audio_texture: WriteDescriptorSet,
+    pub palette: WriteDescriptorSet,
 }
 
 impl GlobalWrites {
+    /// `dft` is the write of the DFT computed on the GPU, the analyzer's is uploaded without one.
     pub fn new(
         uniform_buffer_allocator: &SubbufferAllocator,
         storage_buffer_allocator: &SubbufferAllocator,
+        samples: WriteDescriptorSet,
+        dft: Option<WriteDescriptorSet>,
         texture: &Option<Texture>,
+        textures: &BTreeMap<String, Texture>,
+        audio_texture: &AudioTexture,
+        palette: &PaletteTexture,
         audio_data: &AudioData,
+        time: f32,
     ) -> Self {
+        let texture_writes = |texture: &Texture| {
+            texture.writes(uniform_buffer_allocator, texture.frame(time, audio_data))
+        };
         Self {
-            samples: {
-                let buffer: Subbuffer<Samples> = storage_buffer_allocator
-                    .allocate_unsized(audio_data.samples.data.len() as u64)
-                    .unwrap();
-                let mut guard = buffer.write().unwrap();
-                guard.samples_start = audio_data.samples.start as u32;
-                guard.samples_data.copy_from_slice(&audio_data.samples.data);
-                drop(guard);
-                WriteDescriptorSet::buffer(2, buffer)
-            },
+            samples,
             stabilization: create_write_descriptor_set(
                 &uniform_buffer_allocator,
                 3,
                 audio_data.stabilization,
             ),
-            dft: {
+            dft: dft.unwrap_or_else(|| {
                 let buffer: Subbuffer<Dft> = storage_buffer_allocator
                     .allocate_unsized(audio_data.dft.len() as u64)
                     .unwrap();
@@ -57,15 +64,34 @@ impl GlobalWrites {
                 );
                 drop(guard);
                 WriteDescriptorSet::buffer(4, buffer)
-            },
+            }),
             bass: create_write_descriptor_set(&uniform_buffer_allocator, 5, audio_data.bass),
-            image: match texture {
-                Some(tex) => Some([
-                    WriteDescriptorSet::sampler(6, tex.sampler.clone()),
-                    WriteDescriptorSet::image_view(7, tex.image_view.clone()),
-                ]),
-                None => None,
-            },
+            image: texture.as_ref().map(texture_writes),
+            textures: textures
+                .iter()
+                .map(|(name, texture)| (name.clone(), texture_writes(texture)))
+                .collect(),
+            audio_texture: WriteDescriptorSet::image_view_sampler(
+                8,
+                audio_texture.image_view.clone(),
+                audio_texture.sampler.clone(),
+            ),
+            palette: palette.write(),
         }
     }
 }
+
+/// The write of the samples of `audio_data`.
+pub fn write_samples(
+    storage_buffer_allocator: &SubbufferAllocator,
+    audio_data: &AudioData,
+) -> WriteDescriptorSet {
+    let buffer: Subbuffer<Samples> = storage_buffer_allocator
+        .allocate_unsized(audio_data.samples.data.len() as u64)
+        .unwrap();
+    let mut guard = buffer.write().unwrap();
+    guard.samples_start = audio_data.samples.start as u32;
+    guard.samples_data.copy_from_slice(&audio_data.samples.data);
+    drop(guard);
+    WriteDescriptorSet::buffer(2, buffer)
+}
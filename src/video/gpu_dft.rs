@@ -0,0 +1,378 @@
+use crate::{
+    audio::DftLut,
+    config::Config,
+    error::LavaError,
+    video::{
+        Panel,
+        shaders::{self, Dft},
+    },
+};
+
+use glam::Vec2;
+use std::{collections::VecDeque, sync::Arc};
+use vulkano::{
+    buffer::{
+        Buffer, BufferCreateInfo, BufferUsage, Subbuffer,
+        allocator::{SubbufferAllocator, SubbufferAllocatorCreateInfo},
+    },
+    command_buffer::{AutoCommandBufferBuilder, PrimaryAutoCommandBuffer},
+    descriptor_set::{
+        DescriptorSet, WriteDescriptorSet, allocator::StandardDescriptorSetAllocator,
+    },
+    device::{Device, DeviceOwned, Queue, QueueFlags},
+    memory::allocator::{AllocationCreateInfo, MemoryTypeFilter, StandardMemoryAllocator},
+    pipeline::{
+        ComputePipeline, Pipeline, PipelineBindPoint, PipelineLayout,
+        PipelineShaderStageCreateInfo, compute::ComputePipelineCreateInfo,
+        layout::PipelineDescriptorSetLayoutCreateInfo,
+    },
+    shader::ShaderModule,
+};
+
+/// The most outputs kept waiting for the GPU, older ones are dropped unread.
+const MAX_PENDING: usize = 4;
+
+/// Computes the analyzer's DFT in a compute shader, from the lookup table of [`DftLut`].
+pub struct GpuDft {
+    pipeline: Arc<ComputePipeline>,
+    bins: Subbuffer<[shaders::DftBin]>,
+    terms: Subbuffer<[[f32; 2]]>,
+    bin_count: u32,
+    /// Allocates the outputs in memory the CPU reads them back from.
+    output_allocator: SubbufferAllocator,
+    /// The output of the last recorded dispatch with the position of its samples, until its
+    /// command buffer is submitted.
+    recorded: Option<(Subbuffer<Dft>, u64)>,
+    /// The outputs of the submitted dispatches with the positions of their samples, oldest first.
+    pending: VecDeque<(Subbuffer<Dft>, u64)>,
+}
+
+impl GpuDft {
+    pub fn new(
+        queue: &Arc<Queue>,
+        memory_allocator: &Arc<StandardMemoryAllocator>,
+        config: &Config,
+    ) -> Result<Self, LavaError> {
+        let device = queue.device();
+        let queue_flags = device.physical_device().queue_family_properties()
+            [queue.queue_family_index() as usize]
+            .queue_flags;
+        if !queue_flags.intersects(QueueFlags::COMPUTE) {
+            return Err(LavaError::gpu(
+                "creating the DFT pipeline",
+                "the queue doesn't support compute",
+            ));
+        }
+
+        let module = shaders::load_dft(device.clone())
+            .map_err(|e| LavaError::gpu("loading the DFT shader", e))?;
+        let pipeline = create_pipeline(device, &module, config)?;
+
+        // the windows are folded into the complex exponentials, so each term is one multiply
+        let lut = DftLut::new(config.sample_count, config.bin_count, config.sample_rate);
+        let mut bins = Vec::with_capacity(lut.bins.len());
+        let mut terms = Vec::new();
+        for bin_data in &lut.bins {
+            bins.push(shaders::DftBin {
+                window_start: bin_data.window_start as u32,
+                offset: terms.len() as u32,
+                window_length: bin_data.window_weights.len() as u32,
+                total_window: bin_data.total_window,
+            });
+            terms.extend(
+                bin_data
+                    .complex_exponentials
+                    .iter()
+                    .zip(&bin_data.window_weights)
+                    .map(|(exponential, weight)| (*exponential * *weight).to_array()),
+            );
+        }
+
+        let buffer_info = BufferCreateInfo {
+            usage: BufferUsage::STORAGE_BUFFER,
+            ..Default::default()
+        };
+        let allocation_info = AllocationCreateInfo {
+            memory_type_filter: MemoryTypeFilter::PREFER_DEVICE
+                | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+            ..Default::default()
+        };
+        let bins = Buffer::from_iter(
+            memory_allocator.clone(),
+            buffer_info.clone(),
+            allocation_info.clone(),
+            bins,
+        )
+        .map_err(|e| LavaError::gpu("uploading the DFT lookup table", e))?;
+        let terms = Buffer::from_iter(
+            memory_allocator.clone(),
+            buffer_info,
+            allocation_info,
+            terms,
+        )
+        .map_err(|e| LavaError::gpu("uploading the DFT lookup table", e))?;
+
+        let output_allocator = SubbufferAllocator::new(
+            memory_allocator.clone(),
+            SubbufferAllocatorCreateInfo {
+                buffer_usage: BufferUsage::STORAGE_BUFFER,
+                memory_type_filter: MemoryTypeFilter::PREFER_HOST
+                    | MemoryTypeFilter::HOST_RANDOM_ACCESS,
+                ..Default::default()
+            },
+        );
+
+        Ok(Self {
+            pipeline,
+            bins,
+            terms,
+            bin_count: config.bin_count as u32,
+            output_allocator,
+            recorded: None,
+            pending: VecDeque::new(),
+        })
+    }
+
+    /// Swaps in the DFT shader of `module`, such as one compiled at runtime. Nothing is changed if
+    /// its pipeline fails to build.
+    pub fn replace_shader(
+        &mut self,
+        module: &Arc<ShaderModule>,
+        config: &Config,
+    ) -> Result<(), LavaError> {
+        self.pipeline = create_pipeline(self.pipeline.device(), module, config)?;
+        Ok(())
+    }
+
+    /// Records the DFT of the `samples` at `position` and returns the write of its output for the
+    /// `Dft` binding. Its result is only read back once [`submitted`](Self::submitted) is called,
+    /// a dispatch whose command buffer fails to submit is replaced by the next one.
+    pub fn record(
+        &mut self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        descriptor_set_allocator: &Arc<StandardDescriptorSetAllocator>,
+        samples: &WriteDescriptorSet,
+        position: u64,
+    ) -> WriteDescriptorSet {
+        let output: Subbuffer<Dft> = self
+            .output_allocator
+            .allocate_unsized(self.bin_count as u64)
+            .unwrap();
+        let layout = self.pipeline.layout().set_layouts()[0].clone();
+        let descriptor_set = DescriptorSet::new(
+            descriptor_set_allocator.clone(),
+            layout,
+            [
+                WriteDescriptorSet::buffer(0, self.bins.clone()),
+                WriteDescriptorSet::buffer(1, self.terms.clone()),
+                samples.clone(),
+                WriteDescriptorSet::buffer(4, output.clone()),
+            ],
+            [],
+        )
+        .unwrap();
+
+        builder
+            .bind_pipeline_compute(self.pipeline.clone())
+            .unwrap()
+            .bind_descriptor_sets(
+                PipelineBindPoint::Compute,
+                self.pipeline.layout().clone(),
+                0,
+                descriptor_set,
+            )
+            .unwrap();
+        unsafe { builder.dispatch([self.bin_count, 1, 1]) }.unwrap();
+
+        self.recorded = Some((output.clone(), position));
+        WriteDescriptorSet::buffer(4, output)
+    }
+
+    /// Marks the last recorded dispatch as submitted, to be read back once the GPU is done.
+    pub fn submitted(&mut self) {
+        let Some(recorded) = self.recorded.take() else {
+            return;
+        };
+        if self.pending.len() == MAX_PENDING {
+            self.pending.pop_front();
+        }
+        self.pending.push_back(recorded);
+    }
+
+    /// The latest DFT the GPU finished since the last call, with the position of its samples.
+    pub fn take_result(&mut self) -> Option<(Vec<Vec2>, u64)> {
+        let mut result = None;
+        // outputs still in use by the GPU can't be read yet
+        while let Some((output, position)) = self.pending.front() {
+            let Ok(guard) = output.read() else {
+                break;
+            };
+            result = Some((
+                guard.dft.iter().map(|&bin| Vec2::from_array(bin)).collect(),
+                *position,
+            ));
+            drop(guard);
+            self.pending.pop_front();
+        }
+        result
+    }
+}
+
+fn create_pipeline(
+    device: &Arc<Device>,
+    module: &Arc<ShaderModule>,
+    config: &Config,
+) -> Result<Arc<ComputePipeline>, LavaError> {
+    let stage = PipelineShaderStageCreateInfo::new(Panel::specialize(module, config)?);
+    let layout = PipelineLayout::new(
+        device.clone(),
+        PipelineDescriptorSetLayoutCreateInfo::from_stages([&stage])
+            .into_pipeline_layout_create_info(device.clone())
+            .map_err(|e| LavaError::gpu("creating the DFT pipeline", e))?,
+    )
+    .map_err(|e| LavaError::gpu("creating the DFT pipeline", e))?;
+    ComputePipeline::new(
+        device.clone(),
+        None,
+        ComputePipelineCreateInfo::stage_layout(stage, layout),
+    )
+    .map_err(|e| LavaError::gpu("creating the DFT pipeline", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::CircularBuffer;
+
+    use std::f32::consts::TAU;
+    use vulkano::{
+        VulkanLibrary,
+        command_buffer::{
+            CommandBufferUsage, PrimaryCommandBufferAbstract,
+            allocator::StandardCommandBufferAllocator,
+        },
+        device::{Device, DeviceCreateInfo, QueueCreateInfo},
+        instance::{Instance, InstanceCreateFlags, InstanceCreateInfo},
+        sync::GpuFuture,
+    };
+
+    fn compute_queue() -> Option<Arc<Queue>> {
+        let instance = Instance::new(
+            VulkanLibrary::new().ok()?,
+            InstanceCreateInfo {
+                flags: InstanceCreateFlags::ENUMERATE_PORTABILITY,
+                ..Default::default()
+            },
+        )
+        .ok()?;
+        let (physical_device, queue_family_index) =
+            instance.enumerate_physical_devices().ok()?.find_map(|p| {
+                let i = p
+                    .queue_family_properties()
+                    .iter()
+                    .position(|q| q.queue_flags.intersects(QueueFlags::COMPUTE))?;
+                Some((p, i as u32))
+            })?;
+        let (_, mut queues) = Device::new(
+            physical_device,
+            DeviceCreateInfo {
+                queue_create_infos: vec![QueueCreateInfo {
+                    queue_family_index,
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+        )
+        .ok()?;
+        queues.next()
+    }
+
+    #[test]
+    fn gpu_dft_matches_cpu_dft() {
+        let Some(queue) = compute_queue() else {
+            eprintln!("no Vulkan device with compute, skipping");
+            return;
+        };
+        let device = queue.device();
+        let config = Config {
+            sample_count: 4096,
+            bin_count: 128,
+            ..Default::default()
+        };
+
+        // more samples than fit, so that the buffer wraps around
+        let mut samples = CircularBuffer::new(config.sample_count, 0.0);
+        for i in 0..config.sample_count + 1000 {
+            let t = i as f32 / config.sample_rate as f32;
+            let sample = 0.5 * (TAU * 440.0 * t).sin()
+                + 0.3 * (TAU * 61.0 * t).sin()
+                + 0.1 * (TAU * 5000.0 * t).cos();
+            samples.push(&sample);
+        }
+        let expected = DftLut::new(config.sample_count, config.bin_count, config.sample_rate)
+            .compute(&samples);
+
+        let memory_allocator = Arc::new(StandardMemoryAllocator::new_default(device.clone()));
+        let descriptor_set_allocator = Arc::new(StandardDescriptorSetAllocator::new(
+            device.clone(),
+            Default::default(),
+        ));
+        let command_buffer_allocator = Arc::new(StandardCommandBufferAllocator::new(
+            device.clone(),
+            Default::default(),
+        ));
+        let storage_buffer_allocator = SubbufferAllocator::new(
+            memory_allocator.clone(),
+            SubbufferAllocatorCreateInfo {
+                buffer_usage: BufferUsage::STORAGE_BUFFER,
+                memory_type_filter: MemoryTypeFilter::PREFER_DEVICE
+                    | MemoryTypeFilter::HOST_RANDOM_ACCESS,
+                ..Default::default()
+            },
+        );
+
+        let samples_buffer: Subbuffer<shaders::Samples> = storage_buffer_allocator
+            .allocate_unsized(samples.data.len() as u64)
+            .unwrap();
+        let mut guard = samples_buffer.write().unwrap();
+        guard.samples_start = samples.start as u32;
+        guard.samples_data.copy_from_slice(&samples.data);
+        drop(guard);
+
+        let mut gpu_dft = GpuDft::new(&queue, &memory_allocator, &config).unwrap();
+        let mut builder = AutoCommandBufferBuilder::primary(
+            command_buffer_allocator,
+            queue.queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+        .unwrap();
+        gpu_dft.record(
+            &mut builder,
+            &descriptor_set_allocator,
+            &WriteDescriptorSet::buffer(2, samples_buffer),
+            7,
+        );
+        builder
+            .build()
+            .unwrap()
+            .execute(queue.clone())
+            .unwrap()
+            .then_signal_fence_and_flush()
+            .unwrap()
+            .wait(None)
+            .unwrap();
+        // not read back before it is known to be submitted
+        assert!(gpu_dft.take_result().is_none());
+        gpu_dft.submitted();
+
+        let (actual, position) = gpu_dft.take_result().unwrap();
+        assert_eq!(position, 7);
+        assert_eq!(actual.len(), expected.len());
+        for (bin, (actual, expected)) in actual.iter().zip(&expected).enumerate() {
+            assert!(
+                (*actual - *expected).length() <= 1e-4 + expected.length() * 1e-3,
+                "bin {bin}: {actual} on the GPU, {expected} on the CPU",
+            );
+        }
+    }
+}
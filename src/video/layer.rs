@@ -0,0 +1,386 @@
+use crate::{
+    audio::AudioData,
+    error::LavaError,
+    video::{
+        GlobalWrites, PanelTransform, Renderer, create_pipeline, create_write_descriptor_set,
+        panel::write_transform,
+        shaders,
+        window_size_dependent_setup::{alpha_blend, create_attachment},
+    },
+};
+
+use glam::{Vec2, vec2};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use vulkano::{
+    buffer::allocator::SubbufferAllocator,
+    command_buffer::{AutoCommandBufferBuilder, PrimaryAutoCommandBuffer},
+    descriptor_set::WriteDescriptorSet,
+    device::{Device, DeviceOwned},
+    image::{
+        ImageUsage,
+        sampler::{Filter, Sampler, SamplerAddressMode, SamplerCreateInfo},
+        view::ImageView,
+    },
+    memory::allocator::StandardMemoryAllocator,
+    pipeline::{
+        GraphicsPipeline,
+        graphics::color_blend::{AttachmentBlend, BlendFactor, BlendOp},
+    },
+    render_pass::{Framebuffer, FramebufferCreateInfo, RenderPass},
+    shader::EntryPoint,
+};
+use winit::dpi::PhysicalSize;
+
+/// What layers are cleared to, they hold premultiplied colors over nothing.
+pub const TRANSPARENT: [f32; 4] = [0.0, 0.0, 0.0, 0.0];
+
+/// How a panel is combined with what is drawn below it.
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum BlendMode {
+    #[default]
+    Alpha,
+    Additive,
+    Multiply,
+    Screen,
+    Subtract,
+}
+
+impl BlendMode {
+    pub const ALL: [Self; 5] = [
+        Self::Alpha,
+        Self::Additive,
+        Self::Multiply,
+        Self::Screen,
+        Self::Subtract,
+    ];
+
+    /// The blending of a shader's output with straight alpha, for the modes that have one.
+    /// Panels in the other modes are drawn in a layer first.
+    pub fn straight(self) -> Option<AttachmentBlend> {
+        // the alpha of the target is kept as is by all but alpha blending
+        let keep_alpha = AttachmentBlend {
+            src_alpha_blend_factor: BlendFactor::Zero,
+            dst_alpha_blend_factor: BlendFactor::One,
+            alpha_blend_op: BlendOp::Add,
+            ..alpha_blend()
+        };
+        match self {
+            Self::Alpha => Some(alpha_blend()),
+            Self::Additive => Some(AttachmentBlend {
+                dst_color_blend_factor: BlendFactor::One,
+                ..keep_alpha
+            }),
+            Self::Subtract => Some(AttachmentBlend {
+                dst_color_blend_factor: BlendFactor::One,
+                color_blend_op: BlendOp::ReverseSubtract,
+                ..keep_alpha
+            }),
+            Self::Multiply | Self::Screen => None,
+        }
+    }
+
+    /// The blending of a layer, which holds premultiplied colors.
+    pub fn premultiplied(self) -> AttachmentBlend {
+        let keep_alpha = AttachmentBlend {
+            src_color_blend_factor: BlendFactor::One,
+            src_alpha_blend_factor: BlendFactor::Zero,
+            dst_alpha_blend_factor: BlendFactor::One,
+            alpha_blend_op: BlendOp::Add,
+            ..AttachmentBlend::additive()
+        };
+        match self {
+            Self::Alpha => AttachmentBlend {
+                src_color_blend_factor: BlendFactor::One,
+                ..alpha_blend()
+            },
+            Self::Additive => keep_alpha,
+            Self::Multiply => AttachmentBlend {
+                src_color_blend_factor: BlendFactor::DstColor,
+                dst_color_blend_factor: BlendFactor::OneMinusSrcAlpha,
+                ..keep_alpha
+            },
+            Self::Screen => AttachmentBlend {
+                dst_color_blend_factor: BlendFactor::OneMinusSrcColor,
+                ..keep_alpha
+            },
+            Self::Subtract => AttachmentBlend {
+                color_blend_op: BlendOp::ReverseSubtract,
+                ..keep_alpha
+            },
+        }
+    }
+}
+
+/// A screen sized image a panel is drawn into on its own before being composited, or two drawn
+/// into in turns so that each frame can sample the previous one.
+pub struct Layer {
+    views: Vec<Arc<ImageView>>,
+    framebuffers: Vec<Arc<Framebuffer>>,
+    current: usize,
+}
+
+impl Layer {
+    /// Creates `count` images for `render_pass`.
+    pub fn new(
+        memory_allocator: &Arc<StandardMemoryAllocator>,
+        render_pass: &Arc<RenderPass>,
+        extent: PhysicalSize<u32>,
+        count: usize,
+    ) -> Result<Self, LavaError> {
+        let attachments = render_pass.attachments();
+        let depth = create_attachment(
+            memory_allocator,
+            attachments[1].format,
+            extent,
+            ImageUsage::DEPTH_STENCIL_ATTACHMENT | ImageUsage::TRANSIENT_ATTACHMENT,
+        )?;
+        let views = (0..count)
+            .map(|_| {
+                create_attachment(
+                    memory_allocator,
+                    attachments[0].format,
+                    extent,
+                    ImageUsage::COLOR_ATTACHMENT | ImageUsage::SAMPLED,
+                )
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let framebuffers = views
+            .iter()
+            .map(|view| {
+                Framebuffer::new(
+                    render_pass.clone(),
+                    FramebufferCreateInfo {
+                        attachments: vec![view.clone(), depth.clone()],
+                        ..Default::default()
+                    },
+                )
+                .map_err(|e| LavaError::gpu("creating a layer", e))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self {
+            views,
+            framebuffers,
+            current: 0,
+        })
+    }
+
+    pub fn count(&self) -> usize {
+        self.views.len()
+    }
+
+    /// The framebuffer this frame is drawn into.
+    pub fn framebuffer(&self) -> &Arc<Framebuffer> {
+        &self.framebuffers[self.current]
+    }
+
+    /// What was drawn this frame.
+    pub fn current(&self) -> &Arc<ImageView> {
+        &self.views[self.current]
+    }
+
+    /// What was drawn the frame before, if there are two images.
+    pub fn previous(&self) -> &Arc<ImageView> {
+        &self.views[(self.current + self.views.len() - 1) % self.views.len()]
+    }
+
+    /// Makes this frame the previous one.
+    pub fn swap(&mut self) {
+        self.current = (self.current + 1) % self.views.len();
+    }
+}
+
+/// Draws layers over what is below them.
+pub struct Compositor {
+    shader: EntryPoint,
+    /// One for each blend mode, in the order of `BlendMode::ALL`.
+    pipelines: Vec<Arc<GraphicsPipeline>>,
+    sampler: Arc<Sampler>,
+}
+
+impl Compositor {
+    pub fn new(device: &Arc<Device>) -> Result<Self, LavaError> {
+        Ok(Self {
+            shader: shaders::load_composite(device.clone())
+                .map_err(|e| LavaError::gpu("loading the composite shader", e))?
+                .entry_point("main")
+                .unwrap(),
+            pipelines: Vec::new(),
+            sampler: Sampler::new(
+                device.clone(),
+                SamplerCreateInfo {
+                    mag_filter: Filter::Linear,
+                    min_filter: Filter::Linear,
+                    // what comes from outside the screen is transparent
+                    address_mode: [SamplerAddressMode::ClampToBorder; 3],
+                    ..Default::default()
+                },
+            )
+            .map_err(|e| LavaError::gpu("creating the layer sampler", e))?,
+        })
+    }
+
+    /// Rebuilds the pipelines for a new target size.
+    pub fn resize(
+        &mut self,
+        extent: PhysicalSize<u32>,
+        render_pass: &Arc<RenderPass>,
+        vertex_shader: &EntryPoint,
+    ) -> Result<(), LavaError> {
+        self.pipelines = self.create_pipelines(&self.shader, extent, render_pass, vertex_shader)?;
+        Ok(())
+    }
+
+    /// Swaps in `shader` if there is one, and rebuilds the pipelines with it and `vertex_shader`
+    /// once `resize` has been called. Nothing is changed if a pipeline fails to build.
+    pub fn replace_shader(
+        &mut self,
+        shader: Option<EntryPoint>,
+        extent: PhysicalSize<u32>,
+        render_pass: &Arc<RenderPass>,
+        vertex_shader: &EntryPoint,
+    ) -> Result<(), LavaError> {
+        let shader = shader.unwrap_or_else(|| self.shader.clone());
+        if !self.pipelines.is_empty() {
+            self.pipelines = self.create_pipelines(&shader, extent, render_pass, vertex_shader)?;
+        }
+        self.shader = shader;
+        Ok(())
+    }
+
+    fn create_pipelines(
+        &self,
+        shader: &EntryPoint,
+        extent: PhysicalSize<u32>,
+        render_pass: &Arc<RenderPass>,
+        vertex_shader: &EntryPoint,
+    ) -> Result<Vec<Arc<GraphicsPipeline>>, LavaError> {
+        let device = self.sampler.device().clone();
+        BlendMode::ALL
+            .into_iter()
+            .map(|blend_mode| {
+                create_pipeline(
+                    &device,
+                    extent,
+                    render_pass,
+                    vertex_shader,
+                    shader,
+                    Some(blend_mode.premultiplied()),
+                )
+            })
+            .collect()
+    }
+
+    /// The pipeline and writes drawing `layer`'s current frame clipped to `transform`, only
+    /// where the `mask` layer is drawn, or where it isn't when inverted.
+    pub fn composite(
+        &self,
+        uniform_buffer_allocator: &SubbufferAllocator,
+        layer: &Layer,
+        transform: &PanelTransform,
+        blend_mode: BlendMode,
+        opacity: f32,
+        mask: Option<(&Layer, bool)>,
+    ) -> (Arc<GraphicsPipeline>, Vec<WriteDescriptorSet>) {
+        let extent = layer.current().image().extent();
+        let screen_size = vec2(extent[0] as f32, extent[1] as f32);
+        let index = BlendMode::ALL
+            .iter()
+            .position(|mode| *mode == blend_mode)
+            .unwrap();
+        (
+            self.pipelines[index].clone(),
+            vec![
+                write_transform(uniform_buffer_allocator, transform, screen_size),
+                WriteDescriptorSet::image_view_sampler(
+                    11,
+                    layer.current().clone(),
+                    self.sampler.clone(),
+                ),
+                WriteDescriptorSet::image_view_sampler(
+                    12,
+                    mask.map_or(layer, |(mask, _)| mask).current().clone(),
+                    self.sampler.clone(),
+                ),
+                create_write_descriptor_set(
+                    uniform_buffer_allocator,
+                    10,
+                    shaders::CompositeParameters {
+                        opacity,
+                        mask_mode: match mask {
+                            None => 0,
+                            Some((_, false)) => 1,
+                            Some((_, true)) => 2,
+                        },
+                        tonemap: 0,
+                        exposure: 1.0,
+                    },
+                ),
+            ],
+        )
+    }
+}
+
+impl Renderer {
+    /// Records the panels that need a layer into it, over their previous frame when they have
+    /// feedback.
+    pub(crate) fn draw_layers(
+        &mut self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        global_writes: &GlobalWrites,
+        screen_size: Vec2,
+        audio_data: &AudioData,
+    ) -> Result<(), LavaError> {
+        for i in 0..self.panels.len() {
+            let Some(layer) = self.layers.get(i).and_then(Option::as_ref) else {
+                continue;
+            };
+            let target = layer.framebuffer().clone();
+            let warp = self.panels[i].feedback.map(|feedback| {
+                self.feedback_warp.warp(
+                    &self.uniform_buffer_allocator,
+                    layer,
+                    &feedback,
+                    &self.panels[i].transform,
+                    screen_size,
+                    audio_data.bass.bass,
+                )
+            });
+            self.begin_render_pass(builder, target, TRANSPARENT);
+            if let Some(warp) = warp {
+                self.record_draw(builder, warp);
+            }
+            self.draw_panel(builder, i, global_writes, screen_size, audio_data)?;
+            builder.end_render_pass(Default::default()).unwrap();
+        }
+        Ok(())
+    }
+
+    /// The pipeline and writes drawing the layer of panel `i` where it goes, masked by the layer
+    /// of its mask panel, if the panel is drawn in a layer.
+    pub(crate) fn composite_panel(
+        &self,
+        i: usize,
+    ) -> Option<(Arc<GraphicsPipeline>, Vec<WriteDescriptorSet>)> {
+        let layer = self.layers.get(i)?.as_ref()?;
+        let panel = &self.panels[i];
+        let mask = panel.mask.as_ref().and_then(|mask| {
+            let j = self
+                .panels
+                .iter()
+                .position(|other| other.id.as_ref() == Some(&mask.panel))?;
+            Some((self.layers[j].as_ref()?, mask.invert))
+        });
+        Some(self.compositor.composite(
+            &self.uniform_buffer_allocator,
+            layer,
+            &panel.transform,
+            panel.blend_mode,
+            panel.opacity,
+            mask,
+        ))
+    }
+}
@@ -0,0 +1,781 @@
+use crate::video::{
+    bars::BarsState,
+    material::{
+        ComputeContext,
+        GlobalInput::{self, *},
+        Material, MaterialContext, MaterialGeometry, MaterialShader, MaterialState, StateContext,
+    },
+    meter::MeterState,
+    parameter_block::{PARAMETER_BINDING, pack_parameter_block},
+    particles::ParticlesState,
+    shader_types::{
+        BarsParameters, CircularWaveformParameters, CustomParameters, GrayVenueGridnodeParameters,
+        ImageParameters, LineJoin, MaskedPatternParameters, MeshParameters, MeshShape,
+        MeterParameters, ParticlesParameters, RadialSpectrumParameters, ShadertoyParameters,
+        SimplePatternParameters, SpectrogramParameters, TextParameters, WaveformParameters,
+        WaveformRendering,
+    },
+    shaders,
+    shadertoy::{self, ShadertoyInputs},
+    shapes::MeshState,
+    text::{GlyphAtlas, TextState},
+    trigger,
+};
+
+use std::{fs, path::Path};
+use vulkano::{
+    descriptor_set::WriteDescriptorSet, pipeline::graphics::input_assembly::PrimitiveTopology,
+};
+
+fn validate_scale(scale_min: f32, scale_max: f32) -> Vec<(String, String)> {
+    let mut issues = Vec::new();
+    if scale_min <= 0.0 {
+        issues.push(("scale_min".into(), "must be positive".into()));
+    }
+    if scale_max <= 0.0 {
+        issues.push(("scale_max".into(), "must be positive".into()));
+    }
+    issues
+}
+
+fn validate_polar(line_width: f32, radius: f32) -> Vec<(String, String)> {
+    let mut issues = Vec::new();
+    if line_width <= 0.0 {
+        issues.push(("line_width".into(), "must be positive".into()));
+    }
+    if radius < 0.0 {
+        issues.push(("radius".into(), "must not be negative".into()));
+    }
+    issues
+}
+
+fn validate_file(field: &str, path: &Path) -> Vec<(String, String)> {
+    if path.as_os_str().is_empty() {
+        vec![(field.into(), "must be set".into())]
+    } else if !path.is_file() {
+        vec![(field.into(), format!("{} does not exist", path.display()))]
+    } else {
+        Vec::new()
+    }
+}
+
+impl Material for WaveformParameters {
+    fn name(&self) -> &'static str {
+        "waveform"
+    }
+
+    fn shader(&self) -> MaterialShader {
+        match self.rendering {
+            WaveformRendering::Distance => MaterialShader::Embedded {
+                load: shaders::load_waveform,
+                source: Some("waveform"),
+            },
+            WaveformRendering::Lines => MaterialShader::Embedded {
+                load: shaders::load_waveform_lines,
+                source: Some("waveform_lines"),
+            },
+        }
+    }
+
+    fn inputs(&self) -> &'static [GlobalInput] {
+        &[AspectRatio, Samples, Stabilization, Bass, Palette]
+    }
+
+    fn write_parameters(&self, context: &mut MaterialContext) -> Option<WriteDescriptorSet> {
+        Some(match self.rendering {
+            WaveformRendering::Distance => {
+                context.write(trigger::waveform_parameters(self, context.audio_data))
+            }
+            WaveformRendering::Lines => context.write(trigger::waveform_lines_parameters(
+                self,
+                context.audio_data,
+                context.screen_size,
+            )),
+        })
+    }
+
+    fn geometry(&self) -> Option<MaterialGeometry> {
+        (self.rendering == WaveformRendering::Lines).then(|| MaterialGeometry {
+            load: shaders::load_waveform_lines_vertex,
+            source: "waveform_lines_vertex",
+            topology: match self.join {
+                LineJoin::Miter => PrimitiveTopology::TriangleStrip,
+                LineJoin::Round => PrimitiveTopology::TriangleList,
+            },
+            additive: false,
+            depth_test: false,
+        })
+    }
+
+    fn vertex_count(&self, context: &MaterialContext) -> u32 {
+        trigger::line_vertex_count(self, context.audio_data)
+    }
+
+    fn can_reuse(&self, other: &dyn Material) -> bool {
+        other
+            .downcast_ref::<Self>()
+            .is_some_and(|other| other.rendering == self.rendering && other.join == self.join)
+    }
+
+    fn validate(&self) -> Vec<(String, String)> {
+        let mut issues = Vec::new();
+        if self.line_width <= 0.0 {
+            issues.push(("line_width".into(), "must be positive".into()));
+        }
+        if self.window < 0.0 {
+            issues.push(("window".into(), "must not be negative".into()));
+        }
+        issues
+    }
+}
+
+impl Material for SpectrogramParameters {
+    fn name(&self) -> &'static str {
+        "spectrogram"
+    }
+
+    fn shader(&self) -> MaterialShader {
+        MaterialShader::Embedded {
+            load: shaders::load_spectrogram,
+            source: Some("spectrogram"),
+        }
+    }
+
+    fn inputs(&self) -> &'static [GlobalInput] {
+        &[AspectRatio, Dft, Bass, Palette]
+    }
+
+    fn write_parameters(&self, context: &mut MaterialContext) -> Option<WriteDescriptorSet> {
+        Some(context.write::<shaders::SpectrogramParameters>(self.clone().into()))
+    }
+}
+
+impl Material for CircularWaveformParameters {
+    fn name(&self) -> &'static str {
+        "circular_waveform"
+    }
+
+    fn shader(&self) -> MaterialShader {
+        MaterialShader::Embedded {
+            load: shaders::load_circular_waveform,
+            source: Some("circular_waveform"),
+        }
+    }
+
+    fn inputs(&self) -> &'static [GlobalInput] {
+        &[AspectRatio, Samples, Stabilization, Bass, Palette]
+    }
+
+    fn write_parameters(&self, context: &mut MaterialContext) -> Option<WriteDescriptorSet> {
+        Some(context.write::<shaders::CircularWaveformParameters>(self.clone().into()))
+    }
+
+    fn validate(&self) -> Vec<(String, String)> {
+        let mut issues = validate_polar(self.line_width, self.radius);
+        if self.window <= 0.0 {
+            issues.push(("window".into(), "must be positive".into()));
+        }
+        issues
+    }
+}
+
+impl Material for RadialSpectrumParameters {
+    fn name(&self) -> &'static str {
+        "radial_spectrum"
+    }
+
+    fn shader(&self) -> MaterialShader {
+        MaterialShader::Embedded {
+            load: shaders::load_radial_spectrum,
+            source: Some("radial_spectrum"),
+        }
+    }
+
+    fn inputs(&self) -> &'static [GlobalInput] {
+        &[AspectRatio, Dft, Bass, Palette]
+    }
+
+    fn write_parameters(&self, context: &mut MaterialContext) -> Option<WriteDescriptorSet> {
+        Some(context.write::<shaders::RadialSpectrumParameters>(self.clone().into()))
+    }
+
+    fn validate(&self) -> Vec<(String, String)> {
+        let mut issues = validate_polar(self.line_width, self.radius);
+        if self.rays == 0 {
+            issues.push(("rays".into(), "must be positive".into()));
+        }
+        issues
+    }
+}
+
+impl Material for BarsParameters {
+    fn name(&self) -> &'static str {
+        "bars"
+    }
+
+    fn shader(&self) -> MaterialShader {
+        MaterialShader::Embedded {
+            load: shaders::load_bars,
+            source: Some("bars"),
+        }
+    }
+
+    // the levels are measured from the DFT when writing the parameters
+    fn inputs(&self) -> &'static [GlobalInput] {
+        &[AspectRatio, Bass, Palette]
+    }
+
+    /// The state is the levels of the bars and their peaks.
+    fn create_state(&self, _context: &StateContext) -> Result<MaterialState, String> {
+        Ok(Box::new(BarsState::default()))
+    }
+
+    fn write_parameters(&self, context: &mut MaterialContext) -> Option<WriteDescriptorSet> {
+        let state = context.state.downcast_mut::<BarsState>()?;
+        state.update(self, context.audio_data, context.frame_inputs.time_delta);
+        let parameters = state.parameters(self);
+        Some(context.write(parameters))
+    }
+
+    fn write_resources(&self, context: &mut MaterialContext) -> Vec<WriteDescriptorSet> {
+        match context.state.downcast_ref::<BarsState>() {
+            Some(state) => state.writes(context.storage_buffer_allocator),
+            None => Vec::new(),
+        }
+    }
+
+    fn validate(&self) -> Vec<(String, String)> {
+        let mut issues = Vec::new();
+        if self.bars == 0 {
+            issues.push(("bars".into(), "must be positive".into()));
+        }
+        if self.min_frequency <= 0.0 {
+            issues.push(("min_frequency".into(), "must be positive".into()));
+        }
+        if self.max_frequency <= self.min_frequency {
+            issues.push((
+                "max_frequency".into(),
+                "must be greater than min_frequency".into(),
+            ));
+        }
+        if !(0.0..1.0).contains(&self.gap) {
+            issues.push(("gap".into(), "must be at least 0 and less than 1".into()));
+        }
+        if !(0.0..1.0).contains(&self.segment_gap) {
+            issues.push((
+                "segment_gap".into(),
+                "must be at least 0 and less than 1".into(),
+            ));
+        }
+        if self.peak_hold < 0.0 {
+            issues.push(("peak_hold".into(), "must not be negative".into()));
+        }
+        if self.peak_fall < 0.0 {
+            issues.push(("peak_fall".into(), "must not be negative".into()));
+        }
+        issues
+    }
+}
+
+impl Material for MeterParameters {
+    fn name(&self) -> &'static str {
+        "meter"
+    }
+
+    fn shader(&self) -> MaterialShader {
+        MaterialShader::Embedded {
+            load: shaders::load_meter,
+            source: Some("meter"),
+        }
+    }
+
+    // the levels are written with the parameters
+    fn inputs(&self) -> &'static [GlobalInput] {
+        &[]
+    }
+
+    /// The state is the peak indicator.
+    fn create_state(&self, _context: &StateContext) -> Result<MaterialState, String> {
+        Ok(Box::new(MeterState::default()))
+    }
+
+    fn write_parameters(&self, context: &mut MaterialContext) -> Option<WriteDescriptorSet> {
+        let levels = &context.audio_data.levels;
+        let state = context.state.downcast_mut::<MeterState>()?;
+        state.update(self, levels, context.frame_inputs.time_delta);
+        let parameters = state.parameters(self, levels);
+        Some(context.write(parameters))
+    }
+
+    fn validate(&self) -> Vec<(String, String)> {
+        let mut issues = Vec::new();
+        if self.max_level <= self.min_level {
+            issues.push(("max_level".into(), "must be greater than min_level".into()));
+        }
+        if self.peak_hold < 0.0 {
+            issues.push(("peak_hold".into(), "must not be negative".into()));
+        }
+        if self.peak_fall < 0.0 {
+            issues.push(("peak_fall".into(), "must not be negative".into()));
+        }
+        issues
+    }
+}
+
+impl Material for ParticlesParameters {
+    fn name(&self) -> &'static str {
+        "particles"
+    }
+
+    fn shader(&self) -> MaterialShader {
+        MaterialShader::Embedded {
+            load: shaders::load_particles,
+            source: Some("particles"),
+        }
+    }
+
+    fn inputs(&self) -> &'static [GlobalInput] {
+        &[AspectRatio, Bass, Palette]
+    }
+
+    /// The state is the particles, on the GPU.
+    fn create_state(&self, context: &StateContext) -> Result<MaterialState, String> {
+        let state = ParticlesState::new(self, context.compute_shader, context.memory_allocator)
+            .map_err(|e| e.to_string())?;
+        Ok(Box::new(state))
+    }
+
+    fn compute_source(&self) -> Option<&'static str> {
+        Some("particles_update")
+    }
+
+    fn record_compute(&self, context: &mut ComputeContext) {
+        if let Some(state) = context.state.downcast_mut::<ParticlesState>() {
+            state.record(
+                self,
+                context.builder,
+                context.descriptor_set_allocator,
+                context.uniform_buffer_allocator,
+                context.frame_inputs,
+                context.audio_data,
+            );
+        }
+    }
+
+    fn write_parameters(&self, context: &mut MaterialContext) -> Option<WriteDescriptorSet> {
+        Some(context.write(shaders::ParticlesParameters::from(self.clone())))
+    }
+
+    fn write_resources(&self, context: &mut MaterialContext) -> Vec<WriteDescriptorSet> {
+        match context.state.downcast_ref::<ParticlesState>() {
+            Some(state) => state.writes(),
+            None => Vec::new(),
+        }
+    }
+
+    fn geometry(&self) -> Option<MaterialGeometry> {
+        Some(MaterialGeometry {
+            load: shaders::load_particles_vertex,
+            source: "particles_vertex",
+            topology: PrimitiveTopology::TriangleList,
+            additive: true,
+            depth_test: false,
+        })
+    }
+
+    fn vertex_count(&self, _context: &MaterialContext) -> u32 {
+        self.max_particles * 6
+    }
+
+    fn can_reuse(&self, other: &dyn Material) -> bool {
+        other
+            .downcast_ref::<Self>()
+            .is_some_and(|other| other.max_particles == self.max_particles)
+    }
+
+    fn validate(&self) -> Vec<(String, String)> {
+        let mut issues = Vec::new();
+        if self.max_particles == 0 {
+            issues.push(("max_particles".into(), "must be positive".into()));
+        }
+        if self.rate < 0.0 {
+            issues.push(("rate".into(), "must not be negative".into()));
+        }
+        if self.lifetime <= 0.0 {
+            issues.push(("lifetime".into(), "must be positive".into()));
+        }
+        if self.size <= 0.0 {
+            issues.push(("size".into(), "must be positive".into()));
+        }
+        issues
+    }
+}
+
+impl Material for MeshParameters {
+    fn name(&self) -> &'static str {
+        "mesh"
+    }
+
+    fn shader(&self) -> MaterialShader {
+        MaterialShader::Embedded {
+            load: shaders::load_mesh,
+            source: Some("mesh"),
+        }
+    }
+
+    fn inputs(&self) -> &'static [GlobalInput] {
+        &[AspectRatio, Samples, Stabilization, Dft, Bass, Palette]
+    }
+
+    /// The state is the mesh, on the GPU.
+    fn create_state(&self, context: &StateContext) -> Result<MaterialState, String> {
+        Ok(Box::new(MeshState::new(self, context.memory_allocator)?))
+    }
+
+    fn write_parameters(&self, context: &mut MaterialContext) -> Option<WriteDescriptorSet> {
+        let state = context.state.downcast_mut::<MeshState>()?;
+        state.update(self, context.frame_inputs.time_delta);
+        let parameters = state.parameters(self);
+        Some(context.write(parameters))
+    }
+
+    fn write_resources(&self, context: &mut MaterialContext) -> Vec<WriteDescriptorSet> {
+        match context.state.downcast_ref::<MeshState>() {
+            Some(state) => state.writes(),
+            None => Vec::new(),
+        }
+    }
+
+    fn geometry(&self) -> Option<MaterialGeometry> {
+        Some(MaterialGeometry {
+            load: shaders::load_mesh_vertex,
+            source: "mesh_vertex",
+            topology: PrimitiveTopology::TriangleList,
+            additive: false,
+            depth_test: true,
+        })
+    }
+
+    fn vertex_count(&self, context: &MaterialContext) -> u32 {
+        context
+            .state
+            .downcast_ref::<MeshState>()
+            .map_or(0, MeshState::vertex_count)
+    }
+
+    // the mesh in the state is the shape's
+    fn can_reuse(&self, other: &dyn Material) -> bool {
+        other.downcast_ref::<Self>().is_some_and(|other| {
+            other.shape == self.shape && other.path == self.path && other.detail == self.detail
+        })
+    }
+
+    fn resolve_paths(&mut self, directory: &Path) {
+        if !self.path.as_os_str().is_empty() {
+            self.path = directory.join(&self.path);
+        }
+    }
+
+    fn validate(&self) -> Vec<(String, String)> {
+        let mut issues = Vec::new();
+        if self.shape == MeshShape::Obj {
+            issues.extend(validate_file("path", &self.path));
+            let is_obj = self
+                .path
+                .extension()
+                .is_some_and(|extension| extension.eq_ignore_ascii_case("obj"));
+            if !self.path.as_os_str().is_empty() && !is_obj {
+                issues.push((
+                    "path".into(),
+                    "must be an .obj file, other mesh formats such as glTF are not supported"
+                        .into(),
+                ));
+            }
+        }
+        if self.detail == 0 {
+            issues.push(("detail".into(), "must be positive".into()));
+        }
+        if !(0.0 < self.field_of_view && self.field_of_view < 180.0) {
+            issues.push((
+                "field_of_view".into(),
+                "must be between 0 and 180 degrees".into(),
+            ));
+        }
+        if self.camera_distance <= 1.0 + self.amount.abs() {
+            issues.push((
+                "camera_distance".into(),
+                "must be more than 1 plus the amount, outside the mesh".into(),
+            ));
+        }
+        issues
+    }
+}
+
+impl Material for SimplePatternParameters {
+    fn name(&self) -> &'static str {
+        "simple_pattern"
+    }
+
+    fn shader(&self) -> MaterialShader {
+        MaterialShader::Embedded {
+            load: shaders::load_simple_pattern,
+            source: Some("simple_pattern"),
+        }
+    }
+
+    fn inputs(&self) -> &'static [GlobalInput] {
+        &[AspectRatio, Bass, Palette]
+    }
+
+    fn write_parameters(&self, context: &mut MaterialContext) -> Option<WriteDescriptorSet> {
+        Some(context.write::<shaders::SimplePatternParameters>(self.clone().into()))
+    }
+}
+
+impl Material for MaskedPatternParameters {
+    fn name(&self) -> &'static str {
+        "masked_pattern"
+    }
+
+    fn shader(&self) -> MaterialShader {
+        MaterialShader::Embedded {
+            load: shaders::load_masked_pattern,
+            source: Some("masked_pattern"),
+        }
+    }
+
+    fn inputs(&self) -> &'static [GlobalInput] {
+        &[AspectRatio, Bass, Image, Palette]
+    }
+
+    fn image(&self) -> Option<&str> {
+        self.image.as_deref()
+    }
+
+    fn write_parameters(&self, context: &mut MaterialContext) -> Option<WriteDescriptorSet> {
+        Some(context.write::<shaders::MaskedPatternParameters>(self.clone().into()))
+    }
+
+    fn validate(&self) -> Vec<(String, String)> {
+        validate_scale(self.scale_min, self.scale_max)
+    }
+}
+
+impl Material for ImageParameters {
+    fn name(&self) -> &'static str {
+        "image"
+    }
+
+    fn shader(&self) -> MaterialShader {
+        MaterialShader::Embedded {
+            load: shaders::load_image,
+            source: Some("image"),
+        }
+    }
+
+    fn inputs(&self) -> &'static [GlobalInput] {
+        &[Bass, Image]
+    }
+
+    fn image(&self) -> Option<&str> {
+        self.image.as_deref()
+    }
+
+    fn write_parameters(&self, context: &mut MaterialContext) -> Option<WriteDescriptorSet> {
+        Some(context.write::<shaders::ImageParameters>(self.clone().into()))
+    }
+
+    fn validate(&self) -> Vec<(String, String)> {
+        validate_scale(self.scale_min, self.scale_max)
+    }
+}
+
+impl Material for GrayVenueGridnodeParameters {
+    fn name(&self) -> &'static str {
+        "gray_venue_gridnode"
+    }
+
+    fn shader(&self) -> MaterialShader {
+        MaterialShader::Embedded {
+            load: shaders::load_gray_venue_gridnode,
+            source: Some("gray_venue_gridnode"),
+        }
+    }
+
+    fn inputs(&self) -> &'static [GlobalInput] {
+        &[Dft, Bass]
+    }
+
+    fn write_parameters(&self, context: &mut MaterialContext) -> Option<WriteDescriptorSet> {
+        Some(context.write::<shaders::GrayVenueGridnodeParameters>(self.clone().into()))
+    }
+}
+
+impl Material for CustomParameters {
+    fn name(&self) -> &'static str {
+        "custom"
+    }
+
+    fn shader(&self) -> MaterialShader {
+        MaterialShader::File {
+            path: self.shader.clone(),
+            preprocess: None,
+        }
+    }
+
+    // the shader decides what it reads, the inputs it doesn't declare are dropped when the
+    // descriptor set is created
+    fn inputs(&self) -> &'static [GlobalInput] {
+        &[
+            AspectRatio,
+            Samples,
+            Stabilization,
+            Dft,
+            Bass,
+            Image,
+            Palette,
+        ]
+    }
+
+    /// The state is the uniform block packed from `uniforms`.
+    fn create_state(&self, context: &StateContext) -> Result<MaterialState, String> {
+        let words = context
+            .words
+            .ok_or("custom shaders are compiled from their source")?;
+        Ok(Box::new(pack_parameter_block(words, &self.uniforms)?))
+    }
+
+    fn write_parameters(&self, context: &mut MaterialContext) -> Option<WriteDescriptorSet> {
+        let parameter_block = context.state.downcast_ref::<Vec<u8>>()?;
+        // the shader declares no block at binding 10, and a buffer can't be empty
+        if parameter_block.is_empty() {
+            return None;
+        }
+        let buffer = context
+            .uniform_buffer_allocator
+            .allocate_slice(parameter_block.len() as u64)
+            .unwrap();
+        buffer.write().unwrap().copy_from_slice(parameter_block);
+        Some(WriteDescriptorSet::buffer(PARAMETER_BINDING, buffer))
+    }
+
+    // the shader and its uniforms are all in the parameters
+    fn can_reuse(&self, other: &dyn Material) -> bool {
+        other.downcast_ref::<Self>() == Some(self)
+    }
+
+    fn resolve_paths(&mut self, directory: &Path) {
+        if !self.shader.as_os_str().is_empty() {
+            self.shader = directory.join(&self.shader);
+        }
+    }
+
+    fn validate(&self) -> Vec<(String, String)> {
+        validate_file("shader", &self.shader)
+    }
+}
+
+impl Material for ShadertoyParameters {
+    fn name(&self) -> &'static str {
+        "shadertoy"
+    }
+
+    fn shader(&self) -> MaterialShader {
+        MaterialShader::File {
+            path: self.shader.clone(),
+            preprocess: Some(shadertoy::wrap),
+        }
+    }
+
+    fn inputs(&self) -> &'static [GlobalInput] {
+        &[AudioTexture]
+    }
+
+    fn write_parameters(&self, context: &mut MaterialContext) -> Option<WriteDescriptorSet> {
+        Some(context.write(ShadertoyInputs::new(
+            self,
+            context.transform,
+            context.screen_size,
+            context.frame_inputs,
+        )))
+    }
+
+    fn can_reuse(&self, other: &dyn Material) -> bool {
+        other.downcast_ref::<Self>() == Some(self)
+    }
+
+    fn resolve_paths(&mut self, directory: &Path) {
+        if !self.shader.as_os_str().is_empty() {
+            self.shader = directory.join(&self.shader);
+        }
+    }
+
+    fn validate(&self) -> Vec<(String, String)> {
+        validate_file("shader", &self.shader)
+    }
+}
+
+impl Material for TextParameters {
+    fn name(&self) -> &'static str {
+        "text"
+    }
+
+    fn shader(&self) -> MaterialShader {
+        MaterialShader::Embedded {
+            load: shaders::load_text,
+            source: Some("text"),
+        }
+    }
+
+    fn inputs(&self) -> &'static [GlobalInput] {
+        &[AspectRatio, Bass, Palette]
+    }
+
+    /// The state is the glyph atlas of the font and the last layout.
+    fn create_state(&self, _context: &StateContext) -> Result<MaterialState, String> {
+        let font =
+            fs::read(&self.font).map_err(|e| format!("reading {}: {e}", self.font.display()))?;
+        Ok(Box::new(TextState::new(GlyphAtlas::new(font)?)))
+    }
+
+    fn write_parameters(&self, context: &mut MaterialContext) -> Option<WriteDescriptorSet> {
+        let aspect_ratio =
+            context.screen_size.x / context.screen_size.y * context.transform.get_aspect_ratio();
+        let state = context.state.downcast_mut::<TextState>()?;
+        state.update(self, context.frame_inputs, context.audio_data, aspect_ratio);
+        let parameters = state.parameters(self);
+        Some(context.write(parameters))
+    }
+
+    fn write_resources(&self, context: &mut MaterialContext) -> Vec<WriteDescriptorSet> {
+        match context.state.downcast_ref::<TextState>() {
+            Some(state) => state.writes(context.storage_buffer_allocator),
+            None => Vec::new(),
+        }
+    }
+
+    // the atlas in the state is the font's
+    fn can_reuse(&self, other: &dyn Material) -> bool {
+        other
+            .downcast_ref::<Self>()
+            .is_some_and(|other| other.font == self.font)
+    }
+
+    fn resolve_paths(&mut self, directory: &Path) {
+        if !self.font.as_os_str().is_empty() {
+            self.font = directory.join(&self.font);
+        }
+    }
+
+    fn validate(&self) -> Vec<(String, String)> {
+        let mut issues = validate_file("font", &self.font);
+        if self.size <= 0.0 {
+            issues.push(("size".into(), "must be positive".into()));
+        }
+        if self.line_spacing <= 0.0 {
+            issues.push(("line_spacing".into(), "must be positive".into()));
+        }
+        issues.extend(validate_scale(self.scale_min, self.scale_max));
+        issues
+    }
+}
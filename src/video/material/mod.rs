@@ -0,0 +1,237 @@
+pub mod builtin;
+pub mod registry;
+
+pub use registry::{MaterialRegistry, register_material, registry};
+
+use crate::{
+    audio::AudioData,
+    video::{
+        FrameInputs, PanelTransform, create_write_descriptor_set,
+        parameter_block::PARAMETER_BINDING,
+    },
+};
+
+use glam::Vec2;
+use serde::Serialize;
+use std::{
+    any::Any,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use vulkano::{
+    Validated, VulkanError,
+    buffer::{BufferContents, allocator::SubbufferAllocator},
+    command_buffer::{AutoCommandBufferBuilder, PrimaryAutoCommandBuffer},
+    descriptor_set::{WriteDescriptorSet, allocator::StandardDescriptorSetAllocator},
+    device::Device,
+    memory::allocator::StandardMemoryAllocator,
+    pipeline::graphics::input_assembly::PrimitiveTopology,
+    shader::ShaderModule,
+};
+
+/// A value shared by all panels that a material's shader can read, bound at the binding of its
+/// include in `uniforms/`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum GlobalInput {
+    AspectRatio,
+    Samples,
+    Stabilization,
+    Dft,
+    Bass,
+    Image,
+    AudioTexture,
+    /// The config's `palette`, read by `get_color` of `structs/pattern.glsl`.
+    Palette,
+}
+
+/// Loads an embedded shader module, like the `load` functions generated by
+/// `vulkano_shaders::shader!`.
+pub type LoadShader = fn(Arc<Device>) -> Result<Arc<ShaderModule>, Validated<VulkanError>>;
+
+/// Where the fragment shader of a material comes from.
+pub enum MaterialShader {
+    /// Built into the binary. `source` names the file in the shader source directory it is
+    /// compiled from instead with `--dev-shaders`, which only holds lava's own shaders.
+    Embedded {
+        load: LoadShader,
+        source: Option<&'static str>,
+    },
+    /// Compiled at runtime from a GLSL file, after passing its text through `preprocess`.
+    File {
+        path: PathBuf,
+        preprocess: Option<fn(&str) -> String>,
+    },
+}
+
+/// The primitives of a material that draws its own instead of filling the panel's quad.
+pub struct MaterialGeometry {
+    /// Generates the vertices from `gl_VertexIndex`, without vertex buffers. It reads the same
+    /// bindings as the fragment shader, with the panel's transform at binding 0.
+    pub load: LoadShader,
+    /// Names the file it is compiled from with `--dev-shaders`, like `MaterialShader::Embedded`.
+    pub source: &'static str,
+    pub topology: PrimitiveTopology,
+    /// Whether overlapping primitives add up like light instead of covering each other.
+    pub additive: bool,
+    /// Whether primitives hide the ones behind them. The depth attachment is cleared before the
+    /// panel is drawn.
+    pub depth_test: bool,
+}
+
+/// The state a panel keeps between frames, see [`Material::create_state`].
+pub type MaterialState = Box<dyn Any + Send>;
+
+/// What a material can use to create the state of a panel, see [`Material::create_state`].
+pub struct StateContext<'a> {
+    /// The SPIR-V of shaders compiled at runtime.
+    pub words: Option<&'a [u32]>,
+    /// The material's compute shader compiled at runtime from its `compute_source`, the embedded
+    /// one is used if `None`.
+    pub compute_shader: Option<&'a Arc<ShaderModule>>,
+    /// For buffers on the GPU that last as long as the state.
+    pub memory_allocator: &'a Arc<StandardMemoryAllocator>,
+}
+
+/// What a material can use to record GPU work before a frame is drawn, see
+/// [`Material::record_compute`].
+pub struct ComputeContext<'a> {
+    pub builder: &'a mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+    pub descriptor_set_allocator: &'a Arc<StandardDescriptorSetAllocator>,
+    pub uniform_buffer_allocator: &'a SubbufferAllocator,
+    pub frame_inputs: &'a FrameInputs,
+    pub audio_data: &'a AudioData,
+    /// The state created by `Material::create_state` for this panel.
+    pub state: &'a mut (dyn Any + Send),
+}
+
+/// What a material can use to fill in its parameters for a frame.
+pub struct MaterialContext<'a> {
+    pub uniform_buffer_allocator: &'a SubbufferAllocator,
+    pub storage_buffer_allocator: &'a SubbufferAllocator,
+    pub screen_size: Vec2,
+    pub transform: &'a PanelTransform,
+    pub frame_inputs: &'a FrameInputs,
+    pub audio_data: &'a AudioData,
+    /// The state created by `Material::create_state` for this panel.
+    pub state: &'a mut (dyn Any + Send),
+}
+
+impl MaterialContext<'_> {
+    /// Writes `parameters` as the material's uniform block.
+    pub fn write<T: BufferContents>(&self, parameters: T) -> WriteDescriptorSet {
+        create_write_descriptor_set(self.uniform_buffer_allocator, PARAMETER_BINDING, parameters)
+    }
+}
+
+/// How a panel is drawn: its fragment shader and the values it reads.
+///
+/// Implemented by the parameters of a material as they appear in the config, and registered by
+/// their `type` with [`register_material`].
+pub trait Material: DynMaterial + Send + Sync + 'static {
+    /// The `type` of the material in the config.
+    fn name(&self) -> &'static str;
+
+    fn shader(&self) -> MaterialShader;
+
+    /// The global inputs the shader reads, the others aren't bound.
+    fn inputs(&self) -> &'static [GlobalInput];
+
+    /// Creates the state a panel using this material keeps between frames, once its shader is
+    /// compiled.
+    fn create_state(&self, context: &StateContext) -> Result<MaterialState, String> {
+        let _ = context;
+        Ok(Box::new(()))
+    }
+
+    /// Names the file the compute shader of the state is compiled from with `--dev-shaders`, like
+    /// `MaterialShader::Embedded`, if it has one.
+    fn compute_source(&self) -> Option<&'static str> {
+        None
+    }
+
+    /// Records the compute work of a frame, before any panel is drawn. The results are kept in
+    /// the state for the shaders to read.
+    fn record_compute(&self, context: &mut ComputeContext) {
+        let _ = context;
+    }
+
+    /// The primitives drawn instead of the panel's quad, if any.
+    fn geometry(&self) -> Option<MaterialGeometry> {
+        None
+    }
+
+    /// The vertices drawn for a frame when the material has a geometry, after
+    /// `write_parameters`.
+    fn vertex_count(&self, context: &MaterialContext) -> u32 {
+        let _ = context;
+        0
+    }
+
+    /// The name of the texture in the config's `textures` bound for the `Image` input, the
+    /// image at `image_path` if `None`.
+    fn image(&self) -> Option<&str> {
+        None
+    }
+
+    /// The write of the parameter block at binding 10 for a frame, if the shader has one.
+    fn write_parameters(&self, context: &mut MaterialContext) -> Option<WriteDescriptorSet>;
+
+    /// The writes of the other buffers and images the shader reads for a frame, after
+    /// `write_parameters`.
+    fn write_resources(&self, context: &mut MaterialContext) -> Vec<WriteDescriptorSet> {
+        let _ = context;
+        Vec::new()
+    }
+
+    /// Whether a panel switching from `self` to `other` can keep its pipeline and state.
+    fn can_reuse(&self, other: &dyn Material) -> bool {
+        self.name() == other.name()
+    }
+
+    /// Makes the paths in the parameters relative to the config file's `directory`.
+    fn resolve_paths(&mut self, directory: &Path) {
+        let _ = directory;
+    }
+
+    /// Checks values that parse fine but can't work, as `(field, message)` pairs with fields
+    /// relative to the parameters.
+    fn validate(&self) -> Vec<(String, String)> {
+        Vec::new()
+    }
+}
+
+/// The object safe parts of `Clone`, `PartialEq` and `Serialize`, implemented for every material
+/// that has them.
+pub trait DynMaterial {
+    fn clone_box(&self) -> Box<dyn Material>;
+    fn eq_dyn(&self, other: &dyn Material) -> bool;
+    fn to_value(&self) -> serde_json::Value;
+    fn as_any(&self) -> &dyn Any;
+}
+
+impl<T: Material + Clone + PartialEq + Serialize> DynMaterial for T {
+    fn clone_box(&self) -> Box<dyn Material> {
+        Box::new(self.clone())
+    }
+
+    fn eq_dyn(&self, other: &dyn Material) -> bool {
+        other
+            .as_any()
+            .downcast_ref::<T>()
+            .is_some_and(|other| self == other)
+    }
+
+    fn to_value(&self) -> serde_json::Value {
+        serde_json::to_value(self).unwrap()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl dyn Material {
+    pub fn downcast_ref<T: Material>(&self) -> Option<&T> {
+        self.as_any().downcast_ref()
+    }
+}
@@ -0,0 +1,162 @@
+use crate::{
+    config::{
+        de::{DeError, unknown},
+        jsonc::{Node, Position, Value},
+    },
+    video::{
+        material::Material,
+        shader_types::{
+            BarsParameters, CircularWaveformParameters, CustomParameters,
+            GrayVenueGridnodeParameters, ImageParameters, MaskedPatternParameters, MeshParameters,
+            MeterParameters, ParticlesParameters, RadialSpectrumParameters, ShadertoyParameters,
+            SimplePatternParameters, SpectrogramParameters, TextParameters, WaveformParameters,
+        },
+    },
+};
+
+use schemars::{JsonSchema, Schema, SchemaGenerator, json_schema};
+use serde::de::DeserializeOwned;
+use std::{
+    collections::BTreeMap,
+    sync::{LazyLock, RwLock, RwLockReadGuard},
+};
+
+struct Entry {
+    from_value: fn(serde_json::Value) -> Result<Box<dyn Material>, serde_json::Error>,
+    from_node: fn(&Node) -> Result<Box<dyn Material>, serde_path_to_error::Error<DeError>>,
+    schema: fn(&mut SchemaGenerator) -> Schema,
+}
+
+/// The materials configs can use, by their `type`.
+pub struct MaterialRegistry {
+    entries: BTreeMap<&'static str, Entry>,
+}
+
+/// Global so that configs can be deserialized without passing it around.
+static REGISTRY: LazyLock<RwLock<MaterialRegistry>> =
+    LazyLock::new(|| RwLock::new(MaterialRegistry::with_builtins()));
+
+/// Makes `M` available to configs under its `type`, replacing any material of the same type.
+///
+/// Must be called before loading the configs that use it.
+pub fn register_material<M: Material + Default + DeserializeOwned + JsonSchema>() {
+    REGISTRY.write().unwrap().register::<M>();
+}
+
+pub fn registry() -> RwLockReadGuard<'static, MaterialRegistry> {
+    REGISTRY.read().unwrap()
+}
+
+impl MaterialRegistry {
+    fn with_builtins() -> Self {
+        let mut registry = Self {
+            entries: BTreeMap::new(),
+        };
+        registry.register::<WaveformParameters>();
+        registry.register::<SpectrogramParameters>();
+        registry.register::<CircularWaveformParameters>();
+        registry.register::<RadialSpectrumParameters>();
+        registry.register::<BarsParameters>();
+        registry.register::<MeterParameters>();
+        registry.register::<ParticlesParameters>();
+        registry.register::<MeshParameters>();
+        registry.register::<SimplePatternParameters>();
+        registry.register::<MaskedPatternParameters>();
+        registry.register::<ImageParameters>();
+        registry.register::<GrayVenueGridnodeParameters>();
+        registry.register::<CustomParameters>();
+        registry.register::<ShadertoyParameters>();
+        registry.register::<TextParameters>();
+        registry
+    }
+
+    pub fn register<M: Material + Default + DeserializeOwned + JsonSchema>(&mut self) {
+        self.entries.insert(
+            M::default().name(),
+            Entry {
+                from_value: |value| Ok(Box::new(serde_json::from_value::<M>(value)?)),
+                from_node: |node| Ok(Box::new(serde_path_to_error::deserialize::<_, M>(node)?)),
+                schema: |generator| generator.subschema_for::<M>(),
+            },
+        );
+    }
+
+    pub fn names(&self) -> Vec<&'static str> {
+        self.entries.keys().copied().collect()
+    }
+
+    fn entry(&self, name: &str) -> Result<&Entry, String> {
+        self.entries
+            .get(name)
+            .ok_or_else(|| unknown("type", name, &self.names()))
+    }
+
+    /// Parses the parameters of a material of type `name`.
+    pub fn parse(
+        &self,
+        name: &str,
+        parameters: serde_json::Value,
+    ) -> Result<Box<dyn Material>, String> {
+        (self.entry(name)?.from_value)(parameters).map_err(|e| format!("in `parameters`: {e}"))
+    }
+
+    /// Parses a material written in a config, reporting errors as a path relative to `node`,
+    /// the position and a message.
+    pub fn parse_node(
+        &self,
+        node: &Node,
+    ) -> Result<Box<dyn Material>, (String, Option<Position>, String)> {
+        let Some(kind) = node.get("type") else {
+            return Err((
+                String::new(),
+                Some(node.position),
+                "missing field `type`".into(),
+            ));
+        };
+        let Value::String(name) = &kind.value else {
+            return Err((
+                "type".into(),
+                Some(kind.position),
+                "expected the name of a material".into(),
+            ));
+        };
+        let entry = self
+            .entry(name)
+            .map_err(|message| ("type".into(), Some(kind.position), message))?;
+
+        let empty = Node {
+            value: Value::Object(Vec::new()),
+            position: node.position,
+        };
+        (entry.from_node)(node.get("parameters").unwrap_or(&empty)).map_err(|e| {
+            let path = e.path().to_string();
+            let inner = e.into_inner();
+            let path = if path == "." {
+                "parameters".into()
+            } else {
+                format!("parameters.{path}")
+            };
+            (path, inner.position, inner.message)
+        })
+    }
+
+    /// A schema accepting any registered material.
+    pub fn schema(&self, generator: &mut SchemaGenerator) -> Schema {
+        let materials = self
+            .entries
+            .iter()
+            .map(|(name, entry)| {
+                json_schema!({
+                    "type": "object",
+                    "properties": {
+                        "type": { "const": name },
+                        "parameters": (entry.schema)(generator),
+                    },
+                    "required": ["type"],
+                    "additionalProperties": false,
+                })
+            })
+            .collect::<Vec<_>>();
+        json_schema!({ "oneOf": materials })
+    }
+}
@@ -0,0 +1,64 @@
+use crate::{
+    audio::Levels,
+    video::{
+        shader_types::{MeterBallistics, MeterOrientation, MeterParameters},
+        shaders,
+    },
+};
+
+/// The peak indicator of a meter panel.
+#[derive(Default)]
+pub struct MeterState {
+    /// In dBFS, `None` until the first frame.
+    peak: Option<f32>,
+    /// Seconds since the peak was reached.
+    held: f32,
+}
+
+impl MeterState {
+    pub fn update(&mut self, parameters: &MeterParameters, levels: &Levels, time_delta: f32) {
+        match self.peak {
+            Some(peak) if peak > levels.peak => {
+                self.held += time_delta;
+                if self.held > parameters.peak_hold {
+                    self.peak = Some((peak - parameters.peak_fall * time_delta).max(levels.peak));
+                }
+            }
+            _ => {
+                self.peak = Some(levels.peak);
+                self.held = 0.0;
+            }
+        }
+    }
+
+    pub fn parameters(
+        &self,
+        parameters: &MeterParameters,
+        levels: &Levels,
+    ) -> shaders::MeterParameters {
+        let level = match parameters.ballistics {
+            MeterBallistics::Vu => levels.vu,
+            MeterBallistics::Ppm => levels.ppm,
+            MeterBallistics::Rms => levels.rms,
+            MeterBallistics::LufsMomentary => levels.lufs_momentary,
+            MeterBallistics::LufsShortTerm => levels.lufs_short_term,
+        };
+        // along the meter, from 0 at `min_level` to 1 at `max_level`
+        let position = |level: f32| {
+            ((level - parameters.min_level) / (parameters.max_level - parameters.min_level))
+                .clamp(0.0, 1.0)
+        };
+
+        shaders::MeterParameters {
+            color: parameters.color.to_array(),
+            level: position(level),
+            warning_color: parameters.warning_color.to_array(),
+            warning: position(parameters.warning_level),
+            danger_color: parameters.danger_color.to_array(),
+            danger: position(parameters.danger_level),
+            peak: position(self.peak.unwrap_or(parameters.min_level)),
+            horizontal: (parameters.orientation == MeterOrientation::Horizontal) as u32,
+            show_peak: (parameters.peak_hold > 0.0) as u32,
+        }
+    }
+}
@@ -1,23 +1,54 @@
+pub mod audio_texture;
+pub mod bars;
 pub mod create_write_descriptor_set;
+pub mod feedback;
+pub mod frame_inputs;
 pub mod global_writes;
+pub mod gpu_dft;
+pub mod layer;
+pub mod material;
 pub mod mesh;
+pub mod meter;
 pub mod model;
+pub mod palette;
 pub mod panel;
 pub mod panel_transform;
+pub mod parameter_block;
+pub mod particles;
+pub mod post;
 pub mod render_context;
+pub mod renderer;
+pub mod shader_compiler;
+pub mod shader_reload;
 pub mod shader_types;
 pub mod shaders;
+pub mod shadertoy;
+pub mod shapes;
+pub mod text;
 pub mod texture;
+pub mod trigger;
 pub mod video_engine;
 pub mod window_size_dependent_setup;
 
+pub use audio_texture::AudioTexture;
 pub use create_write_descriptor_set::create_write_descriptor_set;
+pub use feedback::Feedback;
+pub use frame_inputs::{FrameInputs, Mouse};
 pub use global_writes::GlobalWrites;
+pub use gpu_dft::GpuDft;
+pub use layer::BlendMode;
+pub use material::{Material, register_material};
 pub use mesh::Mesh;
 pub use model::{INDICES, POSITIONS, Position, UVS, Uv};
-pub use panel::{Panel, PanelMaterial};
+pub use palette::{Palette, PaletteTexture};
+pub use panel::{Panel, PanelMask, PanelMaterial};
 pub use panel_transform::PanelTransform;
+pub use post::{PostEffect, Tonemap};
 pub use render_context::RenderContext;
-pub use texture::Texture;
+pub use renderer::Renderer;
+pub use shader_compiler::ShaderCompiler;
+pub use texture::{Texture, TextureConfig};
 pub use video_engine::VideoEngine;
-pub use window_size_dependent_setup::window_size_dependent_setup;
+pub use window_size_dependent_setup::{
+    create_geometry_pipeline, create_pipeline, window_size_dependent_setup,
+};
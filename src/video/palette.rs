@@ -0,0 +1,350 @@
+use crate::error::LavaError;
+
+use glam::{DMat3, DVec3, Vec3, dvec3};
+use schemars::JsonSchema;
+use serde::{
+    Deserialize, Deserializer, Serialize,
+    de::{self, IntoDeserializer, SeqAccess, Visitor, value::SeqAccessDeserializer},
+};
+use std::{fmt, sync::Arc};
+use vulkano::{
+    buffer::{Buffer, BufferCreateInfo, BufferUsage},
+    command_buffer::{
+        AutoCommandBufferBuilder, CommandBufferUsage, CopyBufferToImageInfo,
+        PrimaryCommandBufferAbstract, allocator::StandardCommandBufferAllocator,
+    },
+    descriptor_set::WriteDescriptorSet,
+    device::{DeviceOwned, Queue},
+    format::Format,
+    image::{
+        Image, ImageCreateInfo, ImageType, ImageUsage,
+        sampler::{Filter, Sampler, SamplerAddressMode, SamplerCreateInfo},
+        view::ImageView,
+    },
+    memory::allocator::{AllocationCreateInfo, MemoryTypeFilter, StandardMemoryAllocator},
+};
+
+/// The colors patterns sweep through instead of the OkLCH hues, the same for every panel.
+#[derive(Clone, PartialEq, Serialize, JsonSchema)]
+#[serde(untagged)]
+pub enum Palette {
+    Preset(PalettePreset),
+    /// Blended in OkLab between their positions, sorted by position.
+    Stops(Vec<ColorStop>),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PalettePreset {
+    /// Black through purple and red to pale yellow.
+    Magma,
+    /// Dusk purple through pink and orange to gold.
+    Sunset,
+    /// Navy through blue and cyan to white.
+    Ice,
+}
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ColorStop {
+    /// From 0 to 1 along the palette.
+    pub position: f32,
+    /// In OkLab: the lightness from 0 to 1, then the green-red and blue-yellow axes.
+    #[schemars(with = "[f32; 3]")]
+    pub color: Vec3,
+}
+
+impl PalettePreset {
+    pub fn stops(self) -> Vec<ColorStop> {
+        let colors: &[[f32; 3]] = match self {
+            Self::Magma => &[
+                [0.048, -0.003, -0.033],
+                [0.309, 0.067, -0.133],
+                [0.466, 0.149, -0.077],
+                [0.621, 0.181, 0.039],
+                [0.789, 0.088, 0.097],
+                [0.979, -0.025, 0.075],
+            ],
+            Self::Sunset => &[
+                [0.276, 0.040, -0.081],
+                [0.430, 0.126, -0.060],
+                [0.564, 0.177, 0.036],
+                [0.707, 0.113, 0.115],
+                [0.889, 0.005, 0.133],
+            ],
+            Self::Ice => &[
+                [0.234, -0.011, -0.060],
+                [0.427, -0.028, -0.107],
+                [0.615, -0.054, -0.102],
+                [0.828, -0.058, -0.044],
+                [0.983, -0.008, -0.008],
+            ],
+        };
+        colors
+            .iter()
+            .enumerate()
+            .map(|(i, color)| ColorStop {
+                position: i as f32 / (colors.len() - 1) as f32,
+                color: Vec3::from_array(*color),
+            })
+            .collect()
+    }
+}
+
+impl Palette {
+    pub fn stops(&self) -> Vec<ColorStop> {
+        let mut stops = match self {
+            Self::Preset(preset) => preset.stops(),
+            Self::Stops(stops) => stops.clone(),
+        };
+        stops.sort_by(|a, b| a.position.total_cmp(&b.position));
+        stops
+    }
+
+    pub fn validate(&self) -> Vec<(String, String)> {
+        let Self::Stops(stops) = self else {
+            return Vec::new();
+        };
+        if stops.is_empty() {
+            return vec![(String::new(), "needs at least one color stop".into())];
+        }
+        stops
+            .iter()
+            .enumerate()
+            .filter(|(_, stop)| !(0.0..=1.0).contains(&stop.position))
+            .map(|(i, _)| (format!("[{i}].position"), "must be between 0 and 1".into()))
+            .collect()
+    }
+}
+
+/// The color at `position` of the sorted `stops`, blended in OkLab between the stops around it.
+fn blend(stops: &[ColorStop], position: f32) -> Vec3 {
+    let next = stops.partition_point(|stop| stop.position < position);
+    match (stops.get(next.wrapping_sub(1)), stops.get(next)) {
+        (Some(previous), Some(next)) if next.position > previous.position => {
+            let t = (position - previous.position) / (next.position - previous.position);
+            previous.color.lerp(next.color, t)
+        }
+        (_, Some(stop)) | (Some(stop), None) => stop.color,
+        (None, None) => Vec3::ZERO,
+    }
+}
+
+impl<'de> Deserialize<'de> for Palette {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct PaletteVisitor;
+
+        impl<'de> Visitor<'de> for PaletteVisitor {
+            type Value = Palette;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "the name of a preset or a list of color stops")
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<Palette, E> {
+                PalettePreset::deserialize(value.into_deserializer()).map(Palette::Preset)
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> Result<Palette, A::Error> {
+                Vec::deserialize(SeqAccessDeserializer::new(seq)).map(Palette::Stops)
+            }
+        }
+
+        deserializer.deserialize_any(PaletteVisitor)
+    }
+}
+
+/// `oklab_lsrgb` then `lsrgb_srgb` of `oklab.glsl`, so that the texture holds the values the
+/// shaders output.
+fn oklab_srgb(oklab: Vec3) -> Vec3 {
+    let fwd_a = DMat3::from_cols(
+        dvec3(1.0, 1.0, 1.0),
+        dvec3(0.3963377774, -0.1055613458, -0.0894841775),
+        dvec3(0.2158037573, -0.0638541728, -1.2914855480),
+    );
+    let fwd_b = DMat3::from_cols(
+        dvec3(4.0767245293, -1.2681437731, -0.0041119885),
+        dvec3(-3.3072168827, 2.6093323231, -0.7034763098),
+        dvec3(0.2307590544, -0.3411344290, 1.7068625689),
+    );
+    let lms = fwd_a * oklab.as_dvec3();
+    let lsrgb = (fwd_b * (lms * lms * lms))
+        .clamp(DVec3::ZERO, DVec3::ONE)
+        .as_vec3();
+    lsrgb.map(|c| {
+        if c < 0.0031308 {
+            12.92 * c
+        } else {
+            1.055 * c.powf(1.0 / 2.4) - 0.055
+        }
+    })
+}
+
+/// The config's palette as a 1D texture, transparent when there is none so that the patterns
+/// keep their hues.
+pub struct PaletteTexture {
+    pub image_view: Arc<ImageView>,
+    pub sampler: Arc<Sampler>,
+}
+
+impl PaletteTexture {
+    pub const WIDTH: u32 = 256;
+
+    pub fn new(
+        queue: &Arc<Queue>,
+        memory_allocator: &Arc<StandardMemoryAllocator>,
+        command_buffer_allocator: &Arc<StandardCommandBufferAllocator>,
+        palette: Option<&Palette>,
+    ) -> Result<Self, LavaError> {
+        let texels: Vec<[u8; 4]> = match palette {
+            Some(palette) => {
+                let stops = palette.stops();
+                (0..Self::WIDTH)
+                    .map(|x| {
+                        let position = x as f32 / (Self::WIDTH - 1) as f32;
+                        let srgb = oklab_srgb(blend(&stops, position));
+                        let [r, g, b] = srgb.to_array().map(|c| (c * 255.0).round() as u8);
+                        [r, g, b, 255]
+                    })
+                    .collect()
+            }
+            None => vec![[0; 4]; Self::WIDTH as usize],
+        };
+
+        let upload_buffer = Buffer::from_iter(
+            memory_allocator.clone(),
+            BufferCreateInfo {
+                usage: BufferUsage::TRANSFER_SRC,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: MemoryTypeFilter::PREFER_HOST
+                    | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+                ..Default::default()
+            },
+            texels,
+        )
+        .map_err(|e| LavaError::gpu("creating the palette", e))?;
+
+        let image = Image::new(
+            memory_allocator.clone(),
+            ImageCreateInfo {
+                image_type: ImageType::Dim1d,
+                format: Format::R8G8B8A8_UNORM,
+                extent: [Self::WIDTH, 1, 1],
+                usage: ImageUsage::TRANSFER_DST | ImageUsage::SAMPLED,
+                ..Default::default()
+            },
+            AllocationCreateInfo::default(),
+        )
+        .map_err(|e| LavaError::gpu("creating the palette", e))?;
+
+        let mut uploads = AutoCommandBufferBuilder::primary(
+            command_buffer_allocator.clone(),
+            queue.queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+        .map_err(|e| LavaError::gpu("creating the palette", e))?;
+        uploads
+            .copy_buffer_to_image(CopyBufferToImageInfo::buffer_image(
+                upload_buffer,
+                image.clone(),
+            ))
+            .map_err(|e| LavaError::gpu("creating the palette", e))?;
+        let _ = uploads
+            .build()
+            .map_err(|e| LavaError::gpu("creating the palette", e))?
+            .execute(queue.clone())
+            .map_err(|e| LavaError::gpu("creating the palette", e))?;
+
+        let image_view =
+            ImageView::new_default(image).map_err(|e| LavaError::gpu("creating the palette", e))?;
+
+        let sampler = Sampler::new(
+            memory_allocator.device().clone(),
+            SamplerCreateInfo {
+                mag_filter: Filter::Linear,
+                min_filter: Filter::Linear,
+                address_mode: [SamplerAddressMode::ClampToEdge; 3],
+                ..Default::default()
+            },
+        )
+        .map_err(|e| LavaError::gpu("creating the palette", e))?;
+
+        Ok(Self {
+            image_view,
+            sampler,
+        })
+    }
+
+    /// The write of the palette for `uniforms/palette.glsl`.
+    pub fn write(&self) -> WriteDescriptorSet {
+        WriteDescriptorSet::image_view_sampler(15, self.image_view.clone(), self.sampler.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::assert_near;
+
+    use glam::vec3;
+
+    #[test]
+    fn presets_span_the_whole_palette() {
+        for (preset, first, last) in [
+            (
+                PalettePreset::Magma,
+                [0.048, -0.003, -0.033],
+                [0.979, -0.025, 0.075],
+            ),
+            (
+                PalettePreset::Sunset,
+                [0.276, 0.040, -0.081],
+                [0.889, 0.005, 0.133],
+            ),
+            (
+                PalettePreset::Ice,
+                [0.234, -0.011, -0.060],
+                [0.983, -0.008, -0.008],
+            ),
+        ] {
+            let stops = Palette::Preset(preset).stops();
+            assert_eq!(stops.first().unwrap().position, 0.0);
+            assert_eq!(stops.last().unwrap().position, 1.0);
+            assert_near(blend(&stops, 0.0), Vec3::from_array(first), 1e-6);
+            assert_near(blend(&stops, 1.0), Vec3::from_array(last), 1e-6);
+        }
+    }
+
+    #[test]
+    fn blends_in_oklab() {
+        // halfway between the third and the fourth of six stops
+        let stops = PalettePreset::Magma.stops();
+        assert_near(blend(&stops, 0.5), vec3(0.5435, 0.165, -0.019), 1e-6);
+
+        let stops = Palette::Stops(vec![
+            ColorStop {
+                position: 0.8,
+                color: vec3(0.9, -0.1, 0.1),
+            },
+            ColorStop {
+                position: 0.2,
+                color: vec3(0.3, 0.1, -0.1),
+            },
+        ])
+        .stops();
+        assert_near(blend(&stops, 0.35), vec3(0.45, 0.05, -0.05), 1e-6);
+        // the ends hold past the first and the last stops
+        assert_near(blend(&stops, 0.0), vec3(0.3, 0.1, -0.1), 1e-6);
+        assert_near(blend(&stops, 1.0), vec3(0.9, -0.1, 0.1), 1e-6);
+    }
+
+    #[test]
+    fn converts_to_srgb() {
+        assert_near(oklab_srgb(Vec3::ZERO), Vec3::ZERO, 1e-6);
+        assert!(oklab_srgb(Vec3::X).abs_diff_eq(Vec3::ONE, 1e-3));
+        // the OkLab of sRGB red in Björn Ottosson's reference
+        assert!(oklab_srgb(vec3(0.627955, 0.224863, 0.125846)).abs_diff_eq(Vec3::X, 1e-3));
+    }
+}
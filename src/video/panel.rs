@@ -1,176 +1,329 @@
 use crate::{
     config::Config,
+    error::LavaError,
     video::{
-        GlobalWrites, PanelTransform, create_write_descriptor_set,
-        shader_types::{
-            GrayVenueGridnodeParameters, ImageParameters, MaskedPatternParameters,
-            SimplePatternParameters, SpectrogramParameters, WaveformParameters,
-        },
-        shaders::{self, AspectRatio, Transform},
+        BlendMode, Feedback, GlobalWrites, PanelTransform, create_write_descriptor_set,
+        material::{GlobalInput, Material, MaterialContext, MaterialShader, registry},
+        shader_types::WaveformParameters,
+        shaders::{AspectRatio, Transform},
+        window_size_dependent_setup::{additive_blend, alpha_blend},
     },
 };
 
 use glam::Vec2;
-use std::sync::Arc;
+use schemars::{JsonSchema, Schema, SchemaGenerator};
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de, ser::SerializeStruct};
+use std::{
+    borrow::Cow,
+    fmt,
+    ops::{Deref, DerefMut},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 use vulkano::{
-    buffer::allocator::SubbufferAllocator, descriptor_set::WriteDescriptorSet, device::Device,
-    shader::EntryPoint,
+    buffer::allocator::SubbufferAllocator,
+    descriptor_set::WriteDescriptorSet,
+    pipeline::graphics::color_blend::AttachmentBlend,
+    shader::{EntryPoint, ShaderModule},
 };
 
-#[derive(Clone, Serialize, Deserialize)]
-#[serde(tag = "type", content = "parameters")]
-pub enum PanelMaterial {
-    #[serde(rename = "waveform")]
-    Waveform(WaveformParameters),
-    #[serde(rename = "spectrogram")]
-    Spectrogram(SpectrogramParameters),
-    #[serde(rename = "simple_pattern")]
-    SimplePattern(SimplePatternParameters),
-    #[serde(rename = "masked_pattern")]
-    MaskedPattern(MaskedPatternParameters),
-    #[serde(rename = "image")]
-    Image(ImageParameters),
-    #[serde(rename = "gray_venue_gridnode")]
-    GrayVenueGridnode(GrayVenueGridnodeParameters),
+/// The material of a panel, any registered [`Material`] with its parameters.
+pub struct PanelMaterial(Box<dyn Material>);
+
+impl PanelMaterial {
+    pub fn new(material: impl Material) -> Self {
+        Self(Box::new(material))
+    }
+}
+
+impl Deref for PanelMaterial {
+    type Target = dyn Material;
+
+    fn deref(&self) -> &Self::Target {
+        &*self.0
+    }
+}
+
+impl DerefMut for PanelMaterial {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut *self.0
+    }
+}
+
+impl From<Box<dyn Material>> for PanelMaterial {
+    fn from(material: Box<dyn Material>) -> Self {
+        Self(material)
+    }
+}
+
+impl Clone for PanelMaterial {
+    fn clone(&self) -> Self {
+        Self(self.0.clone_box())
+    }
+}
+
+impl PartialEq for PanelMaterial {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.eq_dyn(&*other.0)
+    }
 }
 
 impl Default for PanelMaterial {
     fn default() -> Self {
-        Self::Waveform(Default::default())
+        Self::new(WaveformParameters::default())
+    }
+}
+
+impl fmt::Debug for PanelMaterial {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.name(), self.to_value())
+    }
+}
+
+impl Serialize for PanelMaterial {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut material = serializer.serialize_struct("PanelMaterial", 2)?;
+        material.serialize_field("type", self.name())?;
+        material.serialize_field("parameters", &self.to_value())?;
+        material.end()
+    }
+}
+
+/// Parses the parameters with the material registered for `type`. Errors only point at the
+/// material, `Config::from_jsonc` parses it again from the source to locate them.
+impl<'de> Deserialize<'de> for PanelMaterial {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(deny_unknown_fields)]
+        struct Tagged {
+            #[serde(rename = "type")]
+            name: String,
+            parameters: Option<serde_json::Value>,
+        }
+
+        let tagged = Tagged::deserialize(deserializer)?;
+        registry()
+            .parse(
+                &tagged.name,
+                tagged
+                    .parameters
+                    .unwrap_or_else(|| serde_json::Value::Object(Default::default())),
+            )
+            .map(Self)
+            .map_err(de::Error::custom)
+    }
+}
+
+impl JsonSchema for PanelMaterial {
+    fn schema_name() -> Cow<'static, str> {
+        "PanelMaterial".into()
+    }
+
+    fn json_schema(generator: &mut SchemaGenerator) -> Schema {
+        registry().schema(generator)
     }
 }
 
-use serde::{Deserialize, Serialize};
-#[derive(Default, Clone, Serialize, Deserialize)]
-#[serde(default)]
+#[derive(Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
 pub struct Panel {
+    /// Lets other panels refer to this one.
+    pub id: Option<String>,
     pub material: PanelMaterial,
     pub transform: PanelTransform,
+    /// Draws the panel over its own previous frames.
+    pub feedback: Option<Feedback>,
+    pub opacity: f32,
+    pub blend_mode: BlendMode,
+    /// Panels are drawn from the lowest to the highest, in the order of the list when equal.
+    pub z_index: i32,
+    /// Shows the panel only where another one is drawn.
+    pub mask: Option<PanelMask>,
+    /// Hidden panels are still drawn as masks for the others.
+    pub visible: bool,
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct PanelMask {
+    /// The `id` of the panel whose alpha is used.
+    pub panel: String,
+    /// Shows the panel where the other one isn't drawn instead.
+    #[serde(default)]
+    pub invert: bool,
+}
+
+impl Default for Panel {
+    fn default() -> Self {
+        Self {
+            id: None,
+            material: PanelMaterial::default(),
+            transform: PanelTransform::default(),
+            feedback: None,
+            opacity: 1.0,
+            blend_mode: BlendMode::default(),
+            z_index: 0,
+            mask: None,
+            visible: true,
+        }
+    }
 }
 
 impl Panel {
-    pub fn get_shader_entry_point(&self, device: &Arc<Device>, config: &Config) -> EntryPoint {
-        let device_clone = device.clone();
-        match self.material {
-            PanelMaterial::Waveform(_) => shaders::load_waveform(device_clone),
-            PanelMaterial::Spectrogram(_) => shaders::load_spectrogram(device_clone),
-            PanelMaterial::SimplePattern(_) => shaders::load_simple_pattern(device_clone),
-            PanelMaterial::MaskedPattern(_) => shaders::load_masked_pattern(device_clone),
-            PanelMaterial::Image(_) => shaders::load_image(device_clone),
-            PanelMaterial::GrayVenueGridnode(_) => shaders::load_gray_venue_gridnode(device_clone),
+    /// Whether the panel is drawn in a layer of its own that is then composited, because of its
+    /// feedback, opacity, blend mode or mask. Panels used as masks need one too, see
+    /// [`layered_panels`].
+    pub fn needs_layer(&self) -> bool {
+        self.feedback.is_some()
+            || self.opacity < 1.0
+            || self.blend_mode.straight().is_none()
+            || self.mask.is_some()
+    }
+
+    /// The blending of the panel's pipeline, depending on whether it is drawn in a layer.
+    pub fn blend(&self, layered: bool) -> AttachmentBlend {
+        let additive = self
+            .material
+            .geometry()
+            .is_some_and(|geometry| geometry.additive);
+        let blend_mode = match self.blend_mode {
+            BlendMode::Alpha if additive => BlendMode::Additive,
+            blend_mode => blend_mode,
+        };
+        match blend_mode.straight() {
+            Some(blend) if !layered => blend,
+            // layers start out transparent and are blended as they are composited
+            _ if additive => additive_blend(),
+            _ => alpha_blend(),
         }
-        .unwrap()
-        .specialize(
-            [
-                (0, (config.sample_count as u32).into()),
-                (1, (config.bin_count as u32).into()),
-                (2, (config.sample_rate as u32).into()),
-            ]
-            .into_iter()
-            .collect(),
-        )
-        .unwrap()
-        .entry_point("main")
-        .unwrap()
+    }
+
+    /// The fragment shader source to compile at runtime, given the directory the built-in
+    /// shaders are compiled from, if any.
+    pub fn shader_source(&self, source_directory: Option<&Path>) -> Option<PathBuf> {
+        match self.material.shader() {
+            MaterialShader::File { path, .. } => Some(path),
+            MaterialShader::Embedded {
+                source: Some(source),
+                ..
+            } => source_directory.map(|directory| directory.join(format!("{source}.glsl"))),
+            MaterialShader::Embedded { source: None, .. } => None,
+        }
+    }
+
+    /// The vertex shader source to compile at runtime if the material draws its own geometry,
+    /// given the directory the built-in shaders are compiled from, if any.
+    pub fn geometry_source(&self, source_directory: Option<&Path>) -> Option<PathBuf> {
+        let geometry = self.material.geometry()?;
+        source_directory.map(|directory| directory.join(format!("{}.glsl", geometry.source)))
+    }
+
+    /// The compute shader source to compile at runtime if the material's state has one, given
+    /// the directory the built-in shaders are compiled from, if any.
+    pub fn compute_source(&self, source_directory: Option<&Path>) -> Option<PathBuf> {
+        let source = self.material.compute_source()?;
+        source_directory.map(|directory| directory.join(format!("{source}.glsl")))
+    }
+
+    /// Fills in the analysis constants of a shader module and returns its `main`.
+    pub fn specialize(
+        module: &Arc<ShaderModule>,
+        config: &Config,
+    ) -> Result<EntryPoint, LavaError> {
+        module
+            .specialize(
+                [
+                    (0, (config.sample_count as u32).into()),
+                    (1, (config.bin_count as u32).into()),
+                    (2, (config.sample_rate as u32).into()),
+                ]
+                .into_iter()
+                .collect(),
+            )
+            .map_err(|e| LavaError::gpu("specializing a shader", e))?
+            .entry_point("main")
+            .ok_or_else(|| LavaError::gpu("loading a shader", "no `main` entry point"))
     }
 
     pub fn get_write_descriptor_sets(
         &self,
-        uniform_buffer_allocator: &SubbufferAllocator,
-        screen_size: Vec2,
-        global_writes: GlobalWrites,
+        global_writes: &GlobalWrites,
+        context: &mut MaterialContext,
     ) -> Vec<WriteDescriptorSet> {
-        let transform_write = {
-            let transform = self.transform.get_matrix(screen_size);
-            create_write_descriptor_set(
-                &uniform_buffer_allocator,
-                0,
-                Transform {
-                    transform: [
-                        transform.x_axis.to_array().into(),
-                        transform.y_axis.to_array().into(),
-                        transform.z_axis.to_array().into(),
-                    ],
-                },
-            )
-        };
+        let uniform_buffer_allocator = context.uniform_buffer_allocator;
+        let screen_size: Vec2 = context.screen_size;
 
-        let aspect_ratio_write = create_write_descriptor_set(
-            &uniform_buffer_allocator,
-            1,
-            AspectRatio {
-                aspect_ratio: ((screen_size.x / screen_size.y) * self.transform.get_aspect_ratio())
-                    .into(),
-            },
-        );
-
-        match &self.material {
-            PanelMaterial::Waveform(parameters) => vec![
-                transform_write,
-                aspect_ratio_write,
-                global_writes.samples,
-                global_writes.stabilization,
-                global_writes.bass,
-                create_write_descriptor_set::<shaders::WaveformParameters>(
-                    &uniform_buffer_allocator,
-                    10,
-                    parameters.clone().into(),
-                ),
-            ],
-            PanelMaterial::Spectrogram(parameters) => vec![
-                transform_write,
-                aspect_ratio_write,
-                global_writes.dft,
-                global_writes.bass,
-                create_write_descriptor_set::<shaders::SpectrogramParameters>(
-                    &uniform_buffer_allocator,
-                    10,
-                    parameters.clone().into(),
-                ),
-            ],
-            PanelMaterial::SimplePattern(parameters) => vec![
-                transform_write,
-                aspect_ratio_write,
-                global_writes.bass,
-                create_write_descriptor_set::<shaders::SimplePatternParameters>(
-                    &uniform_buffer_allocator,
-                    10,
-                    parameters.clone().into(),
-                ),
-            ],
-            PanelMaterial::MaskedPattern(parameters) => vec![
-                transform_write,
-                aspect_ratio_write,
-                global_writes.bass,
-                global_writes.image.clone().unwrap()[0].clone(),
-                global_writes.image.clone().unwrap()[1].clone(),
-                create_write_descriptor_set::<shaders::MaskedPatternParameters>(
-                    &uniform_buffer_allocator,
-                    10,
-                    parameters.clone().into(),
-                ),
-            ],
-            PanelMaterial::Image(parameters) => vec![
-                transform_write,
-                global_writes.bass,
-                global_writes.image.clone().unwrap()[0].clone(),
-                global_writes.image.clone().unwrap()[1].clone(),
-                create_write_descriptor_set::<shaders::ImageParameters>(
-                    &uniform_buffer_allocator,
-                    10,
-                    parameters.clone().into(),
-                ),
-            ],
-            PanelMaterial::GrayVenueGridnode(parameters) => vec![
-                transform_write,
-                global_writes.dft,
-                global_writes.bass,
-                create_write_descriptor_set::<shaders::GrayVenueGridnodeParameters>(
-                    &uniform_buffer_allocator,
-                    10,
-                    parameters.clone().into(),
-                ),
-            ],
+        let mut writes = vec![write_transform(
+            uniform_buffer_allocator,
+            &self.transform,
+            screen_size,
+        )];
+
+        for input in self.material.inputs() {
+            match input {
+                GlobalInput::AspectRatio => writes.push(create_write_descriptor_set(
+                    uniform_buffer_allocator,
+                    1,
+                    AspectRatio {
+                        aspect_ratio: ((screen_size.x / screen_size.y)
+                            * self.transform.get_aspect_ratio())
+                        .into(),
+                    },
+                )),
+                GlobalInput::Samples => writes.push(global_writes.samples.clone()),
+                GlobalInput::Stabilization => writes.push(global_writes.stabilization.clone()),
+                GlobalInput::Dft => writes.push(global_writes.dft.clone()),
+                GlobalInput::Bass => writes.push(global_writes.bass.clone()),
+                GlobalInput::Image => {
+                    let image = match self.material.image() {
+                        Some(name) => global_writes.textures.get(name),
+                        None => global_writes.image.as_ref(),
+                    };
+                    writes.extend(image.into_iter().flatten().cloned());
+                }
+                GlobalInput::AudioTexture => writes.push(global_writes.audio_texture.clone()),
+                GlobalInput::Palette => writes.push(global_writes.palette.clone()),
+            }
         }
+
+        writes.extend(self.material.write_parameters(context));
+        writes.extend(self.material.write_resources(context));
+        writes
     }
 }
+
+/// The write of the matrix placing the quad at `transform`, read by the vertex shader.
+pub fn write_transform(
+    uniform_buffer_allocator: &SubbufferAllocator,
+    transform: &PanelTransform,
+    screen_size: Vec2,
+) -> WriteDescriptorSet {
+    let matrix = transform.get_matrix(screen_size);
+    create_write_descriptor_set(
+        uniform_buffer_allocator,
+        0,
+        Transform {
+            transform: [
+                matrix.x_axis.to_array().into(),
+                matrix.y_axis.to_array().into(),
+                matrix.z_axis.to_array().into(),
+            ],
+        },
+    )
+}
+
+/// Whether each panel is drawn in a layer, because it needs one or masks another panel.
+pub fn layered_panels(panels: &[Panel]) -> Vec<bool> {
+    panels
+        .iter()
+        .map(|panel| {
+            panel.needs_layer()
+                || panel.id.as_ref().is_some_and(|id| {
+                    panels
+                        .iter()
+                        .filter_map(|other| other.mask.as_ref())
+                        .any(|mask| mask.panel == *id)
+                })
+        })
+        .collect()
+}
@@ -1,27 +1,28 @@
 use glam::{Mat3, Vec2, vec2};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::f32::consts::PI;
 
-#[derive(Clone, Copy, Serialize, Deserialize)]
-#[serde(tag = "type", content = "value")]
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "type", content = "value", deny_unknown_fields)]
 pub enum PanelScale {
     #[serde(rename = "screen")]
-    Screen(Vec2),
+    Screen(#[schemars(with = "[f32; 2]")] Vec2),
     #[serde(rename = "pixels")]
-    Pixels(Vec2),
+    Pixels(#[schemars(with = "[f32; 2]")] Vec2),
 }
 
-#[derive(Clone, Copy, Serialize, Deserialize)]
-#[serde(tag = "type", content = "value")]
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "type", content = "value", deny_unknown_fields)]
 pub enum PanelPosition {
     #[serde(rename = "screen")]
-    Screen(Vec2),
+    Screen(#[schemars(with = "[f32; 2]")] Vec2),
     #[serde(rename = "pixels")]
-    Pixels(Vec2),
+    Pixels(#[schemars(with = "[f32; 2]")] Vec2),
 }
 
-#[derive(Clone, Copy, Serialize, Deserialize)]
-#[serde(default)]
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
 pub struct PanelTransform {
     pub scale: PanelScale,
     pub position: PanelPosition,
@@ -83,6 +84,14 @@ impl PanelTransform {
         translation * angle * scale
     }
 
+    /// The size of the panel in pixels.
+    pub fn get_size(&self, screen_size: Vec2) -> Vec2 {
+        match self.scale {
+            PanelScale::Screen(scale) => (scale * screen_size).abs(),
+            PanelScale::Pixels(scale) => scale.abs(),
+        }
+    }
+
     pub fn get_aspect_ratio(&self) -> f32 {
         let scale = match self.scale {
             PanelScale::Screen(scale) => scale,
@@ -0,0 +1,478 @@
+use crate::video::shader_types::CustomValue;
+
+use std::{collections::BTreeMap, ops::RangeInclusive};
+use vulkano::shader::spirv::{Decoration, Id, Instruction, Spirv, StorageClass};
+
+/// The binding of the material parameter uniform block.
+pub const PARAMETER_BINDING: u32 = 10;
+
+/// Packs `values` into the layout of the uniform block at set 0, binding 10 of a compiled shader,
+/// as reflected from its SPIR-V. Members without a value are left zeroed.
+///
+/// Returns an empty block if the shader declares none and no values are given.
+pub fn pack_parameter_block(
+    words: &[u32],
+    values: &BTreeMap<String, CustomValue>,
+) -> Result<Vec<u8>, String> {
+    let spirv = Spirv::new(words).map_err(|e| e.to_string())?;
+
+    let Some(block) = find_block(&spirv) else {
+        return match values.keys().next() {
+            Some(name) => Err(format!(
+                "`{name}` is set, but the shader declares no uniform block at binding \
+                 {PARAMETER_BINDING}"
+            )),
+            None => Ok(Vec::new()),
+        };
+    };
+
+    let mut bytes = vec![0; size_of_type(&spirv, block).next_multiple_of(16)];
+    write_struct(&spirv, block, values, 0, "", &mut bytes)?;
+    Ok(bytes)
+}
+
+fn find_block(spirv: &Spirv) -> Option<Id> {
+    spirv.global_variables().iter().find_map(|instruction| {
+        let Instruction::Variable {
+            result_id,
+            result_type_id,
+            storage_class: StorageClass::Uniform,
+            ..
+        } = *instruction
+        else {
+            return None;
+        };
+
+        let decorations = spirv.id(result_id).decorations();
+        let set = decorations.iter().find_map(|d| match *d {
+            Instruction::Decorate {
+                decoration: Decoration::DescriptorSet { descriptor_set },
+                ..
+            } => Some(descriptor_set),
+            _ => None,
+        });
+        let binding = decorations.iter().find_map(|d| match *d {
+            Instruction::Decorate {
+                decoration: Decoration::Binding { binding_point },
+                ..
+            } => Some(binding_point),
+            _ => None,
+        });
+        if set != Some(0) || binding != Some(PARAMETER_BINDING) {
+            return None;
+        }
+
+        match *spirv.id(result_type_id).instruction() {
+            Instruction::TypePointer { ty, .. } => Some(ty),
+            _ => None,
+        }
+    })
+}
+
+/// The members of a struct type as `(name, offset, type)`.
+fn members(spirv: &Spirv, ty: Id) -> Vec<(String, usize, Id)> {
+    let Instruction::TypeStruct {
+        ref member_types, ..
+    } = *spirv.id(ty).instruction()
+    else {
+        return Vec::new();
+    };
+
+    member_types
+        .iter()
+        .zip(spirv.id(ty).members())
+        .map(|(&member_type, info)| {
+            let name = info.names().iter().find_map(|n| match n {
+                Instruction::MemberName { name, .. } => Some(name.clone()),
+                _ => None,
+            });
+            let offset = info.decorations().iter().find_map(|d| match *d {
+                Instruction::MemberDecorate {
+                    decoration: Decoration::Offset { byte_offset },
+                    ..
+                } => Some(byte_offset as usize),
+                _ => None,
+            });
+            (name.unwrap_or_default(), offset.unwrap_or(0), member_type)
+        })
+        .collect()
+}
+
+fn size_of_type(spirv: &Spirv, ty: Id) -> usize {
+    match *spirv.id(ty).instruction() {
+        Instruction::TypeInt { width, .. } | Instruction::TypeFloat { width, .. } => {
+            width as usize / 8
+        }
+        Instruction::TypeBool { .. } => 4,
+        Instruction::TypeVector {
+            component_type,
+            component_count,
+            ..
+        } => size_of_type(spirv, component_type) * component_count as usize,
+        // std140 pads every column to a vec4
+        Instruction::TypeMatrix { column_count, .. } => column_count as usize * 16,
+        Instruction::TypeArray {
+            element_type,
+            length,
+            ..
+        } => {
+            let stride = spirv
+                .id(ty)
+                .decorations()
+                .iter()
+                .find_map(|d| match *d {
+                    Instruction::Decorate {
+                        decoration: Decoration::ArrayStride { array_stride },
+                        ..
+                    } => Some(array_stride as usize),
+                    _ => None,
+                })
+                .unwrap_or_else(|| size_of_type(spirv, element_type));
+            let length = match spirv.id(length).instruction() {
+                Instruction::Constant { value, .. } | Instruction::SpecConstant { value, .. } => {
+                    value[0] as usize
+                }
+                _ => 1,
+            };
+            stride * length
+        }
+        Instruction::TypeStruct { .. } => members(spirv, ty)
+            .into_iter()
+            .map(|(_, offset, member_type)| offset + size_of_type(spirv, member_type))
+            .max()
+            .unwrap_or(0),
+        _ => 0,
+    }
+}
+
+fn write_struct(
+    spirv: &Spirv,
+    ty: Id,
+    values: &BTreeMap<String, CustomValue>,
+    base: usize,
+    prefix: &str,
+    bytes: &mut [u8],
+) -> Result<(), String> {
+    let members = members(spirv, ty);
+    for (name, value) in values {
+        let Some((_, offset, member_type)) = members.iter().find(|(member, ..)| member == name)
+        else {
+            return Err(format!(
+                "`{prefix}{name}` is not a member of the uniform block, it declares {}",
+                members
+                    .iter()
+                    .map(|(member, ..)| format!("`{prefix}{member}`"))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        };
+        write_value(
+            spirv,
+            *member_type,
+            value,
+            base + offset,
+            &format!("{prefix}{name}"),
+            bytes,
+        )?;
+    }
+    Ok(())
+}
+
+/// A scalar component type of a member.
+#[derive(Clone, Copy)]
+enum Scalar {
+    Float,
+    Int,
+    Uint,
+}
+
+fn scalar(spirv: &Spirv, ty: Id) -> Option<Scalar> {
+    match *spirv.id(ty).instruction() {
+        Instruction::TypeFloat { width: 32, .. } => Some(Scalar::Float),
+        Instruction::TypeInt {
+            width: 32,
+            signedness: 1,
+            ..
+        } => Some(Scalar::Int),
+        Instruction::TypeInt { width: 32, .. } => Some(Scalar::Uint),
+        _ => None,
+    }
+}
+
+/// A number from the config, integers kept exact so that they pack into `int`s and `uint`s
+/// without going through a float.
+#[derive(Clone, Copy)]
+enum Number {
+    Float(f64),
+    Integer(i64),
+}
+
+fn write_scalar(
+    scalar: Scalar,
+    value: Number,
+    offset: usize,
+    name: &str,
+    bytes: &mut [u8],
+) -> Result<(), String> {
+    let out_of_range = |declared: &str| format!("`{name}` is out of the range of {declared}");
+    // vector components are floats, `2.0` is taken as an integer but not `2.5`
+    let integer = |value: f64, range: RangeInclusive<f64>, declared: &str| {
+        if !range.contains(&value) {
+            Err(out_of_range(declared))
+        } else if value.fract() != 0.0 {
+            Err(format!(
+                "`{name}` is declared as {declared}, it must be a whole number"
+            ))
+        } else {
+            Ok(value)
+        }
+    };
+    let word = match (scalar, value) {
+        (Scalar::Float, Number::Float(value)) => (value as f32).to_ne_bytes(),
+        (Scalar::Float, Number::Integer(value)) => (value as f32).to_ne_bytes(),
+        (Scalar::Int, Number::Float(value)) => {
+            (integer(value, i32::MIN.into()..=i32::MAX.into(), "an int")? as i32).to_ne_bytes()
+        }
+        (Scalar::Int, Number::Integer(value)) => i32::try_from(value)
+            .map_err(|_| out_of_range("an int"))?
+            .to_ne_bytes(),
+        (Scalar::Uint, Number::Float(value)) => {
+            (integer(value, 0.0..=u32::MAX.into(), "a uint")? as u32).to_ne_bytes()
+        }
+        (Scalar::Uint, Number::Integer(value)) => u32::try_from(value)
+            .map_err(|_| out_of_range("a uint"))?
+            .to_ne_bytes(),
+    };
+    bytes[offset..offset + 4].copy_from_slice(&word);
+    Ok(())
+}
+
+fn write_value(
+    spirv: &Spirv,
+    ty: Id,
+    value: &CustomValue,
+    offset: usize,
+    name: &str,
+    bytes: &mut [u8],
+) -> Result<(), String> {
+    let mismatch = |expected: &str| Err(format!("`{name}` is declared as {expected}"));
+
+    match *spirv.id(ty).instruction() {
+        Instruction::TypeStruct { .. } => match value {
+            CustomValue::Struct(values) => {
+                write_struct(spirv, ty, values, offset, &format!("{name}."), bytes)
+            }
+            _ => mismatch("a struct, expected an object"),
+        },
+        Instruction::TypeVector {
+            component_type,
+            component_count,
+            ..
+        } => {
+            let Some(component) = scalar(spirv, component_type) else {
+                return mismatch("a vector type that can't be set from the config");
+            };
+            let count = component_count as usize;
+            let components = match value {
+                CustomValue::Vector(components) if components.len() == count => components.clone(),
+                CustomValue::Vector(components) if components.len() == 3 && count == 4 => {
+                    vec![components[0], components[1], components[2], 1.0]
+                }
+                CustomValue::Color(color) if count >= 3 => CustomValue::parse_color(color).unwrap()
+                    [..count]
+                    .iter()
+                    .map(|&channel| channel as f64)
+                    .collect(),
+                _ => return mismatch(&format!("a {count} component vector")),
+            };
+            for (i, component_value) in components.into_iter().enumerate() {
+                let number = Number::Float(component_value);
+                write_scalar(component, number, offset + i * 4, name, bytes)?;
+            }
+            Ok(())
+        }
+        _ => {
+            let Some(scalar) = scalar(spirv, ty) else {
+                return mismatch("a type that can't be set from the config");
+            };
+            // booleans in uniform blocks are stored as uints
+            let number = match (scalar, value) {
+                (_, CustomValue::Number(number)) => Number::Float(*number as f64),
+                (_, CustomValue::Integer(integer)) => Number::Integer(*integer),
+                (Scalar::Int | Scalar::Uint, CustomValue::Bool(flag)) => {
+                    Number::Integer(*flag as i64)
+                }
+                (Scalar::Float, _) => return mismatch("a float, expected a number"),
+                _ => return mismatch("a bool or an integer, expected a bool or a number"),
+            };
+            write_scalar(scalar, number, offset, name, bytes)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::video::ShaderCompiler;
+
+    use shaderc::ShaderKind;
+    use std::{env, fs};
+
+    const SHADER: &str = "#version 450
+
+struct Inner {
+    float a;
+    vec3 b;
+};
+
+layout(set = 0, binding = 10) uniform Parameters {
+    float scale;
+    vec3 tint;
+    float after_tint;
+    int offset;
+    uint count;
+    bool enabled;
+    Inner inner;
+    float weights[3];
+    mat2 rotation;
+    vec4 color;
+};
+
+layout(location = 0) out vec4 f_color;
+
+void main() {
+    f_color = color * scale + vec4(tint, after_tint) + float(offset + int(count)) +
+        float(enabled) + inner.a + inner.b.x + weights[1] + rotation[1].x;
+}
+";
+
+    fn words() -> Vec<u32> {
+        let path =
+            env::temp_dir().join(format!("lava-parameter-block-{}.frag", std::process::id()));
+        fs::write(&path, SHADER).unwrap();
+        let words = ShaderCompiler::new()
+            .compile(&path, ShaderKind::Fragment)
+            .unwrap();
+        fs::remove_file(&path).unwrap();
+        words
+    }
+
+    fn pack(values: &[(&str, CustomValue)]) -> Result<Vec<u8>, String> {
+        let values = values
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.clone()))
+            .collect();
+        pack_parameter_block(&words(), &values)
+    }
+
+    fn put(bytes: &mut [u8], offset: usize, word: [u8; 4]) {
+        bytes[offset..offset + 4].copy_from_slice(&word);
+    }
+
+    #[test]
+    fn packs_std140() {
+        let packed = pack(&[
+            ("scale", CustomValue::Number(2.0)),
+            ("tint", CustomValue::Vector(vec![0.25, 0.5, 0.75])),
+            ("after_tint", CustomValue::Integer(3)),
+            ("offset", CustomValue::Integer(-7)),
+            // not representable as a f32
+            ("count", CustomValue::Integer(16_777_217)),
+            ("enabled", CustomValue::Bool(true)),
+            (
+                "inner",
+                CustomValue::Struct(BTreeMap::from([
+                    ("a".into(), CustomValue::Number(1.5)),
+                    ("b".into(), CustomValue::Vector(vec![4.0, 5.0, 6.0])),
+                ])),
+            ),
+            ("color", CustomValue::Vector(vec![0.1, 0.2, 0.3])),
+        ])
+        .unwrap();
+
+        let mut expected = vec![0; 176];
+        put(&mut expected, 0, 2f32.to_ne_bytes());
+        put(&mut expected, 16, 0.25f32.to_ne_bytes());
+        put(&mut expected, 20, 0.5f32.to_ne_bytes());
+        put(&mut expected, 24, 0.75f32.to_ne_bytes());
+        // a float fills the end of a vec3
+        put(&mut expected, 28, 3f32.to_ne_bytes());
+        put(&mut expected, 32, (-7i32).to_ne_bytes());
+        put(&mut expected, 36, 16_777_217u32.to_ne_bytes());
+        // booleans are stored as uints
+        put(&mut expected, 40, 1u32.to_ne_bytes());
+        // structs start on 16 bytes, and so does the vec3 inside
+        put(&mut expected, 48, 1.5f32.to_ne_bytes());
+        put(&mut expected, 64, 4f32.to_ne_bytes());
+        put(&mut expected, 68, 5f32.to_ne_bytes());
+        put(&mut expected, 72, 6f32.to_ne_bytes());
+        // after 3 floats with a stride of 16 and the 2 columns of the mat2, padded to vec4s;
+        // 3 components fill a vec4 with an alpha of 1
+        put(&mut expected, 160, 0.1f32.to_ne_bytes());
+        put(&mut expected, 164, 0.2f32.to_ne_bytes());
+        put(&mut expected, 168, 0.3f32.to_ne_bytes());
+        put(&mut expected, 172, 1f32.to_ne_bytes());
+        assert_eq!(packed, expected);
+    }
+
+    #[test]
+    fn reports_unknown_members() {
+        assert_eq!(
+            pack(&[(
+                "inner",
+                CustomValue::Struct(BTreeMap::from([("c".into(), CustomValue::Number(1.0))])),
+            )]),
+            Err(
+                "`inner.c` is not a member of the uniform block, it declares `inner.a`, `inner.b`"
+                    .into()
+            )
+        );
+        assert!(
+            pack(&[("scael", CustomValue::Number(1.0))])
+                .unwrap_err()
+                .starts_with("`scael` is not a member of the uniform block, it declares `scale`, ")
+        );
+    }
+
+    #[test]
+    fn reports_mismatches() {
+        assert_eq!(
+            pack(&[("count", CustomValue::Integer(-1))]),
+            Err("`count` is out of the range of a uint".into())
+        );
+        assert_eq!(
+            pack(&[("scale", CustomValue::Bool(true))]),
+            Err("`scale` is declared as a float, expected a number".into())
+        );
+        assert_eq!(
+            pack(&[("tint", CustomValue::Vector(vec![1.0, 2.0]))]),
+            Err("`tint` is declared as a 3 component vector".into())
+        );
+    }
+
+    #[test]
+    fn packs_whole_floats_into_integers() {
+        let write = |scalar, value| {
+            let mut bytes = [0; 4];
+            write_scalar(scalar, Number::Float(value), 0, "n", &mut bytes).map(|()| bytes)
+        };
+        assert_eq!(write(Scalar::Int, -3.0), Ok((-3i32).to_ne_bytes()));
+        assert_eq!(write(Scalar::Uint, 4e9), Ok(4_000_000_000u32.to_ne_bytes()));
+        assert_eq!(
+            write(Scalar::Int, 2.5),
+            Err("`n` is declared as an int, it must be a whole number".into())
+        );
+        assert_eq!(
+            write(Scalar::Int, 3e9),
+            Err("`n` is out of the range of an int".into())
+        );
+        assert_eq!(
+            write(Scalar::Uint, -1.0),
+            Err("`n` is out of the range of a uint".into())
+        );
+        assert_eq!(
+            write(Scalar::Uint, f64::NAN),
+            Err("`n` is out of the range of a uint".into())
+        );
+    }
+}
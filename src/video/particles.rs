@@ -0,0 +1,178 @@
+use crate::{
+    audio::AudioData,
+    error::LavaError,
+    video::{
+        FrameInputs,
+        shader_types::{ParticleEmission, ParticlesParameters},
+        shaders,
+    },
+};
+
+use std::{iter, sync::Arc};
+use vulkano::{
+    buffer::{Buffer, BufferCreateInfo, BufferUsage, Subbuffer, allocator::SubbufferAllocator},
+    command_buffer::{AutoCommandBufferBuilder, PrimaryAutoCommandBuffer},
+    descriptor_set::{
+        DescriptorSet, WriteDescriptorSet, allocator::StandardDescriptorSetAllocator,
+    },
+    device::DeviceOwned,
+    memory::allocator::{AllocationCreateInfo, MemoryTypeFilter, StandardMemoryAllocator},
+    pipeline::{
+        ComputePipeline, Pipeline, PipelineBindPoint, PipelineLayout,
+        PipelineShaderStageCreateInfo, compute::ComputePipelineCreateInfo,
+        layout::PipelineDescriptorSetLayoutCreateInfo,
+    },
+    shader::ShaderModule,
+};
+
+/// The invocations of a workgroup of `particles_update.glsl`.
+const WORKGROUP_SIZE: u32 = 64;
+
+/// The particles of a particles panel, kept on the GPU and moved by a compute shader every frame.
+pub struct ParticlesState {
+    pipeline: Arc<ComputePipeline>,
+    particles: Subbuffer<[shaders::Particle]>,
+    /// The next particle to be replaced, the oldest one.
+    emit_start: u32,
+    /// The fraction of a particle left over from the previous frames.
+    remainder: f32,
+    /// `Beats::count` of the previous frame, `None` before the first one.
+    beat_count: Option<u32>,
+}
+
+impl ParticlesState {
+    /// Moves the particles with `update_shader`, the embedded `particles_update.glsl` if `None`.
+    pub fn new(
+        parameters: &ParticlesParameters,
+        update_shader: Option<&Arc<ShaderModule>>,
+        memory_allocator: &Arc<StandardMemoryAllocator>,
+    ) -> Result<Self, LavaError> {
+        let device = memory_allocator.device();
+        let module = match update_shader {
+            Some(module) => module.clone(),
+            None => shaders::load_particles_update(device.clone())
+                .map_err(|e| LavaError::gpu("loading the particles shader", e))?,
+        };
+        let stage =
+            PipelineShaderStageCreateInfo::new(module.entry_point("main").ok_or_else(|| {
+                LavaError::gpu("loading the particles shader", "no `main` entry point")
+            })?);
+        let layout = PipelineLayout::new(
+            device.clone(),
+            PipelineDescriptorSetLayoutCreateInfo::from_stages([&stage])
+                .into_pipeline_layout_create_info(device.clone())
+                .map_err(|e| LavaError::gpu("creating the particles pipeline", e))?,
+        )
+        .map_err(|e| LavaError::gpu("creating the particles pipeline", e))?;
+        let pipeline = ComputePipeline::new(
+            device.clone(),
+            None,
+            ComputePipelineCreateInfo::stage_layout(stage, layout),
+        )
+        .map_err(|e| LavaError::gpu("creating the particles pipeline", e))?;
+
+        // every particle starts out dead
+        let dead = shaders::Particle {
+            position: [0.0; 2],
+            velocity: [0.0; 2],
+            age: 1.0,
+            lifetime: 0.0,
+        };
+        let particles = Buffer::from_iter(
+            memory_allocator.clone(),
+            BufferCreateInfo {
+                usage: BufferUsage::STORAGE_BUFFER,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: MemoryTypeFilter::PREFER_DEVICE
+                    | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+                ..Default::default()
+            },
+            iter::repeat_n(dead, parameters.max_particles as usize),
+        )
+        .map_err(|e| LavaError::gpu("creating the particles", e))?;
+
+        Ok(Self {
+            pipeline,
+            particles,
+            emit_start: 0,
+            remainder: 0.0,
+            beat_count: None,
+        })
+    }
+
+    /// Records the emission of this frame's particles and the movement of the others.
+    pub fn record(
+        &mut self,
+        parameters: &ParticlesParameters,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        descriptor_set_allocator: &Arc<StandardDescriptorSetAllocator>,
+        uniform_buffer_allocator: &SubbufferAllocator,
+        frame_inputs: &FrameInputs,
+        audio_data: &AudioData,
+    ) {
+        let time_delta = frame_inputs.time_delta;
+        let beats = &audio_data.beats;
+        let new_beats = beats
+            .count
+            .wrapping_sub(self.beat_count.unwrap_or(beats.count));
+        self.beat_count = Some(beats.count);
+
+        let max_particles = self.particles.len() as u32;
+        let emitted = self.remainder
+            + match parameters.emission {
+                ParticleEmission::Bass => parameters.rate * audio_data.bass.bass * time_delta,
+                ParticleEmission::Beats => parameters.rate * new_beats as f32,
+            };
+        self.remainder = emitted.fract();
+        let emit_count = (emitted as u32).min(max_particles);
+
+        let update = shaders::ParticleUpdate {
+            emit_start: self.emit_start,
+            emit_count,
+            seed: (frame_inputs.frame % 1000) as f32,
+            time_delta,
+            gravity: parameters.gravity.to_array(),
+            origin: parameters.origin.to_array(),
+            direction: parameters.direction.to_radians(),
+            spread: parameters.spread.to_radians(),
+            speed: parameters.speed,
+            lifetime: parameters.lifetime,
+            curl: parameters.curl,
+            curl_scale: parameters.curl_scale,
+            chrono: frame_inputs.chrono,
+        };
+        self.emit_start = (self.emit_start + emit_count) % max_particles;
+
+        let update_buffer = uniform_buffer_allocator.allocate_sized().unwrap();
+        *update_buffer.write().unwrap() = update;
+        let descriptor_set = DescriptorSet::new(
+            descriptor_set_allocator.clone(),
+            self.pipeline.layout().set_layouts()[0].clone(),
+            [
+                WriteDescriptorSet::buffer(0, update_buffer),
+                WriteDescriptorSet::buffer(1, self.particles.clone()),
+            ],
+            [],
+        )
+        .unwrap();
+
+        builder
+            .bind_pipeline_compute(self.pipeline.clone())
+            .unwrap()
+            .bind_descriptor_sets(
+                PipelineBindPoint::Compute,
+                self.pipeline.layout().clone(),
+                0,
+                descriptor_set,
+            )
+            .unwrap();
+        unsafe { builder.dispatch([max_particles.div_ceil(WORKGROUP_SIZE), 1, 1]) }.unwrap();
+    }
+
+    /// The write of the particles for `particles_vertex.glsl`.
+    pub fn writes(&self) -> Vec<WriteDescriptorSet> {
+        vec![WriteDescriptorSet::buffer(13, self.particles.clone())]
+    }
+}
@@ -0,0 +1,666 @@
+use crate::{
+    error::LavaError,
+    video::{
+        PanelTransform, create_pipeline, create_write_descriptor_set, material::LoadShader,
+        panel::write_transform, shaders, window_size_dependent_setup::create_attachment,
+    },
+};
+
+use glam::vec2;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, sync::Arc};
+use vulkano::{
+    buffer::allocator::SubbufferAllocator,
+    descriptor_set::WriteDescriptorSet,
+    device::{Device, DeviceOwned},
+    format::Format,
+    image::{
+        ImageUsage,
+        sampler::{Filter, Sampler, SamplerAddressMode, SamplerCreateInfo},
+        view::ImageView,
+    },
+    memory::allocator::StandardMemoryAllocator,
+    pipeline::GraphicsPipeline,
+    render_pass::{Framebuffer, FramebufferCreateInfo, RenderPass},
+    shader::EntryPoint,
+};
+use winit::dpi::PhysicalSize;
+
+/// The format the panels and the effects are drawn in, with room for the colors above 1 that
+/// bloom picks up.
+pub const HDR_FORMAT: Format = Format::R16G16B16A16_SFLOAT;
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct Bloom {
+    /// Only the colors brighter than this glow.
+    pub threshold: f32,
+    pub intensity: f32,
+    /// In pixels.
+    pub radius: f32,
+    /// How much the bass amplifies the intensity.
+    pub bass_response: f32,
+}
+
+impl Default for Bloom {
+    fn default() -> Self {
+        Self {
+            threshold: 0.5,
+            intensity: 1.0,
+            radius: 16.0,
+            bass_response: 0.0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct Blur {
+    /// In pixels.
+    pub radius: f32,
+    /// How much the bass amplifies the radius.
+    pub bass_response: f32,
+}
+
+impl Default for Blur {
+    fn default() -> Self {
+        Self {
+            radius: 4.0,
+            bass_response: 0.0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct ChromaticAberration {
+    /// How far apart red and blue are in the corners, in pixels.
+    pub amount: f32,
+    /// How much the bass amplifies the amount.
+    pub bass_response: f32,
+}
+
+impl Default for ChromaticAberration {
+    fn default() -> Self {
+        Self {
+            amount: 8.0,
+            bass_response: 0.0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct Vignette {
+    pub intensity: f32,
+    /// Where the darkening starts, 1 is the middle of the edges.
+    pub radius: f32,
+    pub softness: f32,
+    /// How much the bass amplifies the intensity.
+    pub bass_response: f32,
+}
+
+impl Default for Vignette {
+    fn default() -> Self {
+        Self {
+            intensity: 0.6,
+            radius: 0.8,
+            softness: 0.6,
+            bass_response: 0.0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct Crt {
+    pub scanline_intensity: f32,
+    /// The height of a scanline in pixels.
+    pub scanline_size: f32,
+    /// How much the picture bulges, 0 keeps it flat.
+    pub curvature: f32,
+    /// How much the bass amplifies the scanline intensity.
+    pub bass_response: f32,
+}
+
+impl Default for Crt {
+    fn default() -> Self {
+        Self {
+            scanline_intensity: 0.3,
+            scanline_size: 3.0,
+            curvature: 0.1,
+            bass_response: 0.0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct FilmGrain {
+    pub intensity: f32,
+    /// The size of a grain in pixels.
+    pub size: f32,
+    /// How much the bass amplifies the intensity.
+    pub bass_response: f32,
+}
+
+impl Default for FilmGrain {
+    fn default() -> Self {
+        Self {
+            intensity: 0.08,
+            size: 1.5,
+            bass_response: 0.0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TonemapOperator {
+    /// Clips the colors above 1.
+    None,
+    /// `color / (1 + color)`, which never reaches white.
+    Reinhard,
+    /// The filmic curve of ACES, fitted by Krzysztof Narkowicz.
+    Aces,
+}
+
+/// How the HDR scene is brought into the range of the screen after the effects.
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct Tonemap {
+    pub operator: TonemapOperator,
+    /// Multiplies the linear colors before the operator.
+    pub exposure: f32,
+}
+
+impl Default for Tonemap {
+    fn default() -> Self {
+        Self {
+            operator: TonemapOperator::None,
+            exposure: 1.0,
+        }
+    }
+}
+
+impl Tonemap {
+    pub fn validate(&self) -> Vec<(String, String)> {
+        if self.exposure < 0.0 {
+            vec![("exposure".into(), "must not be negative".into())]
+        } else {
+            Vec::new()
+        }
+    }
+}
+
+/// A full screen effect applied to everything drawn, in the order of the `post` list.
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "type", content = "parameters", deny_unknown_fields)]
+pub enum PostEffect {
+    #[serde(rename = "bloom")]
+    Bloom(Bloom),
+    #[serde(rename = "blur")]
+    Blur(Blur),
+    #[serde(rename = "chromatic_aberration")]
+    ChromaticAberration(ChromaticAberration),
+    #[serde(rename = "vignette")]
+    Vignette(Vignette),
+    #[serde(rename = "crt")]
+    Crt(Crt),
+    #[serde(rename = "film_grain")]
+    FilmGrain(FilmGrain),
+}
+
+impl PostEffect {
+    /// Checks values that parse fine but can't work, as `(field, message)` pairs with fields
+    /// relative to the parameters.
+    pub fn validate(&self) -> Vec<(String, String)> {
+        let mut issues = Vec::new();
+        let mut at_least_zero = |field: &str, value: f32| {
+            if value < 0.0 {
+                issues.push((field.into(), "must not be negative".into()));
+            }
+        };
+        match self {
+            Self::Bloom(bloom) => {
+                at_least_zero("radius", bloom.radius);
+                at_least_zero("intensity", bloom.intensity);
+            }
+            Self::Blur(blur) => at_least_zero("radius", blur.radius),
+            Self::ChromaticAberration(_) => {}
+            Self::Vignette(vignette) => at_least_zero("softness", vignette.softness),
+            Self::Crt(crt) => at_least_zero("curvature", crt.curvature),
+            Self::FilmGrain(film_grain) => at_least_zero("intensity", film_grain.intensity),
+        }
+        match self {
+            Self::Crt(crt) if crt.scanline_size <= 0.0 => {
+                issues.push(("scanline_size".into(), "must be positive".into()))
+            }
+            Self::FilmGrain(film_grain) if film_grain.size <= 0.0 => {
+                issues.push(("size".into(), "must be positive".into()))
+            }
+            _ => {}
+        }
+        issues
+    }
+}
+
+/// A full screen draw of the post chain, in its own render pass.
+pub struct PostPass {
+    pub framebuffer: Arc<Framebuffer>,
+    pub pipeline: Arc<GraphicsPipeline>,
+    pub writes: Vec<WriteDescriptorSet>,
+}
+
+struct Target {
+    view: Arc<ImageView>,
+    framebuffer: Arc<Framebuffer>,
+}
+
+#[derive(Clone)]
+struct Shaders {
+    blur: EntryPoint,
+    chromatic_aberration: EntryPoint,
+    vignette: EntryPoint,
+    crt: EntryPoint,
+    film_grain: EntryPoint,
+    output: EntryPoint,
+}
+
+struct Pipelines {
+    blur: Arc<GraphicsPipeline>,
+    chromatic_aberration: Arc<GraphicsPipeline>,
+    vignette: Arc<GraphicsPipeline>,
+    crt: Arc<GraphicsPipeline>,
+    film_grain: Arc<GraphicsPipeline>,
+    output: Arc<GraphicsPipeline>,
+}
+
+/// The HDR target the panels are drawn into and the effects that take it to the output image.
+pub struct PostProcessing {
+    render_pass: Arc<RenderPass>,
+    shaders: Shaders,
+    sampler: Arc<Sampler>,
+    pipelines: Option<Pipelines>,
+    scene: Option<Target>,
+    /// Two targets the effects draw into in turns and one for the first half of the blurs.
+    targets: Option<[Target; 3]>,
+}
+
+impl PostProcessing {
+    /// The names of the sources of the effects' fragment shaders, compiled with `--dev-shaders`.
+    pub const SOURCES: [&str; 6] = [
+        "blur",
+        "chromatic_aberration",
+        "vignette",
+        "crt",
+        "film_grain",
+        "composite",
+    ];
+
+    pub fn new(device: &Arc<Device>) -> Result<Self, LavaError> {
+        let render_pass = vulkano::single_pass_renderpass!(device.clone(),
+            attachments: {
+                color: {
+                    format: HDR_FORMAT,
+                    samples: 1,
+                    load_op: DontCare,
+                    store_op: Store,
+                },
+            },
+            pass: {
+                color: [color],
+                depth_stencil: {},
+            },
+        )
+        .map_err(|e| LavaError::gpu("creating the post processing render pass", e))?;
+
+        let load = |load: LoadShader| {
+            load(device.clone())
+                .map_err(|e| LavaError::gpu("loading the post processing shaders", e))
+                .map(|module| module.entry_point("main").unwrap())
+        };
+        let shaders = Shaders {
+            blur: load(shaders::load_blur)?,
+            chromatic_aberration: load(shaders::load_chromatic_aberration)?,
+            vignette: load(shaders::load_vignette)?,
+            crt: load(shaders::load_crt)?,
+            film_grain: load(shaders::load_film_grain)?,
+            output: load(shaders::load_composite)?,
+        };
+
+        let sampler = Sampler::new(
+            device.clone(),
+            SamplerCreateInfo {
+                mag_filter: Filter::Linear,
+                min_filter: Filter::Linear,
+                address_mode: [SamplerAddressMode::ClampToEdge; 3],
+                ..Default::default()
+            },
+        )
+        .map_err(|e| LavaError::gpu("creating the post processing sampler", e))?;
+
+        Ok(Self {
+            render_pass,
+            shaders,
+            sampler,
+            pipelines: None,
+            scene: None,
+            targets: None,
+        })
+    }
+
+    /// Recreates the targets and pipelines for a new size. `scene_render_pass` is the one the
+    /// panels are drawn with and `output_render_pass` the one of the output images.
+    pub fn resize(
+        &mut self,
+        memory_allocator: &Arc<StandardMemoryAllocator>,
+        extent: PhysicalSize<u32>,
+        scene_render_pass: &Arc<RenderPass>,
+        output_render_pass: &Arc<RenderPass>,
+        vertex_shader: &EntryPoint,
+    ) -> Result<(), LavaError> {
+        self.pipelines = Some(self.create_pipelines(
+            &self.shaders,
+            extent,
+            output_render_pass,
+            vertex_shader,
+        )?);
+
+        let color = || {
+            create_attachment(
+                memory_allocator,
+                HDR_FORMAT,
+                extent,
+                ImageUsage::COLOR_ATTACHMENT | ImageUsage::SAMPLED,
+            )
+        };
+        let target = |render_pass: &Arc<RenderPass>, attachments: Vec<Arc<ImageView>>| {
+            let view = attachments[0].clone();
+            Framebuffer::new(
+                render_pass.clone(),
+                FramebufferCreateInfo {
+                    attachments,
+                    ..Default::default()
+                },
+            )
+            .map(|framebuffer| Target { view, framebuffer })
+            .map_err(|e| LavaError::gpu("creating framebuffers", e))
+        };
+
+        let depth = create_attachment(
+            memory_allocator,
+            scene_render_pass.attachments()[1].format,
+            extent,
+            ImageUsage::DEPTH_STENCIL_ATTACHMENT | ImageUsage::TRANSIENT_ATTACHMENT,
+        )?;
+        self.scene = Some(target(scene_render_pass, vec![color()?, depth])?);
+        self.targets = Some([
+            target(&self.render_pass, vec![color()?])?,
+            target(&self.render_pass, vec![color()?])?,
+            target(&self.render_pass, vec![color()?])?,
+        ]);
+        Ok(())
+    }
+
+    /// Swaps in the fragment shaders of `shaders`, by their name in `SOURCES`, and rebuilds the
+    /// pipelines with them and `vertex_shader` once `resize` has been called. Nothing is
+    /// changed if a pipeline fails to build.
+    pub fn replace_shaders(
+        &mut self,
+        shaders: &HashMap<&str, EntryPoint>,
+        extent: PhysicalSize<u32>,
+        output_render_pass: &Arc<RenderPass>,
+        vertex_shader: &EntryPoint,
+    ) -> Result<(), LavaError> {
+        let mut new_shaders = self.shaders.clone();
+        for (source, shader) in [
+            ("blur", &mut new_shaders.blur),
+            (
+                "chromatic_aberration",
+                &mut new_shaders.chromatic_aberration,
+            ),
+            ("vignette", &mut new_shaders.vignette),
+            ("crt", &mut new_shaders.crt),
+            ("film_grain", &mut new_shaders.film_grain),
+            ("composite", &mut new_shaders.output),
+        ] {
+            if let Some(new_shader) = shaders.get(source) {
+                *shader = new_shader.clone();
+            }
+        }
+        if self.pipelines.is_some() {
+            self.pipelines = Some(self.create_pipelines(
+                &new_shaders,
+                extent,
+                output_render_pass,
+                vertex_shader,
+            )?);
+        }
+        self.shaders = new_shaders;
+        Ok(())
+    }
+
+    fn create_pipelines(
+        &self,
+        shaders: &Shaders,
+        extent: PhysicalSize<u32>,
+        output_render_pass: &Arc<RenderPass>,
+        vertex_shader: &EntryPoint,
+    ) -> Result<Pipelines, LavaError> {
+        let device = self.sampler.device().clone();
+        let pipeline = |render_pass: &Arc<RenderPass>, fs: &EntryPoint| {
+            create_pipeline(&device, extent, render_pass, vertex_shader, fs, None)
+        };
+        Ok(Pipelines {
+            blur: pipeline(&self.render_pass, &shaders.blur)?,
+            chromatic_aberration: pipeline(&self.render_pass, &shaders.chromatic_aberration)?,
+            vignette: pipeline(&self.render_pass, &shaders.vignette)?,
+            crt: pipeline(&self.render_pass, &shaders.crt)?,
+            film_grain: pipeline(&self.render_pass, &shaders.film_grain)?,
+            output: pipeline(output_render_pass, &shaders.output)?,
+        })
+    }
+
+    /// The framebuffer the panels are drawn into, for the render pass given to `resize`.
+    pub fn scene(&self) -> &Arc<Framebuffer> {
+        &self
+            .scene
+            .as_ref()
+            .expect("`resize` wasn't called")
+            .framebuffer
+    }
+
+    /// The passes applying `effects` to the scene and drawing the result into `output` through
+    /// `tonemap`.
+    pub fn passes(
+        &self,
+        uniform_buffer_allocator: &SubbufferAllocator,
+        effects: &[PostEffect],
+        tonemap: &Tonemap,
+        output: Arc<Framebuffer>,
+        bass: f32,
+        time: f32,
+    ) -> Vec<PostPass> {
+        let pipelines = self.pipelines.as_ref().expect("`resize` wasn't called");
+        let [first, second, half_blurred] = self.targets.as_ref().unwrap();
+        let extent = output.extent();
+        let screen_size = vec2(extent[0] as f32, extent[1] as f32);
+        let horizontal = vec2(1.0 / screen_size.x, 0.0);
+        let vertical = vec2(0.0, 1.0 / screen_size.y);
+
+        let pass = |framebuffer: &Arc<Framebuffer>,
+                    pipeline: &Arc<GraphicsPipeline>,
+                    frame: &Arc<ImageView>,
+                    mut writes: Vec<WriteDescriptorSet>| {
+            writes.push(write_transform(
+                uniform_buffer_allocator,
+                &PanelTransform::FULLSCREEN,
+                screen_size,
+            ));
+            writes.push(WriteDescriptorSet::image_view_sampler(
+                11,
+                frame.clone(),
+                self.sampler.clone(),
+            ));
+            PostPass {
+                framebuffer: framebuffer.clone(),
+                pipeline: pipeline.clone(),
+                writes,
+            }
+        };
+        let blur = |framebuffer: &Arc<Framebuffer>,
+                    frame: &Arc<ImageView>,
+                    base: &Arc<ImageView>,
+                    parameters: shaders::BlurParameters| {
+            pass(
+                framebuffer,
+                &pipelines.blur,
+                frame,
+                vec![
+                    WriteDescriptorSet::image_view_sampler(12, base.clone(), self.sampler.clone()),
+                    create_write_descriptor_set(uniform_buffer_allocator, 10, parameters),
+                ],
+            )
+        };
+
+        let mut passes = Vec::new();
+        let mut input = &self.scene.as_ref().unwrap().view;
+        for (i, effect) in effects.iter().enumerate() {
+            let target = if i % 2 == 0 { first } else { second };
+            match effect {
+                PostEffect::Bloom(bloom) => {
+                    let amount = 1.0 + bloom.bass_response * bass;
+                    passes.push(blur(
+                        &half_blurred.framebuffer,
+                        input,
+                        input,
+                        shaders::BlurParameters {
+                            direction: horizontal.to_array(),
+                            radius: bloom.radius,
+                            threshold: bloom.threshold,
+                            base_amount: 0.0,
+                            intensity: 1.0,
+                        },
+                    ));
+                    passes.push(blur(
+                        &target.framebuffer,
+                        &half_blurred.view,
+                        input,
+                        shaders::BlurParameters {
+                            direction: vertical.to_array(),
+                            radius: bloom.radius,
+                            threshold: 0.0,
+                            base_amount: 1.0,
+                            intensity: bloom.intensity * amount,
+                        },
+                    ));
+                }
+                PostEffect::Blur(blur_effect) => {
+                    let radius = blur_effect.radius * (1.0 + blur_effect.bass_response * bass);
+                    for (framebuffer, frame, direction) in [
+                        (&half_blurred.framebuffer, input, horizontal),
+                        (&target.framebuffer, &half_blurred.view, vertical),
+                    ] {
+                        passes.push(blur(
+                            framebuffer,
+                            frame,
+                            frame,
+                            shaders::BlurParameters {
+                                direction: direction.to_array(),
+                                radius,
+                                threshold: 0.0,
+                                base_amount: 0.0,
+                                intensity: 1.0,
+                            },
+                        ));
+                    }
+                }
+                PostEffect::ChromaticAberration(chromatic_aberration) => passes.push(pass(
+                    &target.framebuffer,
+                    &pipelines.chromatic_aberration,
+                    input,
+                    vec![create_write_descriptor_set(
+                        uniform_buffer_allocator,
+                        10,
+                        shaders::ChromaticAberrationParameters {
+                            amount: chromatic_aberration.amount
+                                * (1.0 + chromatic_aberration.bass_response * bass),
+                        },
+                    )],
+                )),
+                PostEffect::Vignette(vignette) => passes.push(pass(
+                    &target.framebuffer,
+                    &pipelines.vignette,
+                    input,
+                    vec![create_write_descriptor_set(
+                        uniform_buffer_allocator,
+                        10,
+                        shaders::VignetteParameters {
+                            intensity: vignette.intensity * (1.0 + vignette.bass_response * bass),
+                            radius: vignette.radius,
+                            softness: vignette.softness,
+                        },
+                    )],
+                )),
+                PostEffect::Crt(crt) => passes.push(pass(
+                    &target.framebuffer,
+                    &pipelines.crt,
+                    input,
+                    vec![create_write_descriptor_set(
+                        uniform_buffer_allocator,
+                        10,
+                        shaders::CrtParameters {
+                            scanline_intensity: crt.scanline_intensity
+                                * (1.0 + crt.bass_response * bass),
+                            scanline_size: crt.scanline_size,
+                            curvature: crt.curvature,
+                        },
+                    )],
+                )),
+                PostEffect::FilmGrain(film_grain) => passes.push(pass(
+                    &target.framebuffer,
+                    &pipelines.film_grain,
+                    input,
+                    vec![create_write_descriptor_set(
+                        uniform_buffer_allocator,
+                        10,
+                        shaders::FilmGrainParameters {
+                            intensity: film_grain.intensity
+                                * (1.0 + film_grain.bass_response * bass),
+                            size: film_grain.size,
+                            time,
+                        },
+                    )],
+                )),
+            }
+            input = &target.view;
+        }
+
+        passes.push(pass(
+            &output,
+            &pipelines.output,
+            input,
+            vec![
+                create_write_descriptor_set(
+                    uniform_buffer_allocator,
+                    10,
+                    shaders::CompositeParameters {
+                        opacity: 1.0,
+                        mask_mode: 0,
+                        tonemap: match tonemap.operator {
+                            TonemapOperator::None => 1,
+                            TonemapOperator::Reinhard => 2,
+                            TonemapOperator::Aces => 3,
+                        },
+                        exposure: tonemap.exposure,
+                    },
+                ),
+                // unused without a mask, but it has to be bound
+                WriteDescriptorSet::image_view_sampler(12, input.clone(), self.sampler.clone()),
+            ],
+        ));
+        passes
+    }
+}
@@ -1,32 +1,19 @@
-use crate::{
-    audio::analyzer::AudioData,
-    config::Config,
-    video::{
-        GlobalWrites, Mesh, Panel, Texture, shaders::load_vertex, window_size_dependent_setup,
-    },
-};
+use crate::{audio::analyzer::AudioData, config::Config, error::LavaError, video::Renderer};
 
-use glam::vec2;
 use std::sync::Arc;
 use vulkano::{
     Validated, VulkanError,
-    buffer::allocator::SubbufferAllocator,
     command_buffer::{
-        AutoCommandBufferBuilder, CommandBufferUsage, RenderPassBeginInfo,
-        allocator::StandardCommandBufferAllocator,
+        AutoCommandBufferBuilder, CommandBufferUsage, allocator::StandardCommandBufferAllocator,
     },
-    descriptor_set::{DescriptorSet, allocator::StandardDescriptorSetAllocator},
-    device::Device,
-    device::Queue,
-    format::Format,
+    device::{Device, Queue},
     image::ImageUsage,
     instance::Instance,
     memory::allocator::StandardMemoryAllocator,
-    pipeline::{GraphicsPipeline, Pipeline, PipelineBindPoint},
-    render_pass::{Framebuffer, RenderPass},
-    shader::EntryPoint,
+    render_pass::Framebuffer,
     swapchain::{
-        Surface, Swapchain, SwapchainCreateInfo, SwapchainPresentInfo, acquire_next_image,
+        PresentMode, Surface, Swapchain, SwapchainCreateInfo, SwapchainPresentInfo,
+        acquire_next_image,
     },
     sync::{self, GpuFuture},
 };
@@ -35,12 +22,8 @@ use winit::{dpi::PhysicalSize, window::Window};
 
 pub struct RenderContext {
     pub swapchain: Arc<Swapchain>,
-    pub render_pass: Arc<RenderPass>,
     pub framebuffers: Vec<Arc<Framebuffer>>,
-    pub vertex_shader: EntryPoint,
-    pub fragment_shaders: Vec<EntryPoint>,
-    pub panels: Vec<Panel>,
-    pub pipelines: Vec<Arc<GraphicsPipeline>>,
+    pub renderer: Renderer,
     pub recreate_swapchain: bool,
     pub previous_frame_end: Option<Box<dyn GpuFuture>>,
 }
@@ -49,22 +32,43 @@ impl RenderContext {
     pub fn new(
         instance: &Arc<Instance>,
         device: &Arc<Device>,
+        queue: &Arc<Queue>,
         memory_allocator: &Arc<StandardMemoryAllocator>,
+        command_buffer_allocator: &Arc<StandardCommandBufferAllocator>,
         window: &Arc<Box<dyn Window>>,
         config: &Config,
-    ) -> Self {
-        let surface = Surface::from_window(instance.clone(), window.clone()).unwrap();
-        let window_size = window.surface_size();
+    ) -> Result<Self, LavaError> {
+        let surface = Surface::from_window(instance.clone(), window.clone())
+            .map_err(|e| LavaError::gpu("creating the window surface", e))?;
+        Self::from_surface(
+            device,
+            queue,
+            memory_allocator,
+            command_buffer_allocator,
+            surface,
+            window.surface_size(),
+            config,
+        )
+    }
 
+    pub fn from_surface(
+        device: &Arc<Device>,
+        queue: &Arc<Queue>,
+        memory_allocator: &Arc<StandardMemoryAllocator>,
+        command_buffer_allocator: &Arc<StandardCommandBufferAllocator>,
+        surface: Arc<Surface>,
+        window_size: PhysicalSize<u32>,
+        config: &Config,
+    ) -> Result<Self, LavaError> {
         let (swapchain, images) = {
             let surface_capabilities = device
                 .physical_device()
                 .surface_capabilities(&surface, Default::default())
-                .unwrap();
+                .map_err(|e| LavaError::gpu("querying surface capabilities", e))?;
             let (image_format, _) = device
                 .physical_device()
                 .surface_formats(&surface, Default::default())
-                .unwrap()[0];
+                .map_err(|e| LavaError::gpu("querying surface formats", e))?[0];
 
             Swapchain::new(
                 device.clone(),
@@ -82,83 +86,67 @@ impl RenderContext {
                     ..Default::default()
                 },
             )
-            .unwrap()
+            .map_err(|e| LavaError::gpu("creating the swapchain", e))?
         };
 
-        let render_pass = vulkano::single_pass_renderpass!(device.clone(),
-            attachments: {
-                color: {
-                    format: swapchain.image_format(),
-                    samples: 1,
-                    load_op: Clear,
-                    store_op: Store,
-                },
-                depth_stencil: {
-                    format: Format::D16_UNORM,
-                    samples: 1,
-                    load_op: Clear,
-                    store_op: DontCare,
-                },
-            },
-            pass: {
-                color: [color],
-                depth_stencil: {depth_stencil},
-            },
-        )
-        .unwrap();
-
-        let panels = config.panels.clone();
-
-        let vertex_shader = load_vertex(device.clone())
-            .unwrap()
-            .entry_point("main")
-            .unwrap();
+        let mut renderer = Renderer::new(
+            device,
+            queue,
+            memory_allocator,
+            command_buffer_allocator,
+            config,
+            swapchain.image_format(),
+        )?;
 
-        let fragment_shaders = panels
-            .iter()
-            .map(|p| p.get_shader_entry_point(&device, &config))
-            .collect();
-
-        let (framebuffers, pipelines) = window_size_dependent_setup(
-            window_size,
-            &images,
-            &render_pass,
-            &memory_allocator,
-            &vertex_shader,
-            &fragment_shaders,
-        );
+        let framebuffers = renderer.resize(&images)?;
 
         let previous_frame_end = Some(sync::now(device.clone()).boxed());
 
-        Self {
+        Ok(Self {
             swapchain,
-            render_pass,
             framebuffers,
-            vertex_shader,
-            fragment_shaders,
-            panels,
-            pipelines,
+            renderer,
             recreate_swapchain: false,
             previous_frame_end,
-        }
+        })
+    }
+
+    /// Presents without waiting for the display's refresh where the surface allows it, so that
+    /// frames take as long as they take to draw. Returns whether it could.
+    pub fn uncap_frame_rate(&mut self, device: &Arc<Device>) -> Result<bool, LavaError> {
+        let present_modes = device
+            .physical_device()
+            .surface_present_modes(self.swapchain.surface(), Default::default())
+            .map_err(|e| LavaError::gpu("querying surface present modes", e))?;
+        let Some(present_mode) = [PresentMode::Immediate, PresentMode::Mailbox]
+            .into_iter()
+            .find(|mode| present_modes.contains(mode))
+        else {
+            return Ok(false);
+        };
+
+        let (swapchain, images) = self
+            .swapchain
+            .recreate(SwapchainCreateInfo {
+                present_mode,
+                ..self.swapchain.create_info()
+            })
+            .map_err(|e| LavaError::gpu("recreating the swapchain", e))?;
+        self.swapchain = swapchain;
+        self.framebuffers = self.renderer.resize(&images)?;
+        Ok(true)
     }
 
     pub fn redraw(
         &mut self,
         device: &Arc<Device>,
         queue: &Arc<Queue>,
-        memory_allocator: &Arc<StandardMemoryAllocator>,
-        descriptor_set_allocator: &Arc<StandardDescriptorSetAllocator>,
         command_buffer_allocator: &Arc<StandardCommandBufferAllocator>,
-        uniform_buffer_allocator: &SubbufferAllocator,
-        storage_buffer_allocator: &SubbufferAllocator,
-        mesh: &Mesh,
-        texture: &Option<Texture>,
         window_size: &PhysicalSize<u32>,
         audio_data: &AudioData,
-    ) {
+    ) -> Result<(), LavaError> {
         if window_size.width == 0 || window_size.height == 0 {
-            return;
+            return Ok(());
         }
 
         self.previous_frame_end.as_mut().unwrap().cleanup_finished();
@@ -170,17 +158,10 @@ impl RenderContext {
                     image_extent: window_size.clone().into(),
                     ..self.swapchain.create_info()
                 })
-                .expect("failed to recreate swapchain");
+                .map_err(|e| LavaError::gpu("recreating the swapchain", e))?;
 
             self.swapchain = new_swapchain;
-            (self.framebuffers, self.pipelines) = window_size_dependent_setup(
-                window_size.clone(),
-                &new_images,
-                &self.render_pass,
-                &memory_allocator,
-                &self.vertex_shader,
-                &self.fragment_shaders,
-            );
+            self.framebuffers = self.renderer.resize(&new_images)?;
             self.recreate_swapchain = false;
         }
 
@@ -189,9 +170,9 @@ impl RenderContext {
                 Ok(r) => r,
                 Err(VulkanError::OutOfDate) => {
                     self.recreate_swapchain = true;
-                    return;
+                    return Ok(());
                 }
-                Err(e) => panic!("failed to acquire next image: {e}"),
+                Err(e) => return Err(LavaError::gpu("acquiring the next swapchain image", e)),
             };
 
         if suboptimal {
@@ -203,71 +184,24 @@ impl RenderContext {
             queue.queue_family_index(),
             CommandBufferUsage::OneTimeSubmit,
         )
-        .unwrap();
-
-        builder
-            .begin_render_pass(
-                RenderPassBeginInfo {
-                    clear_values: vec![
-                        Some([0.0, 0.0, 0.0, 1.0].into()), // background color
-                        Some(1f32.into()),
-                    ],
-                    ..RenderPassBeginInfo::framebuffer(
-                        self.framebuffers[image_index as usize].clone(),
-                    )
-                },
-                Default::default(),
-            )
-            .unwrap()
-            .bind_vertex_buffers(0, (mesh.vertex_buffer.clone(), mesh.uvs_buffer.clone()))
-            .unwrap()
-            .bind_index_buffer(mesh.index_buffer.clone())
-            .unwrap();
+        .map_err(|e| LavaError::gpu("creating the frame command buffer", e))?;
 
-        let global_writes = GlobalWrites::new(
-            &uniform_buffer_allocator,
-            &storage_buffer_allocator,
-            &texture,
+        self.renderer.draw(
+            &mut builder,
+            self.framebuffers[image_index as usize].clone(),
             &audio_data,
-        );
+        )?;
 
-        for i in 0..(self.panels.len()) {
-            let panel = &self.panels[i];
-
-            let layout = self.pipelines[i].layout().set_layouts()[0].clone();
-
-            let writes = panel.get_write_descriptor_sets(
-                &uniform_buffer_allocator,
-                vec2(window_size.width as f32, window_size.height as f32),
-                global_writes.clone(),
-            );
-
-            let descriptor_set =
-                DescriptorSet::new(descriptor_set_allocator.clone(), layout, writes, []).unwrap();
-
-            builder
-                .bind_pipeline_graphics(self.pipelines[i].clone())
-                .unwrap()
-                .bind_descriptor_sets(
-                    PipelineBindPoint::Graphics,
-                    self.pipelines[i].layout().clone(),
-                    0,
-                    descriptor_set,
-                )
-                .unwrap();
-            unsafe { builder.draw_indexed(mesh.index_buffer.len() as u32, 1, 0, 0, 0) }.unwrap();
-        }
-
-        builder.end_render_pass(Default::default()).unwrap();
-
-        let command_buffer = builder.build().unwrap();
+        let command_buffer = builder
+            .build()
+            .map_err(|e| LavaError::gpu("building the frame command buffer", e))?;
         let future = self
             .previous_frame_end
             .take()
             .unwrap()
             .join(acquire_future)
             .then_execute(queue.clone(), command_buffer)
-            .unwrap()
+            .map_err(|e| LavaError::gpu("submitting the frame command buffer", e))?
             .then_swapchain_present(
                 queue.clone(),
                 SwapchainPresentInfo::swapchain_image_index(self.swapchain.clone(), image_index),
@@ -277,15 +211,19 @@ impl RenderContext {
         match future.map_err(Validated::unwrap) {
             Ok(future) => {
                 self.previous_frame_end = Some(future.boxed());
+                if let Some(gpu_dft) = &mut self.renderer.gpu_dft {
+                    gpu_dft.submitted();
+                }
             }
             Err(VulkanError::OutOfDate) => {
                 self.recreate_swapchain = true;
                 self.previous_frame_end = Some(sync::now(device.clone()).boxed());
             }
             Err(e) => {
-                println!("failed to flush future: {e}");
                 self.previous_frame_end = Some(sync::now(device.clone()).boxed());
+                return Err(LavaError::gpu("presenting the frame", e));
             }
         }
+        Ok(())
     }
 }
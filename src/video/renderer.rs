@@ -0,0 +1,907 @@
+use crate::{
+    audio::AudioData,
+    config::Config,
+    error::LavaError,
+    video::{
+        AudioTexture, BlendMode, FrameInputs, GlobalWrites, Mesh, PaletteTexture, Panel,
+        PanelTransform, ShaderCompiler, Texture, TextureConfig, create_geometry_pipeline,
+        create_pipeline,
+        feedback::{Feedback, FeedbackWarp},
+        global_writes::write_samples,
+        gpu_dft::GpuDft,
+        layer::{Compositor, Layer, TRANSPARENT},
+        material::{ComputeContext, GlobalInput, MaterialContext, MaterialState},
+        panel::layered_panels,
+        post::{HDR_FORMAT, PostEffect, PostProcessing, Tonemap},
+        shader_reload::{check_image, load_fragment_shader, load_geometry_shader},
+        shaders::load_vertex,
+        window_size_dependent_setup,
+    },
+};
+
+use glam::{Vec2, vec2};
+use shaderc::ShaderKind;
+use std::{collections::BTreeMap, mem, sync::Arc};
+use vulkano::{
+    buffer::{
+        BufferUsage,
+        allocator::{SubbufferAllocator, SubbufferAllocatorCreateInfo},
+    },
+    command_buffer::{
+        AutoCommandBufferBuilder, ClearAttachment, ClearRect, PrimaryAutoCommandBuffer,
+        RenderPassBeginInfo, allocator::StandardCommandBufferAllocator,
+    },
+    descriptor_set::{
+        DescriptorSet, WriteDescriptorSet, allocator::StandardDescriptorSetAllocator,
+    },
+    device::{Device, DeviceOwned, Queue},
+    format::Format,
+    image::{Image, ImageAspects},
+    memory::allocator::{MemoryTypeFilter, StandardMemoryAllocator},
+    pipeline::{GraphicsPipeline, Pipeline, PipelineBindPoint},
+    render_pass::{AttachmentLoadOp, Framebuffer, RenderPass},
+    shader::EntryPoint,
+};
+use winit::dpi::PhysicalSize;
+
+/// Draws the configured panels into any color image of the format given at creation.
+///
+/// This is the embedding entry point: the caller owns the device, the target images and the
+/// command buffer, the renderer only records render passes into it.
+pub struct Renderer {
+    pub memory_allocator: Arc<StandardMemoryAllocator>,
+    pub descriptor_set_allocator: Arc<StandardDescriptorSetAllocator>,
+    pub uniform_buffer_allocator: SubbufferAllocator,
+    pub storage_buffer_allocator: SubbufferAllocator,
+    pub mesh: Mesh,
+    /// The image at `image_path`.
+    pub texture: Option<Texture>,
+    pub textures: BTreeMap<String, Texture>,
+    pub audio_texture: AudioTexture,
+    /// The config's `palette`.
+    pub palette: PaletteTexture,
+    pub frame_inputs: FrameInputs,
+
+    /// Draws the panels into HDR targets.
+    pub render_pass: Arc<RenderPass>,
+    /// Draws into the target images.
+    pub output_render_pass: Arc<RenderPass>,
+    pub vertex_shader: EntryPoint,
+    /// The vertex shaders of the panels whose material draws its own geometry.
+    pub panel_vertex_shaders: Vec<Option<EntryPoint>>,
+    pub fragment_shaders: Vec<EntryPoint>,
+    /// What each panel's material keeps between frames.
+    pub states: Vec<MaterialState>,
+    pub panels: Vec<Panel>,
+    pub pipelines: Vec<Arc<GraphicsPipeline>>,
+    pub extent: PhysicalSize<u32>,
+
+    /// Applied to everything drawn, on top of the panels' own feedback.
+    pub feedback: Option<Feedback>,
+    pub feedback_warp: FeedbackWarp,
+    pub compositor: Compositor,
+    /// One for each panel that needs one.
+    pub layers: Vec<Option<Layer>>,
+    /// Holds everything drawn when there is a global feedback.
+    pub global_layer: Option<Layer>,
+
+    pub post_effects: Vec<PostEffect>,
+    pub tonemap: Tonemap,
+    pub post_processing: PostProcessing,
+
+    /// Computes the DFT once `create_gpu_dft` succeeded.
+    pub gpu_dft: Option<GpuDft>,
+
+    pub shader_compiler: ShaderCompiler,
+}
+
+impl Renderer {
+    pub fn new(
+        device: &Arc<Device>,
+        queue: &Arc<Queue>,
+        memory_allocator: &Arc<StandardMemoryAllocator>,
+        command_buffer_allocator: &Arc<StandardCommandBufferAllocator>,
+        config: &Config,
+        color_format: Format,
+    ) -> Result<Self, LavaError> {
+        let descriptor_set_allocator = Arc::new(StandardDescriptorSetAllocator::new(
+            device.clone(),
+            Default::default(),
+        ));
+
+        let uniform_buffer_allocator = SubbufferAllocator::new(
+            memory_allocator.clone(),
+            SubbufferAllocatorCreateInfo {
+                buffer_usage: BufferUsage::UNIFORM_BUFFER,
+                memory_type_filter: MemoryTypeFilter::PREFER_DEVICE
+                    | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+                ..Default::default()
+            },
+        );
+
+        let storage_buffer_allocator = SubbufferAllocator::new(
+            memory_allocator.clone(),
+            SubbufferAllocatorCreateInfo {
+                buffer_usage: BufferUsage::STORAGE_BUFFER,
+                memory_type_filter: MemoryTypeFilter::PREFER_DEVICE
+                    | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+                ..Default::default()
+            },
+        );
+
+        let mesh = Mesh::new(&memory_allocator);
+
+        let load_texture = |texture: &TextureConfig| {
+            Texture::new(
+                &device,
+                &queue,
+                &memory_allocator,
+                &command_buffer_allocator,
+                texture,
+            )
+        };
+        let texture = match &config.image_path {
+            Some(path) => Some(load_texture(&TextureConfig {
+                path: path.clone(),
+                ..Default::default()
+            })?),
+            None => None,
+        };
+        let textures = config
+            .textures
+            .iter()
+            .map(|(name, texture)| Ok((name.clone(), load_texture(texture)?)))
+            .collect::<Result<_, LavaError>>()?;
+
+        let audio_texture = AudioTexture::new(memory_allocator)?;
+        let palette = PaletteTexture::new(
+            queue,
+            memory_allocator,
+            command_buffer_allocator,
+            config.palette.as_ref(),
+        )?;
+
+        let render_pass = vulkano::single_pass_renderpass!(device.clone(),
+            attachments: {
+                color: {
+                    format: HDR_FORMAT,
+                    samples: 1,
+                    load_op: Clear,
+                    store_op: Store,
+                },
+                depth_stencil: {
+                    format: Format::D16_UNORM,
+                    samples: 1,
+                    load_op: Clear,
+                    store_op: DontCare,
+                },
+            },
+            pass: {
+                color: [color],
+                depth_stencil: {depth_stencil},
+            },
+        )
+        .map_err(|e| LavaError::gpu("creating the render pass", e))?;
+
+        // everything in the target is overwritten by the post processing
+        let output_render_pass = vulkano::single_pass_renderpass!(device.clone(),
+            attachments: {
+                color: {
+                    format: color_format,
+                    samples: 1,
+                    load_op: DontCare,
+                    store_op: Store,
+                },
+            },
+            pass: {
+                color: [color],
+                depth_stencil: {},
+            },
+        )
+        .map_err(|e| LavaError::gpu("creating the render pass", e))?;
+
+        let panels = config.panels.clone();
+
+        let vertex_shader = load_vertex(device.clone())
+            .map_err(|e| LavaError::gpu("loading the vertex shader", e))?
+            .entry_point("main")
+            .unwrap();
+
+        let mut shader_compiler = ShaderCompiler::new();
+        let (fragment_shaders, states) = panels
+            .iter()
+            .map(|p| load_fragment_shader(&mut shader_compiler, memory_allocator, p, &config))
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter()
+            .unzip();
+        let panel_vertex_shaders = panels
+            .iter()
+            .map(|panel| load_geometry_shader(&mut shader_compiler, device, panel, config))
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self {
+            memory_allocator: memory_allocator.clone(),
+            descriptor_set_allocator,
+            uniform_buffer_allocator,
+            storage_buffer_allocator,
+            mesh,
+            texture,
+            textures,
+            audio_texture,
+            palette,
+            frame_inputs: FrameInputs::new(),
+            render_pass,
+            output_render_pass,
+            vertex_shader,
+            panel_vertex_shaders,
+            fragment_shaders,
+            states,
+            panels,
+            pipelines: Vec::new(),
+            extent: PhysicalSize::new(0, 0),
+            feedback: config.feedback,
+            feedback_warp: FeedbackWarp::new(device)?,
+            compositor: Compositor::new(device)?,
+            layers: Vec::new(),
+            global_layer: None,
+            post_effects: config.post.clone(),
+            tonemap: config.tonemap,
+            post_processing: PostProcessing::new(device)?,
+            gpu_dft: None,
+            shader_compiler,
+        })
+    }
+
+    /// Rebuilds the pipelines for the extent of `images` and returns one framebuffer per image.
+    ///
+    /// Must be called before the first `draw` and whenever the target size changes.
+    pub fn resize(&mut self, images: &[Arc<Image>]) -> Result<Vec<Arc<Framebuffer>>, LavaError> {
+        let extent = images
+            .first()
+            .ok_or_else(|| LavaError::gpu("resizing the renderer", "no target images"))?
+            .extent();
+        self.extent = PhysicalSize::new(extent[0], extent[1]);
+        let framebuffers = window_size_dependent_setup(images, &self.output_render_pass)?;
+        let layered = layered_panels(&self.panels);
+        self.pipelines = (0..self.panels.len())
+            .map(|i| {
+                self.create_panel_pipeline(
+                    &self.panels[i],
+                    layered[i],
+                    self.panel_vertex_shaders[i].as_ref(),
+                    &self.fragment_shaders[i],
+                )
+            })
+            .collect::<Result<_, _>>()?;
+        self.post_processing.resize(
+            &self.memory_allocator,
+            self.extent,
+            &self.render_pass,
+            &self.output_render_pass,
+            &self.vertex_shader,
+        )?;
+        self.feedback_warp
+            .resize(self.extent, &self.render_pass, &self.vertex_shader)?;
+        self.compositor
+            .resize(self.extent, &self.render_pass, &self.vertex_shader)?;
+        // what was drawn at the previous size is dropped
+        self.layers.clear();
+        self.global_layer = None;
+        self.update_layers()?;
+        Ok(framebuffers)
+    }
+
+    /// Creates the layers the panels and the config need that don't exist yet and drops the
+    /// others.
+    fn update_layers(&mut self) -> Result<(), LavaError> {
+        let layers = self.create_layers(&self.panels, &self.feedback)?;
+        self.replace_layers(layers);
+        Ok(())
+    }
+
+    /// The layers `panels` and `feedback` need where the current ones don't fit, `None` where
+    /// they do. Feedback needs two images, to sample the previous frame while drawing the next.
+    fn create_layers(
+        &self,
+        panels: &[Panel],
+        feedback: &Option<Feedback>,
+    ) -> Result<NewLayers, LavaError> {
+        // there is nothing to size them after before the first `resize`
+        if self.extent.width == 0 || self.extent.height == 0 {
+            return Ok((panels.iter().map(|_| None).collect(), None));
+        }
+
+        let create = |count: usize, current: Option<&Layer>| {
+            if current.map_or(0, Layer::count) == count {
+                Ok(None)
+            } else if count == 0 {
+                Ok(Some(None))
+            } else {
+                Layer::new(
+                    &self.memory_allocator,
+                    &self.render_pass,
+                    self.extent,
+                    count,
+                )
+                .map(|layer| Some(Some(layer)))
+            }
+        };
+        let count = |needed: bool, feedback: &Option<Feedback>| match (needed, feedback) {
+            (false, _) => 0,
+            (true, None) => 1,
+            (true, Some(_)) => 2,
+        };
+
+        // masks are read from the layers of the panels they come from
+        let layers = panels
+            .iter()
+            .zip(layered_panels(panels))
+            .enumerate()
+            .map(|(i, (panel, layered))| {
+                let current = self.layers.get(i).and_then(Option::as_ref);
+                create(count(layered, &panel.feedback), current)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let global_layer = create(
+            count(feedback.is_some(), feedback),
+            self.global_layer.as_ref(),
+        )?;
+        Ok((layers, global_layer))
+    }
+
+    /// Replaces the layers with those of `create_layers`, keeping the current ones where it
+    /// found them fitting.
+    fn replace_layers(&mut self, (layers, global_layer): NewLayers) {
+        let mut old_layers = mem::take(&mut self.layers).into_iter();
+        self.layers = layers
+            .into_iter()
+            .map(|layer| {
+                let old_layer = old_layers.next().flatten();
+                layer.unwrap_or(old_layer)
+            })
+            .collect();
+        if let Some(global_layer) = global_layer {
+            self.global_layer = global_layer;
+        }
+    }
+
+    /// Creates the pipeline of `panel`, drawing its material's geometry with `vertex_shader` if it
+    /// has one.
+    pub(crate) fn create_panel_pipeline(
+        &self,
+        panel: &Panel,
+        layered: bool,
+        vertex_shader: Option<&EntryPoint>,
+        fragment_shader: &EntryPoint,
+    ) -> Result<Arc<GraphicsPipeline>, LavaError> {
+        let device = self.memory_allocator.device();
+        let blend = Some(panel.blend(layered));
+        match (vertex_shader, panel.material.geometry()) {
+            (Some(vertex_shader), Some(geometry)) => create_geometry_pipeline(
+                device,
+                self.extent,
+                &self.render_pass,
+                vertex_shader,
+                fragment_shader,
+                &geometry,
+                blend,
+            ),
+            _ => create_pipeline(
+                device,
+                self.extent,
+                &self.render_pass,
+                &self.vertex_shader,
+                fragment_shader,
+                blend,
+            ),
+        }
+    }
+
+    /// Switches from `old` to `new` while keeping everything that didn't change.
+    ///
+    /// Panels that keep their material only get their parameters and transform updated, the
+    /// others get a new pipeline. Everything the new config needs is loaded before anything is
+    /// replaced, so nothing is changed if any part of it fails to load, and the shader compiler
+    /// forgets the shaders it compiled for it.
+    pub fn apply_config(
+        &mut self,
+        queue: &Arc<Queue>,
+        command_buffer_allocator: &Arc<StandardCommandBufferAllocator>,
+        old: &Config,
+        new: &Config,
+    ) -> Result<(), LavaError> {
+        let checkpoint = self.shader_compiler.checkpoint();
+        let LoadedConfig {
+            texture,
+            changed_textures,
+            palette,
+            rebuilt,
+            layers,
+        } = self
+            .load_config(queue, command_buffer_allocator, old, new)
+            .inspect_err(|_| self.shader_compiler.restore(checkpoint))?;
+
+        if let Some(texture) = texture {
+            self.texture = texture;
+        }
+        self.textures
+            .retain(|name, _| new.textures.contains_key(name));
+        self.textures.extend(changed_textures);
+        if let Some(palette) = palette {
+            self.palette = palette;
+        }
+        let mut old_states = mem::take(&mut self.states).into_iter();
+        let mut vertex_shaders = Vec::with_capacity(rebuilt.len());
+        let mut fragment_shaders = Vec::with_capacity(rebuilt.len());
+        let mut pipelines = Vec::with_capacity(rebuilt.len());
+        for (i, rebuilt) in rebuilt.into_iter().enumerate() {
+            let old_state = old_states.next();
+            let (vertex_shader, fragment_shader, state, pipeline) = match rebuilt {
+                Some((vertex_shader, fragment_shader, state, pipeline)) => (
+                    vertex_shader,
+                    fragment_shader,
+                    state.or(old_state).unwrap(),
+                    pipeline,
+                ),
+                None => (
+                    self.panel_vertex_shaders[i].clone(),
+                    self.fragment_shaders[i].clone(),
+                    old_state.unwrap(),
+                    self.pipelines.get(i).cloned(),
+                ),
+            };
+            vertex_shaders.push(vertex_shader);
+            fragment_shaders.push(fragment_shader);
+            self.states.push(state);
+            pipelines.extend(pipeline);
+        }
+        self.panel_vertex_shaders = vertex_shaders;
+        self.fragment_shaders = fragment_shaders;
+        self.pipelines = pipelines;
+        self.panels = new.panels.clone();
+        self.feedback = new.feedback;
+        self.post_effects = new.post.clone();
+        self.tonemap = new.tonemap;
+        // specialized for the old analysis settings, `create_gpu_dft` makes a new one
+        if !keep_shaders(old, new) {
+            self.gpu_dft = None;
+        }
+        self.replace_layers(layers);
+        Ok(())
+    }
+
+    /// Loads what `apply_config` needs for `new` without replacing anything.
+    fn load_config(
+        &mut self,
+        queue: &Arc<Queue>,
+        command_buffer_allocator: &Arc<StandardCommandBufferAllocator>,
+        old: &Config,
+        new: &Config,
+    ) -> Result<LoadedConfig, LavaError> {
+        let device = self.memory_allocator.device().clone();
+        let keep_shaders = keep_shaders(old, new);
+        // pipelines only exist once `resize` has been called, which builds them all
+        let resized = self.extent.width > 0;
+
+        let load_texture = |texture: &TextureConfig| {
+            Texture::new(
+                &device,
+                queue,
+                &self.memory_allocator,
+                command_buffer_allocator,
+                texture,
+            )
+        };
+        let texture = if old.image_path == new.image_path {
+            None
+        } else {
+            match &new.image_path {
+                Some(path) => Some(Some(load_texture(&TextureConfig {
+                    path: path.clone(),
+                    ..Default::default()
+                })?)),
+                None => Some(None),
+            }
+        };
+        // only the textures whose file or sampling changed are loaded again
+        let changed_textures = new
+            .textures
+            .iter()
+            .filter(|(name, texture)| old.textures.get(*name) != Some(texture))
+            .map(|(name, texture)| Ok((name.clone(), load_texture(texture)?)))
+            .collect::<Result<Vec<_>, LavaError>>()?;
+        let palette = if old.palette == new.palette {
+            None
+        } else {
+            Some(PaletteTexture::new(
+                queue,
+                &self.memory_allocator,
+                command_buffer_allocator,
+                new.palette.as_ref(),
+            )?)
+        };
+
+        // `None` for the panels that keep what they have, a `None` state for those that only
+        // need a new pipeline
+        let mut rebuilt = Vec::with_capacity(new.panels.len());
+        let old_layered = layered_panels(&self.panels);
+        let new_layered = layered_panels(&new.panels);
+        for (i, panel) in new.panels.iter().enumerate() {
+            let old_panel = self.panels.get(i);
+            let same_material = keep_shaders
+                && old_panel
+                    .is_some_and(|old_panel| old_panel.material.can_reuse(&*panel.material));
+            let same_blend = old_panel.is_some_and(|old_panel| {
+                old_panel.blend(old_layered[i]) == panel.blend(new_layered[i])
+            });
+
+            // a kept shader may read an image the new config removed
+            if same_material
+                && let Some(path) = panel.shader_source(self.shader_compiler.source_directory())
+            {
+                check_image(panel, &path, &self.fragment_shaders[i], new)?;
+            }
+            if same_material && same_blend {
+                rebuilt.push(None);
+            } else if same_material {
+                let vertex_shader = self.panel_vertex_shaders[i].clone();
+                let fragment_shader = self.fragment_shaders[i].clone();
+                let pipeline = resized
+                    .then(|| {
+                        self.create_panel_pipeline(
+                            panel,
+                            new_layered[i],
+                            vertex_shader.as_ref(),
+                            &fragment_shader,
+                        )
+                    })
+                    .transpose()?;
+                rebuilt.push(Some((vertex_shader, fragment_shader, None, pipeline)));
+            } else {
+                let vertex_shader =
+                    load_geometry_shader(&mut self.shader_compiler, &device, panel, new)?;
+                let (fragment_shader, state) = load_fragment_shader(
+                    &mut self.shader_compiler,
+                    &self.memory_allocator,
+                    panel,
+                    new,
+                )?;
+                let pipeline = resized
+                    .then(|| {
+                        self.create_panel_pipeline(
+                            panel,
+                            new_layered[i],
+                            vertex_shader.as_ref(),
+                            &fragment_shader,
+                        )
+                    })
+                    .transpose()?;
+                rebuilt.push(Some((
+                    vertex_shader,
+                    fragment_shader,
+                    Some(state),
+                    pipeline,
+                )));
+            }
+        }
+
+        Ok(LoadedConfig {
+            texture,
+            changed_textures,
+            palette,
+            rebuilt,
+            layers: self.create_layers(&new.panels, &new.feedback)?,
+        })
+    }
+
+    /// Computes the DFT in a compute shader from now on if `config` asks for it, keeping the one
+    /// already running. On an error the analyzer should go on computing it.
+    pub fn create_gpu_dft(&mut self, queue: &Arc<Queue>, config: &Config) -> Result<(), LavaError> {
+        if !config.gpu_dft {
+            self.gpu_dft = None;
+        } else if self.gpu_dft.is_none() {
+            let mut gpu_dft = GpuDft::new(queue, &self.memory_allocator, config)?;
+            if let Some(directory) = self.shader_compiler.source_directory() {
+                let path = directory.join("dft.glsl");
+                let module =
+                    self.shader_compiler
+                        .load(queue.device(), &path, ShaderKind::Compute)?;
+                gpu_dft.replace_shader(&module, config)?;
+            }
+            self.gpu_dft = Some(gpu_dft);
+        }
+        Ok(())
+    }
+
+    /// Records the render passes drawing every panel into `framebuffer`, a framebuffer returned
+    /// by `resize`. Call [`GpuDft::submitted`] on `gpu_dft` once the command buffer is submitted.
+    pub fn draw(
+        &mut self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        framebuffer: Arc<Framebuffer>,
+        audio_data: &AudioData,
+    ) -> Result<(), LavaError> {
+        let extent = framebuffer.extent();
+        let screen_size = vec2(extent[0] as f32, extent[1] as f32);
+        let bass = audio_data.bass.bass;
+
+        self.frame_inputs.begin_frame(audio_data.bass.chrono);
+
+        // copies can't be recorded inside the render pass
+        if self
+            .panels
+            .iter()
+            .any(|panel| panel.material.inputs().contains(&GlobalInput::AudioTexture))
+        {
+            self.audio_texture.update(builder, audio_data);
+        }
+
+        let samples = write_samples(&self.storage_buffer_allocator, audio_data);
+        // the shaders see the DFT of this frame's samples, the analyzer gets it once it's done
+        let gpu_dft = self.gpu_dft.as_mut().map(|gpu_dft| {
+            gpu_dft.record(
+                builder,
+                &self.descriptor_set_allocator,
+                &samples,
+                audio_data.position,
+            )
+        });
+        let global_writes = GlobalWrites::new(
+            &self.uniform_buffer_allocator,
+            &self.storage_buffer_allocator,
+            samples,
+            gpu_dft,
+            &self.texture,
+            &self.textures,
+            &self.audio_texture,
+            &self.palette,
+            &audio_data,
+            self.frame_inputs.time,
+        );
+        for i in 0..self.panels.len() {
+            self.panels[i].material.record_compute(&mut ComputeContext {
+                builder,
+                descriptor_set_allocator: &self.descriptor_set_allocator,
+                uniform_buffer_allocator: &self.uniform_buffer_allocator,
+                frame_inputs: &self.frame_inputs,
+                audio_data,
+                state: &mut *self.states[i],
+            });
+        }
+
+        // panels that need a layer are drawn into it first
+        self.draw_layers(builder, &global_writes, screen_size, audio_data)?;
+
+        match (&self.feedback, &self.global_layer) {
+            (Some(feedback), Some(layer)) => {
+                let warp = self.feedback_warp.warp(
+                    &self.uniform_buffer_allocator,
+                    layer,
+                    feedback,
+                    &PanelTransform::FULLSCREEN,
+                    screen_size,
+                    bass,
+                );
+                self.begin_render_pass(builder, layer.framebuffer().clone(), TRANSPARENT);
+                self.record_draw(builder, warp);
+            }
+            _ => self.begin_render_pass(builder, self.post_processing.scene().clone(), BACKGROUND),
+        }
+
+        // the sort is stable, so equal panels keep the order of the list
+        let mut order = (0..self.panels.len()).collect::<Vec<_>>();
+        order.sort_by_key(|&i| self.panels[i].z_index);
+        for i in order {
+            if !self.panels[i].visible {
+                continue;
+            }
+            match self.composite_panel(i) {
+                Some(composite) => self.record_draw(builder, composite),
+                None => self.draw_panel(builder, i, &global_writes, screen_size, audio_data)?,
+            }
+        }
+
+        builder.end_render_pass(Default::default()).unwrap();
+
+        if let (Some(_), Some(layer)) = (&self.feedback, &self.global_layer) {
+            let composite = self.compositor.composite(
+                &self.uniform_buffer_allocator,
+                layer,
+                &PanelTransform::FULLSCREEN,
+                BlendMode::Alpha,
+                1.0,
+                None,
+            );
+            self.begin_render_pass(builder, self.post_processing.scene().clone(), BACKGROUND);
+            self.record_draw(builder, composite);
+            builder.end_render_pass(Default::default()).unwrap();
+        }
+
+        for pass in self.post_processing.passes(
+            &self.uniform_buffer_allocator,
+            &self.post_effects,
+            &self.tonemap,
+            framebuffer,
+            bass,
+            self.frame_inputs.time,
+        ) {
+            self.begin_render_pass(builder, pass.framebuffer, BACKGROUND);
+            self.record_draw(builder, (pass.pipeline, pass.writes));
+            builder.end_render_pass(Default::default()).unwrap();
+        }
+
+        for layer in self
+            .layers
+            .iter_mut()
+            .chain([&mut self.global_layer])
+            .flatten()
+        {
+            layer.swap();
+        }
+
+        self.frame_inputs.end_frame();
+        Ok(())
+    }
+
+    /// Begins a render pass into `framebuffer`, clearing the color attachments that are cleared on
+    /// load to `clear_color`.
+    pub(crate) fn begin_render_pass(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        framebuffer: Arc<Framebuffer>,
+        clear_color: [f32; 4],
+    ) {
+        builder
+            .begin_render_pass(
+                RenderPassBeginInfo {
+                    clear_values: framebuffer
+                        .render_pass()
+                        .attachments()
+                        .iter()
+                        .map(|attachment| match attachment.load_op {
+                            AttachmentLoadOp::Clear
+                                if attachment.format.aspects().intersects(ImageAspects::DEPTH) =>
+                            {
+                                Some(1f32.into())
+                            }
+                            AttachmentLoadOp::Clear => Some(clear_color.into()),
+                            _ => None,
+                        })
+                        .collect(),
+                    ..RenderPassBeginInfo::framebuffer(framebuffer)
+                },
+                Default::default(),
+            )
+            .unwrap()
+            .bind_vertex_buffers(
+                0,
+                (
+                    self.mesh.vertex_buffer.clone(),
+                    self.mesh.uvs_buffer.clone(),
+                ),
+            )
+            .unwrap()
+            .bind_index_buffer(self.mesh.index_buffer.clone())
+            .unwrap();
+    }
+
+    /// Records the draw of panel `i` with its own material, inside a render pass.
+    pub(crate) fn draw_panel(
+        &mut self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        i: usize,
+        global_writes: &GlobalWrites,
+        screen_size: Vec2,
+        audio_data: &AudioData,
+    ) -> Result<(), LavaError> {
+        let panel = &self.panels[i];
+        let mut context = MaterialContext {
+            uniform_buffer_allocator: &self.uniform_buffer_allocator,
+            storage_buffer_allocator: &self.storage_buffer_allocator,
+            screen_size,
+            transform: &panel.transform,
+            frame_inputs: &self.frame_inputs,
+            audio_data,
+            state: &mut *self.states[i],
+        };
+        let writes = panel.get_write_descriptor_sets(global_writes, &mut context);
+        // the writes come from the material, which may not match what a custom shader reads
+        let draw_error = |e| LavaError::gpu("drawing a panel", e);
+        if self.panel_vertex_shaders[i].is_none() {
+            self.bind_pipeline(builder, self.pipelines[i].clone(), writes);
+            unsafe { builder.draw_indexed(self.mesh.index_buffer.len() as u32, 1, 0, 0, 0) }
+                .map_err(draw_error)?;
+            return Ok(());
+        }
+
+        let vertex_count = panel.material.vertex_count(&context);
+        if vertex_count > 0 {
+            // so that only the panel's own primitives hide each other
+            if panel
+                .material
+                .geometry()
+                .is_some_and(|geometry| geometry.depth_test)
+            {
+                builder
+                    .clear_attachments(
+                        [ClearAttachment::Depth(1.0)].into_iter().collect(),
+                        [ClearRect {
+                            offset: [0, 0],
+                            extent: [screen_size.x as u32, screen_size.y as u32],
+                            array_layers: 0..1,
+                        }]
+                        .into_iter()
+                        .collect(),
+                    )
+                    .unwrap();
+            }
+            self.bind_pipeline(builder, self.pipelines[i].clone(), writes);
+            unsafe { builder.draw(vertex_count, 1, 0, 0) }.map_err(draw_error)?;
+        }
+        Ok(())
+    }
+
+    /// Records the draw of the quad with `pipeline`, binding the `writes` its shaders use.
+    pub(crate) fn record_draw(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        (pipeline, writes): (Arc<GraphicsPipeline>, Vec<WriteDescriptorSet>),
+    ) {
+        self.bind_pipeline(builder, pipeline, writes);
+        unsafe { builder.draw_indexed(self.mesh.index_buffer.len() as u32, 1, 0, 0, 0) }.unwrap();
+    }
+
+    /// Binds `pipeline` with a descriptor set of the `writes` its shaders use.
+    fn bind_pipeline(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        pipeline: Arc<GraphicsPipeline>,
+        mut writes: Vec<WriteDescriptorSet>,
+    ) {
+        let layout = pipeline.layout().set_layouts()[0].clone();
+        writes.retain(|write| layout.bindings().contains_key(&write.binding()));
+
+        let descriptor_set =
+            DescriptorSet::new(self.descriptor_set_allocator.clone(), layout, writes, []).unwrap();
+
+        builder
+            .bind_pipeline_graphics(pipeline.clone())
+            .unwrap()
+            .bind_descriptor_sets(
+                PipelineBindPoint::Graphics,
+                pipeline.layout().clone(),
+                0,
+                descriptor_set,
+            )
+            .unwrap();
+    }
+}
+
+const BACKGROUND: [f32; 4] = [0.0, 0.0, 0.0, 1.0];
+
+/// A panel's new vertex shader, fragment shader, state if it needs a new one, and pipeline if
+/// the renderer has been resized.
+type RebuiltPanel = (
+    Option<EntryPoint>,
+    EntryPoint,
+    Option<MaterialState>,
+    Option<Arc<GraphicsPipeline>>,
+);
+
+/// New panel layers and global layer, `None` where the current one is kept.
+type NewLayers = (Vec<Option<Option<Layer>>>, Option<Option<Layer>>);
+
+/// What `apply_config` loads before replacing anything.
+struct LoadedConfig {
+    texture: Option<Option<Texture>>,
+    changed_textures: Vec<(String, Texture)>,
+    palette: Option<PaletteTexture>,
+    /// `None` for the panels that keep what they have.
+    rebuilt: Vec<Option<RebuiltPanel>>,
+    layers: NewLayers,
+}
+
+/// Whether the shaders specialized for `old` can be kept for `new`.
+fn keep_shaders(old: &Config, new: &Config) -> bool {
+    old.same_analysis_settings(new)
+}
@@ -0,0 +1,401 @@
+use crate::error::LavaError;
+
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use shaderc::{
+    CompileOptions, Compiler, EnvVersion, IncludeType, ResolvedInclude, ShaderKind, TargetEnv,
+};
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    fs, mem,
+    path::{Component, Path, PathBuf},
+    sync::{
+        Arc,
+        mpsc::{Receiver, channel},
+    },
+};
+use vulkano::{
+    device::Device,
+    shader::{ShaderModule, ShaderModuleCreateInfo},
+};
+
+/// The includes every shader can use, built into the binary so they resolve without the lava
+/// sources.
+const STANDARD_INCLUDES: &[(&str, &str)] = &[
+    ("lib/consts.glsl", include_str!("shaders/lib/consts.glsl")),
+    (
+        "lib/gridnode.glsl",
+        include_str!("shaders/lib/gridnode.glsl"),
+    ),
+    ("lib/in_out.glsl", include_str!("shaders/lib/in_out.glsl")),
+    ("lib/mesh.glsl", include_str!("shaders/lib/mesh.glsl")),
+    ("lib/noise.glsl", include_str!("shaders/lib/noise.glsl")),
+    ("lib/oklab.glsl", include_str!("shaders/lib/oklab.glsl")),
+    (
+        "lib/particles.glsl",
+        include_str!("shaders/lib/particles.glsl"),
+    ),
+    ("lib/polar.glsl", include_str!("shaders/lib/polar.glsl")),
+    (
+        "lib/waveform_lines.glsl",
+        include_str!("shaders/lib/waveform_lines.glsl"),
+    ),
+    (
+        "structs/particle.glsl",
+        include_str!("shaders/structs/particle.glsl"),
+    ),
+    (
+        "structs/pattern.glsl",
+        include_str!("shaders/structs/pattern.glsl"),
+    ),
+    (
+        "uniforms/aspect_ratio.glsl",
+        include_str!("shaders/uniforms/aspect_ratio.glsl"),
+    ),
+    (
+        "uniforms/bass.glsl",
+        include_str!("shaders/uniforms/bass.glsl"),
+    ),
+    (
+        "uniforms/dft.glsl",
+        include_str!("shaders/uniforms/dft.glsl"),
+    ),
+    (
+        "uniforms/frame.glsl",
+        include_str!("shaders/uniforms/frame.glsl"),
+    ),
+    (
+        "uniforms/image.glsl",
+        include_str!("shaders/uniforms/image.glsl"),
+    ),
+    (
+        "uniforms/palette.glsl",
+        include_str!("shaders/uniforms/palette.glsl"),
+    ),
+    (
+        "uniforms/samples.glsl",
+        include_str!("shaders/uniforms/samples.glsl"),
+    ),
+    (
+        "uniforms/shadertoy.glsl",
+        include_str!("shaders/uniforms/shadertoy.glsl"),
+    ),
+    (
+        "uniforms/stabilization.glsl",
+        include_str!("shaders/uniforms/stabilization.glsl"),
+    ),
+    (
+        "uniforms/transform.glsl",
+        include_str!("shaders/uniforms/transform.glsl"),
+    ),
+];
+
+/// Prefix of the resolved names of standard includes, to tell them apart from files.
+const STANDARD_PREFIX: &str = "lava:";
+
+/// Compiles GLSL sources at runtime and reports which of them changed on disk.
+///
+/// `#include`s are resolved relative to the including file first and fall back to the standard
+/// includes (`lib/`, `uniforms/`, `structs/`). Every file a shader was compiled from is watched.
+///
+/// shaderc and the file watcher are only started once a shader is compiled, so a renderer that
+/// only uses embedded shaders doesn't need either.
+#[derive(Default)]
+pub struct ShaderCompiler {
+    compiler: Option<Compiler>,
+    source_directory: Option<PathBuf>,
+    dependencies: HashMap<PathBuf, HashSet<PathBuf>>,
+    watched: HashSet<PathBuf>,
+    /// The watcher of the `watched` directories and its events, once there is one.
+    watcher: Option<(RecommendedWatcher, Receiver<notify::Result<Event>>)>,
+}
+
+impl ShaderCompiler {
+    /// The shader sources of the lava checkout this binary was built from.
+    pub const SOURCE_DIRECTORY: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/video/shaders");
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The directory the built-in shaders are compiled from, if they aren't the ones embedded at
+    /// build time.
+    pub fn source_directory(&self) -> Option<&Path> {
+        self.source_directory.as_deref()
+    }
+
+    pub fn set_source_directory(&mut self, directory: &Path) -> Result<(), LavaError> {
+        self.source_directory =
+            Some(fs::canonicalize(directory).map_err(|source| LavaError::Io {
+                path: directory.to_path_buf(),
+                source,
+            })?);
+        Ok(())
+    }
+
+    /// Compiles the shader at `path` into SPIR-V words.
+    pub fn compile(&mut self, path: &Path, kind: ShaderKind) -> Result<Vec<u32>, LavaError> {
+        let source = fs::read_to_string(path).map_err(|source| LavaError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        self.compile_source(path, &source, kind)
+    }
+
+    /// Compiles `source` as if it was read from `path`, which it may be derived from. Includes are
+    /// resolved and watched relative to `path`.
+    pub fn compile_source(
+        &mut self,
+        path: &Path,
+        source: &str,
+        kind: ShaderKind,
+    ) -> Result<Vec<u32>, LavaError> {
+        let compile_error = |message: String| LavaError::Shader {
+            path: path.to_path_buf(),
+            message,
+        };
+
+        let file = fs::canonicalize(path).map_err(|source| LavaError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+
+        let compiler = match &mut self.compiler {
+            Some(compiler) => compiler,
+            compiler => compiler.insert(
+                Compiler::new().ok_or_else(|| compile_error("shaderc is unavailable".into()))?,
+            ),
+        };
+
+        let included = RefCell::new(HashSet::from([file.clone()]));
+        let artifact = {
+            let mut options =
+                CompileOptions::new().ok_or_else(|| compile_error("no compile options".into()))?;
+            options.set_target_env(TargetEnv::Vulkan, EnvVersion::Vulkan1_0 as u32);
+            options.set_include_callback(|requested, include_type, requesting, _| {
+                let (resolved_name, content, file) = resolve(requested, include_type, requesting)?;
+                if let Some(file) = file {
+                    included.borrow_mut().insert(file);
+                }
+                Ok(ResolvedInclude {
+                    resolved_name,
+                    content,
+                })
+            });
+
+            compiler
+                .compile_into_spirv(
+                    source,
+                    kind,
+                    &file.to_string_lossy(),
+                    "main",
+                    Some(&options),
+                )
+                .map_err(|e| compile_error(e.to_string()))
+        };
+        // recorded even if compilation failed, so fixing the error triggers a reload
+        let included = included.into_inner();
+        self.watch(&included);
+        self.dependencies.insert(path.to_path_buf(), included);
+        let artifact = artifact?;
+
+        if artifact.get_num_warnings() > 0 {
+            eprintln!("{}", artifact.get_warning_messages());
+        }
+        Ok(artifact.as_binary().to_vec())
+    }
+
+    /// Compiles the shader at `path` into a module.
+    pub fn load(
+        &mut self,
+        device: &Arc<Device>,
+        path: &Path,
+        kind: ShaderKind,
+    ) -> Result<Arc<ShaderModule>, LavaError> {
+        let words = self.compile(path, kind)?;
+        create_module(device, path, &words)
+    }
+
+    /// Paths of the compiled shaders that depend on a file changed since the last call.
+    pub fn changed_shaders(&mut self) -> HashSet<PathBuf> {
+        let mut changed_files = HashSet::new();
+        let Some((_, events)) = &self.watcher else {
+            return HashSet::new();
+        };
+        while let Ok(event) = events.try_recv() {
+            match event {
+                Ok(event) if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) => {
+                    changed_files.extend(event.paths);
+                }
+                Ok(_) => {}
+                Err(e) => eprintln!("could not watch the shaders: {e}"),
+            }
+        }
+
+        self.dependencies
+            .iter()
+            .filter(|(_, files)| files.iter().any(|file| changed_files.contains(file)))
+            .map(|(path, _)| path.clone())
+            .collect()
+    }
+
+    /// The shaders compiled so far, to `restore` if what is compiled next isn't used.
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint(self.dependencies.clone())
+    }
+
+    /// Forgets the shaders compiled since `checkpoint` and stops watching the directories only
+    /// they were read from.
+    pub fn restore(&mut self, checkpoint: Checkpoint) {
+        self.dependencies = checkpoint.0;
+        let needed: HashSet<&Path> = self
+            .dependencies
+            .values()
+            .flatten()
+            .filter_map(|file| file.parent())
+            .collect();
+        for directory in mem::take(&mut self.watched) {
+            if needed.contains(directory.as_path()) {
+                self.watched.insert(directory);
+            } else if let Some((watcher, _)) = &mut self.watcher
+                && let Err(e) = watcher.unwatch(&directory)
+            {
+                eprintln!("could not stop watching {}: {e}", directory.display());
+            }
+        }
+    }
+
+    /// Watches the directories of `files`. Shaders still compile if they can't be watched, they
+    /// just aren't reloaded.
+    fn watch(&mut self, files: &HashSet<PathBuf>) {
+        for directory in files.iter().filter_map(|file| file.parent()) {
+            if !self.watched.insert(directory.to_path_buf()) {
+                continue;
+            }
+            let watcher = match &mut self.watcher {
+                Some((watcher, _)) => watcher,
+                watcher => {
+                    let (sender, events) = channel();
+                    match notify::recommended_watcher(sender) {
+                        Ok(new_watcher) => &mut watcher.insert((new_watcher, events)).0,
+                        Err(e) => {
+                            eprintln!(
+                                "{}",
+                                LavaError::Watch {
+                                    path: directory.to_path_buf(),
+                                    message: e.to_string(),
+                                }
+                            );
+                            continue;
+                        }
+                    }
+                }
+            };
+            if let Err(e) = watcher.watch(directory, RecursiveMode::NonRecursive) {
+                eprintln!(
+                    "{}",
+                    LavaError::Watch {
+                        path: directory.to_path_buf(),
+                        message: e.to_string(),
+                    }
+                );
+            }
+        }
+    }
+}
+
+/// The shaders a `ShaderCompiler` had compiled at some point.
+pub struct Checkpoint(HashMap<PathBuf, HashSet<PathBuf>>);
+
+/// Creates a module from the SPIR-V compiled from `path`.
+pub fn create_module(
+    device: &Arc<Device>,
+    path: &Path,
+    words: &[u32],
+) -> Result<Arc<ShaderModule>, LavaError> {
+    unsafe { ShaderModule::new(device.clone(), ShaderModuleCreateInfo::new(words)) }.map_err(|e| {
+        LavaError::Shader {
+            path: path.to_path_buf(),
+            message: e.to_string(),
+        }
+    })
+}
+
+/// Returns the resolved name, the content and, for files, the canonical path of an include.
+fn resolve(
+    requested: &str,
+    include_type: IncludeType,
+    requesting: &str,
+) -> Result<(String, String, Option<PathBuf>), String> {
+    // includes of a standard include stay among the standard includes
+    if let Some(requesting) = requesting.strip_prefix(STANDARD_PREFIX) {
+        let directory = Path::new(requesting).parent().unwrap_or(Path::new(""));
+        return standard_include(&directory.join(requested))
+            .ok_or_else(|| format!("`{requested}` is not a standard include"));
+    }
+
+    if matches!(include_type, IncludeType::Relative) {
+        let requesting = Path::new(requesting);
+        let candidate = requesting.parent().unwrap_or(requesting).join(requested);
+        if candidate.is_file() {
+            let file = fs::canonicalize(&candidate)
+                .map_err(|e| format!("{}: {e}", candidate.display()))?;
+            let content =
+                fs::read_to_string(&file).map_err(|e| format!("{}: {e}", file.display()))?;
+            return Ok((file.to_string_lossy().into_owned(), content, Some(file)));
+        }
+    }
+
+    standard_include(Path::new(requested)).ok_or_else(|| {
+        format!("`{requested}` is neither a file next to the shader nor a standard include")
+    })
+}
+
+fn standard_include(path: &Path) -> Option<(String, String, Option<PathBuf>)> {
+    let mut segments = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(segment) => segments.push(segment.to_str()?),
+            Component::ParentDir => {
+                segments.pop();
+            }
+            _ => {}
+        }
+    }
+    let name = segments.join("/");
+
+    STANDARD_INCLUDES
+        .iter()
+        .find(|(include, _)| *include == name)
+        .map(|(include, content)| {
+            (
+                format!("{STANDARD_PREFIX}{include}"),
+                content.to_string(),
+                None,
+            )
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_include_is_standard() {
+        let shaders = Path::new(ShaderCompiler::SOURCE_DIRECTORY);
+        for directory in ["lib", "structs", "uniforms"] {
+            for entry in fs::read_dir(shaders.join(directory)).unwrap() {
+                let name = format!(
+                    "{directory}/{}",
+                    entry.unwrap().file_name().to_string_lossy()
+                );
+                assert!(
+                    STANDARD_INCLUDES
+                        .iter()
+                        .any(|(include, _)| *include == name),
+                    "{name} is missing from STANDARD_INCLUDES"
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,507 @@
+use crate::{
+    config::Config,
+    error::LavaError,
+    video::{
+        Panel, Renderer, ShaderCompiler,
+        material::{GlobalInput, MaterialShader, MaterialState, StateContext},
+        panel::layered_panels,
+        post::PostProcessing,
+        shader_compiler::create_module,
+    },
+};
+
+use shaderc::ShaderKind;
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use vulkano::{
+    device::{Device, DeviceOwned},
+    memory::allocator::StandardMemoryAllocator,
+    shader::{EntryPoint, ShaderModule},
+};
+
+impl Renderer {
+    /// Compiles the built-in shaders from the sources in `directory` from now on, so that
+    /// `update_shaders` picks up edits to them.
+    pub fn use_shader_sources(
+        &mut self,
+        directory: &Path,
+        config: &Config,
+    ) -> Result<(), LavaError> {
+        self.shader_compiler.set_source_directory(directory)?;
+        let directory = self
+            .shader_compiler
+            .source_directory()
+            .unwrap()
+            .to_path_buf();
+
+        let mut sources = self
+            .panels
+            .iter()
+            .flat_map(|panel| {
+                [
+                    panel.shader_source(Some(&directory)),
+                    panel.geometry_source(Some(&directory)),
+                    panel.compute_source(Some(&directory)),
+                ]
+            })
+            .flatten()
+            .collect::<HashSet<_>>();
+        sources.extend(
+            ["vertex", "feedback", "dft"]
+                .into_iter()
+                .chain(PostProcessing::SOURCES)
+                .map(|source| directory.join(format!("{source}.glsl"))),
+        );
+        self.reload_shaders(&sources, config);
+        Ok(())
+    }
+
+    /// Rebuilds the pipelines whose shader sources changed on disk since the last call.
+    pub fn update_shaders(&mut self, config: &Config) {
+        let changed = self.shader_compiler.changed_shaders();
+        if !changed.is_empty() {
+            self.reload_shaders(&changed, config);
+        }
+    }
+
+    /// Recompiles the shaders compiled from the `changed` sources. A shader that fails to compile
+    /// is reported and its panels keep their previous pipeline.
+    fn reload_shaders(&mut self, changed: &HashSet<PathBuf>, config: &Config) {
+        let device = self.memory_allocator.device().clone();
+        let source_directory = self
+            .shader_compiler
+            .source_directory()
+            .map(Path::to_path_buf);
+        let mut reloaded = Vec::new();
+
+        let vertex_source = source_directory.as_ref().map(|d| d.join("vertex.glsl"));
+        let vertex_reloaded = match vertex_source.filter(|path| changed.contains(path)) {
+            Some(path) => match load_main(
+                &mut self.shader_compiler,
+                &device,
+                &path,
+                ShaderKind::Vertex,
+            ) {
+                Ok(vertex_shader) => {
+                    self.vertex_shader = vertex_shader;
+                    reloaded.push(path);
+                    true
+                }
+                Err(e) => {
+                    eprintln!("{e}");
+                    eprintln!("keeping the previous vertex shader");
+                    false
+                }
+            },
+            None => false,
+        };
+
+        // each changed source is compiled once, however many panels use it
+        let mut compiled = HashMap::new();
+        let mut compiled_vertex = HashMap::new();
+        let mut compiled_compute = HashMap::new();
+        let layered = layered_panels(&self.panels);
+        for i in 0..self.panels.len() {
+            let source = self.panels[i].shader_source(source_directory.as_deref());
+            let compute_source = self.panels[i].compute_source(source_directory.as_deref());
+            let compute_changed = compute_source
+                .as_ref()
+                .is_some_and(|path| changed.contains(path));
+            // a new compute shader goes into a new state, which comes with the fragment shader
+            let fragment_changed =
+                compute_changed || source.as_ref().is_some_and(|path| changed.contains(path));
+            let compute_shader = match compute_source.filter(|_| fragment_changed) {
+                Some(path) => {
+                    let module =
+                        compiled_compute.entry(path.clone()).or_insert_with(|| {
+                            match self
+                                .shader_compiler
+                                .load(&device, &path, ShaderKind::Compute)
+                            {
+                                Ok(module) => {
+                                    if compute_changed {
+                                        reloaded.push(path.clone());
+                                    }
+                                    Some(module)
+                                }
+                                Err(e) => {
+                                    eprintln!("{e}");
+                                    eprintln!("keeping the previous pipeline");
+                                    None
+                                }
+                            }
+                        });
+                    let Some(module) = module else {
+                        continue;
+                    };
+                    Some(module.clone())
+                }
+                None => None,
+            };
+            let fragment = match source.filter(|_| fragment_changed) {
+                Some(path) => {
+                    let words =
+                        compiled.entry(path.clone()).or_insert_with(
+                            || match compile_fragment_shader(
+                                &mut self.shader_compiler,
+                                &self.panels[i],
+                                &path,
+                            ) {
+                                Ok(words) => {
+                                    if changed.contains(&path) {
+                                        reloaded.push(path.clone());
+                                    }
+                                    Some(words)
+                                }
+                                Err(e) => {
+                                    eprintln!("{e}");
+                                    eprintln!("keeping the previous pipeline");
+                                    None
+                                }
+                            },
+                        );
+                    let Some(words) = words else {
+                        continue;
+                    };
+                    match build_fragment_shader(
+                        &self.memory_allocator,
+                        &self.panels[i],
+                        &path,
+                        words,
+                        compute_shader.as_ref(),
+                        config,
+                    ) {
+                        Ok((fragment_shader, state)) => Some((fragment_shader, Some(state))),
+                        Err(e) => {
+                            eprintln!("{e}");
+                            continue;
+                        }
+                    }
+                }
+                None => None,
+            };
+
+            let geometry_source = self.panels[i].geometry_source(source_directory.as_deref());
+            let geometry_shader = match geometry_source.filter(|path| changed.contains(path)) {
+                Some(path) => {
+                    let module =
+                        compiled_vertex.entry(path.clone()).or_insert_with(|| {
+                            match self
+                                .shader_compiler
+                                .load(&device, &path, ShaderKind::Vertex)
+                            {
+                                Ok(module) => {
+                                    reloaded.push(path.clone());
+                                    Some(module)
+                                }
+                                Err(e) => {
+                                    eprintln!("{e}");
+                                    eprintln!("keeping the previous pipeline");
+                                    None
+                                }
+                            }
+                        });
+                    let Some(module) = module else {
+                        continue;
+                    };
+                    match Panel::specialize(module, config) {
+                        Ok(geometry_shader) => Some(geometry_shader),
+                        Err(e) => {
+                            eprintln!("{e}");
+                            continue;
+                        }
+                    }
+                }
+                None => None,
+            };
+
+            if fragment.is_none() && geometry_shader.is_none() && !vertex_reloaded {
+                continue;
+            }
+            let (fragment_shader, state) =
+                fragment.unwrap_or_else(|| (self.fragment_shaders[i].clone(), None));
+            let geometry_shader = geometry_shader.or_else(|| self.panel_vertex_shaders[i].clone());
+
+            // pipelines only exist once `resize` has been called
+            if i < self.pipelines.len() {
+                match self.create_panel_pipeline(
+                    &self.panels[i],
+                    layered[i],
+                    geometry_shader.as_ref(),
+                    &fragment_shader,
+                ) {
+                    Ok(pipeline) => self.pipelines[i] = pipeline,
+                    Err(e) => {
+                        eprintln!("{e}");
+                        continue;
+                    }
+                }
+            }
+            self.panel_vertex_shaders[i] = geometry_shader;
+            self.fragment_shaders[i] = fragment_shader;
+            if let Some(state) = state {
+                self.states[i] = state;
+            }
+        }
+
+        // the passes around the panels draw with `vertex.glsl` too
+        let mut pass_shaders = HashMap::new();
+        let mut pass_paths = Vec::new();
+        if let Some(directory) = &source_directory {
+            for source in PostProcessing::SOURCES.into_iter().chain(["feedback"]) {
+                let path = directory.join(format!("{source}.glsl"));
+                if !changed.contains(&path) {
+                    continue;
+                }
+                match load_main(
+                    &mut self.shader_compiler,
+                    &device,
+                    &path,
+                    ShaderKind::Fragment,
+                ) {
+                    Ok(shader) => {
+                        pass_shaders.insert(source, shader);
+                        pass_paths.push(path);
+                    }
+                    Err(e) => {
+                        eprintln!("{e}");
+                        eprintln!("keeping the previous shader");
+                    }
+                }
+            }
+        }
+        if vertex_reloaded || !pass_shaders.is_empty() {
+            match self.replace_pass_shaders(&pass_shaders) {
+                Ok(()) => reloaded.extend(pass_paths),
+                Err(e) => {
+                    eprintln!("{e}");
+                    eprintln!("keeping the previous shaders of the feedback and post passes");
+                }
+            }
+        }
+
+        if let Some(directory) = &source_directory
+            && let Some(gpu_dft) = &mut self.gpu_dft
+        {
+            let path = directory.join("dft.glsl");
+            if changed.contains(&path) {
+                match self
+                    .shader_compiler
+                    .load(&device, &path, ShaderKind::Compute)
+                    .and_then(|module| gpu_dft.replace_shader(&module, config))
+                {
+                    Ok(()) => reloaded.push(path),
+                    Err(e) => {
+                        eprintln!("{e}");
+                        eprintln!("keeping the previous DFT shader");
+                    }
+                }
+            }
+        }
+
+        for path in reloaded {
+            println!("Reloaded {}", path.display());
+        }
+    }
+
+    /// Swaps in the fragment shaders of the feedback, layer and post passes in `shaders`, by the
+    /// name of their source, and rebuilds their pipelines with the current vertex shader.
+    fn replace_pass_shaders(
+        &mut self,
+        shaders: &HashMap<&str, EntryPoint>,
+    ) -> Result<(), LavaError> {
+        self.post_processing.replace_shaders(
+            shaders,
+            self.extent,
+            &self.output_render_pass,
+            &self.vertex_shader,
+        )?;
+        self.feedback_warp.replace_shader(
+            shaders.get("feedback").cloned(),
+            self.extent,
+            &self.render_pass,
+            &self.vertex_shader,
+        )?;
+        // the same shader draws the layers and the output of the post chain
+        self.compositor.replace_shader(
+            shaders.get("composite").cloned(),
+            self.extent,
+            &self.render_pass,
+            &self.vertex_shader,
+        )
+    }
+}
+
+// the sampler, texture and frame of `uniforms/image.glsl`
+const IMAGE_BINDINGS: [u32; 3] = [6, 7, 9];
+
+/// Loads the fragment shader of `panel` with the state its material needs, compiling it from
+/// source if it has one.
+pub fn load_fragment_shader(
+    shader_compiler: &mut ShaderCompiler,
+    memory_allocator: &Arc<StandardMemoryAllocator>,
+    panel: &Panel,
+    config: &Config,
+) -> Result<(EntryPoint, MaterialState), LavaError> {
+    let compute_shader = load_compute_shader(shader_compiler, memory_allocator.device(), panel)?;
+    match (
+        panel.shader_source(shader_compiler.source_directory()),
+        panel.material.shader(),
+    ) {
+        (Some(path), _) => {
+            let words = compile_fragment_shader(shader_compiler, panel, &path)?;
+            build_fragment_shader(
+                memory_allocator,
+                panel,
+                &path,
+                &words,
+                compute_shader.as_ref(),
+                config,
+            )
+        }
+        (None, MaterialShader::Embedded { load, .. }) => {
+            let module = load(memory_allocator.device().clone())
+                .map_err(|e| LavaError::gpu("loading a fragment shader", e))?;
+            let state = panel
+                .material
+                .create_state(&StateContext {
+                    words: None,
+                    compute_shader: compute_shader.as_ref(),
+                    memory_allocator,
+                })
+                .map_err(|e| LavaError::gpu("creating a material", e))?;
+            Ok((Panel::specialize(&module, config)?, state))
+        }
+        (None, MaterialShader::File { .. }) => unreachable!("file shaders have a source"),
+    }
+}
+
+/// Loads the vertex shader of `panel`'s material if it draws its own geometry, compiling it from
+/// source if there is a source directory.
+pub fn load_geometry_shader(
+    shader_compiler: &mut ShaderCompiler,
+    device: &Arc<Device>,
+    panel: &Panel,
+    config: &Config,
+) -> Result<Option<EntryPoint>, LavaError> {
+    let Some(geometry) = panel.material.geometry() else {
+        return Ok(None);
+    };
+    let module = match panel.geometry_source(shader_compiler.source_directory()) {
+        Some(path) => shader_compiler.load(device, &path, ShaderKind::Vertex)?,
+        None => (geometry.load)(device.clone())
+            .map_err(|e| LavaError::gpu("loading a vertex shader", e))?,
+    };
+    Panel::specialize(&module, config).map(Some)
+}
+
+/// Compiles the compute shader of `panel`'s state from source if there is a source directory,
+/// the state loads the embedded one otherwise.
+fn load_compute_shader(
+    shader_compiler: &mut ShaderCompiler,
+    device: &Arc<Device>,
+    panel: &Panel,
+) -> Result<Option<Arc<ShaderModule>>, LavaError> {
+    panel
+        .compute_source(shader_compiler.source_directory())
+        .map(|path| shader_compiler.load(device, &path, ShaderKind::Compute))
+        .transpose()
+}
+
+/// Compiles the shader at `path` and returns its `main`, for the shaders without analysis
+/// constants.
+fn load_main(
+    shader_compiler: &mut ShaderCompiler,
+    device: &Arc<Device>,
+    path: &Path,
+    kind: ShaderKind,
+) -> Result<EntryPoint, LavaError> {
+    shader_compiler
+        .load(device, path, kind)?
+        .entry_point("main")
+        .ok_or_else(|| LavaError::Shader {
+            path: path.to_path_buf(),
+            message: "no `main` entry point".into(),
+        })
+}
+
+/// Fails if the fragment shader of `panel` compiled from `path` reads the image of
+/// `uniforms/image.glsl` but `config` gives it none, as custom shaders can.
+pub fn check_image(
+    panel: &Panel,
+    path: &Path,
+    fragment_shader: &EntryPoint,
+    config: &Config,
+) -> Result<(), LavaError> {
+    let reads_image = IMAGE_BINDINGS.iter().any(|binding| {
+        fragment_shader
+            .info()
+            .descriptor_binding_requirements
+            .contains_key(&(0, *binding))
+    });
+    let has_image = panel.material.inputs().contains(&GlobalInput::Image)
+        && match panel.material.image() {
+            Some(name) => config.textures.contains_key(name),
+            None => config.image_path.is_some(),
+        };
+    if reads_image && !has_image {
+        return Err(LavaError::Shader {
+            path: path.to_path_buf(),
+            message: "reads the image of `uniforms/image.glsl`, but the config sets no \
+                      `image_path` or `image` from `textures`"
+                .into(),
+        });
+    }
+    Ok(())
+}
+
+/// Compiles the fragment shader source of `panel` at `path`, preprocessed by its material.
+fn compile_fragment_shader(
+    shader_compiler: &mut ShaderCompiler,
+    panel: &Panel,
+    path: &Path,
+) -> Result<Vec<u32>, LavaError> {
+    match panel.material.shader() {
+        MaterialShader::File {
+            preprocess: Some(preprocess),
+            ..
+        } => {
+            let source = fs::read_to_string(path).map_err(|source| LavaError::Io {
+                path: path.to_path_buf(),
+                source,
+            })?;
+            shader_compiler.compile_source(path, &preprocess(&source), ShaderKind::Fragment)
+        }
+        _ => shader_compiler.compile(path, ShaderKind::Fragment),
+    }
+}
+
+fn build_fragment_shader(
+    memory_allocator: &Arc<StandardMemoryAllocator>,
+    panel: &Panel,
+    path: &Path,
+    words: &[u32],
+    compute_shader: Option<&Arc<ShaderModule>>,
+    config: &Config,
+) -> Result<(EntryPoint, MaterialState), LavaError> {
+    let module = create_module(memory_allocator.device(), path, words)?;
+    let fragment_shader = Panel::specialize(&module, config)?;
+    check_image(panel, path, &fragment_shader, config)?;
+
+    let state = panel
+        .material
+        .create_state(&StateContext {
+            words: Some(words),
+            compute_shader,
+            memory_allocator,
+        })
+        .map_err(|message| LavaError::Shader {
+            path: path.to_path_buf(),
+            message,
+        })?;
+    Ok((fragment_shader, state))
+}
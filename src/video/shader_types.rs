@@ -1,12 +1,18 @@
-use glam::{Vec3, vec3};
-use serde::{Deserialize, Serialize};
+use glam::{Vec2, Vec3, vec2, vec3};
+use schemars::JsonSchema;
+use serde::{
+    Deserialize, Deserializer, Serialize,
+    de::{self, MapAccess, SeqAccess, Unexpected, Visitor},
+};
+use std::{collections::BTreeMap, fmt, path::PathBuf};
 use vulkano::padded::Padded;
 
 use crate::video::shaders;
 
-#[derive(Clone, Serialize, Deserialize)]
-#[serde(default)]
+#[derive(Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
 pub struct Pattern {
+    #[schemars(with = "[f32; 3]")]
     pub color: Vec3,
     pub use_rainbow: bool,
     pub lightness: f32,
@@ -47,12 +53,20 @@ impl From<Pattern> for shaders::Pattern {
     }
 }
 
-#[derive(Clone, Serialize, Deserialize)]
-#[serde(default)]
+#[derive(Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
 pub struct WaveformParameters {
     pub pattern: Pattern,
     pub line_width: f32,
     pub gain: f32,
+    pub trigger: WaveformTrigger,
+    /// The level the edge triggers cross, as drawn, with the gain applied.
+    pub trigger_level: f32,
+    /// The milliseconds of samples across the panel, all of them if 0.
+    pub window: f32,
+    pub rendering: WaveformRendering,
+    /// How the segments of `lines` rendering meet.
+    pub join: LineJoin,
 }
 
 impl Default for WaveformParameters {
@@ -61,22 +75,51 @@ impl Default for WaveformParameters {
             pattern: Default::default(),
             line_width: 50.0,
             gain: 0.9,
+            trigger: WaveformTrigger::default(),
+            trigger_level: 0.0,
+            window: 0.0,
+            rendering: WaveformRendering::default(),
+            join: LineJoin::default(),
         }
     }
 }
 
-impl From<WaveformParameters> for shaders::WaveformParameters {
-    fn from(value: WaveformParameters) -> Self {
-        Self {
-            pattern: Padded(value.pattern.into()),
-            line_width: value.line_width,
-            gain: value.gain,
-        }
-    }
+/// Which sample a waveform panel shows at the analyzer's focus.
+#[derive(Default, Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum WaveformTrigger {
+    /// The latest samples, unsynchronized.
+    FreeRun,
+    /// The latest crossing of `trigger_level` going up, free running if there is none.
+    Rising,
+    Falling,
+    /// A phase of the loudest frequency, so that its period stays still.
+    #[default]
+    Pitch,
+}
+
+/// How a waveform panel draws its line.
+#[derive(Default, Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum WaveformRendering {
+    /// Every pixel of the panel finds its distance to the nearby samples.
+    #[default]
+    Distance,
+    /// The samples are connected by triangles covering only the line.
+    Lines,
+}
+
+#[derive(Default, Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum LineJoin {
+    /// The edges of the segments extended until they meet, up to 4 times the line's width.
+    #[default]
+    Miter,
+    Round,
 }
 
-#[derive(Default, Clone, Serialize, Deserialize)]
-#[serde(default)]
+#[derive(Default, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
 pub struct SimplePatternParameters {
     pub pattern: Pattern,
 }
@@ -89,8 +132,8 @@ impl From<SimplePatternParameters> for shaders::SimplePatternParameters {
     }
 }
 
-#[derive(Clone, Serialize, Deserialize)]
-#[serde(default)]
+#[derive(Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
 pub struct SpectrogramParameters {
     pub pattern: Pattern,
     pub gain: f32,
@@ -114,8 +157,342 @@ impl From<SpectrogramParameters> for shaders::SpectrogramParameters {
     }
 }
 
-#[derive(Clone, Serialize, Deserialize)]
-#[serde(default)]
+/// The waveform wrapped around a ring. Lengths are in panel heights.
+#[derive(Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct CircularWaveformParameters {
+    pub pattern: Pattern,
+    pub line_width: f32,
+    pub gain: f32,
+    pub radius: f32,
+    /// How far from the ring a full-scale sample moves the line.
+    pub amplitude: f32,
+    /// How much the bass grows the radius.
+    pub breathing: f32,
+    /// The samples around the ring, rounded to whole periods of the pitch.
+    pub window: f32,
+}
+
+impl Default for CircularWaveformParameters {
+    fn default() -> Self {
+        Self {
+            pattern: Default::default(),
+            line_width: 0.01,
+            gain: 0.9,
+            radius: 0.3,
+            amplitude: 0.1,
+            breathing: 0.05,
+            window: 2048.0,
+        }
+    }
+}
+
+impl From<CircularWaveformParameters> for shaders::CircularWaveformParameters {
+    fn from(value: CircularWaveformParameters) -> Self {
+        Self {
+            pattern: Padded(value.pattern.into()),
+            line_width: value.line_width,
+            gain: value.gain,
+            radius: value.radius,
+            amplitude: value.amplitude,
+            breathing: value.breathing,
+            window: value.window,
+        }
+    }
+}
+
+/// The spectrum as rays around a circle. Lengths are in panel heights.
+#[derive(Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct RadialSpectrumParameters {
+    pub pattern: Pattern,
+    pub line_width: f32,
+    pub gain: f32,
+    pub radius: f32,
+    /// The length of a ray at full magnitude.
+    pub ray_length: f32,
+    /// How much the bass grows the radius.
+    pub breathing: f32,
+    pub rays: u32,
+}
+
+impl Default for RadialSpectrumParameters {
+    fn default() -> Self {
+        Self {
+            pattern: Default::default(),
+            line_width: 0.008,
+            gain: 1.5,
+            radius: 0.2,
+            ray_length: 0.25,
+            breathing: 0.05,
+            rays: 96,
+        }
+    }
+}
+
+impl From<RadialSpectrumParameters> for shaders::RadialSpectrumParameters {
+    fn from(value: RadialSpectrumParameters) -> Self {
+        Self {
+            pattern: Padded(value.pattern.into()),
+            line_width: value.line_width,
+            gain: value.gain,
+            radius: value.radius,
+            ray_length: value.ray_length,
+            breathing: value.breathing,
+            rays: value.rays,
+        }
+    }
+}
+
+/// The spectrum as discrete bars, spread evenly over octaves.
+#[derive(Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct BarsParameters {
+    pub pattern: Pattern,
+    pub bars: u32,
+    /// In Hz.
+    pub min_frequency: f32,
+    pub max_frequency: f32,
+    pub gain: f32,
+    /// The space between bars, in fractions of a bar's width.
+    pub gap: f32,
+    pub rounded: bool,
+    /// Grows the bars from the middle of the panel both ways instead of from the bottom.
+    pub mirrored: bool,
+    /// The seconds the peak markers stay before falling, no markers if 0.
+    pub peak_hold: f32,
+    /// In panel heights per second.
+    pub peak_fall: f32,
+    /// LED segments in a full bar, continuous bars if 0.
+    pub segments: u32,
+    /// The space between segments, in fractions of a segment's height.
+    pub segment_gap: f32,
+}
+
+impl Default for BarsParameters {
+    fn default() -> Self {
+        Self {
+            pattern: Default::default(),
+            bars: 32,
+            min_frequency: 30.0,
+            max_frequency: 16000.0,
+            gain: 1.5,
+            gap: 0.2,
+            rounded: false,
+            mirrored: false,
+            peak_hold: 0.5,
+            peak_fall: 0.5,
+            segments: 0,
+            segment_gap: 0.3,
+        }
+    }
+}
+
+/// A level meter of the input before the automatic gain. Levels are in dBFS, or LUFS for the
+/// loudness ballistics.
+#[derive(Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct MeterParameters {
+    pub ballistics: MeterBallistics,
+    pub orientation: MeterOrientation,
+    /// The levels at the ends of the meter.
+    pub min_level: f32,
+    pub max_level: f32,
+    /// Where the zones of `warning_color` and `danger_color` start.
+    pub warning_level: f32,
+    pub danger_level: f32,
+    #[schemars(with = "[f32; 3]")]
+    pub color: Vec3,
+    #[schemars(with = "[f32; 3]")]
+    pub warning_color: Vec3,
+    #[schemars(with = "[f32; 3]")]
+    pub danger_color: Vec3,
+    /// The seconds the peak indicator stays at the highest sample before falling, none if 0.
+    pub peak_hold: f32,
+    /// In dB per second.
+    pub peak_fall: f32,
+}
+
+impl Default for MeterParameters {
+    fn default() -> Self {
+        Self {
+            ballistics: MeterBallistics::default(),
+            orientation: MeterOrientation::default(),
+            min_level: -60.0,
+            max_level: 0.0,
+            warning_level: -18.0,
+            danger_level: -6.0,
+            color: vec3(0.2, 0.9, 0.3),
+            warning_color: vec3(1.0, 0.8, 0.1),
+            danger_color: vec3(1.0, 0.15, 0.1),
+            peak_hold: 1.5,
+            peak_fall: 20.0,
+        }
+    }
+}
+
+#[derive(Default, Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum MeterBallistics {
+    Vu,
+    #[default]
+    Ppm,
+    Rms,
+    LufsMomentary,
+    LufsShortTerm,
+}
+
+#[derive(Default, Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum MeterOrientation {
+    #[default]
+    Vertical,
+    Horizontal,
+}
+
+/// Particles simulated on the GPU and drawn as additive sprites. Positions and velocities are
+/// in panel heights, with y going up from -1 at the bottom to 1 at the top.
+#[derive(Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct ParticlesParameters {
+    pub pattern: Pattern,
+    /// The particles alive at once, the oldest are replaced when more are emitted.
+    pub max_particles: u32,
+    pub emission: ParticleEmission,
+    /// Particles emitted per second at full bass, or per beat.
+    pub rate: f32,
+    /// In seconds.
+    pub lifetime: f32,
+    #[schemars(with = "[f32; 2]")]
+    pub origin: Vec2,
+    /// The angle particles are emitted at in degrees, counterclockwise from the right, and how
+    /// far they spread to either side of it.
+    pub direction: f32,
+    pub spread: f32,
+    pub speed: f32,
+    #[schemars(with = "[f32; 2]")]
+    pub gravity: Vec2,
+    /// How strongly particles are swirled by curl noise, and the size of its swirls.
+    pub curl: f32,
+    pub curl_scale: f32,
+    pub size: f32,
+}
+
+impl Default for ParticlesParameters {
+    fn default() -> Self {
+        Self {
+            pattern: Default::default(),
+            max_particles: 4096,
+            emission: ParticleEmission::default(),
+            rate: 2000.0,
+            lifetime: 2.0,
+            origin: vec2(0.0, -0.8),
+            direction: 90.0,
+            spread: 30.0,
+            speed: 0.8,
+            gravity: vec2(0.0, -0.5),
+            curl: 0.3,
+            curl_scale: 2.0,
+            size: 0.02,
+        }
+    }
+}
+
+impl From<ParticlesParameters> for shaders::ParticlesParameters {
+    fn from(value: ParticlesParameters) -> Self {
+        Self {
+            pattern: Padded(value.pattern.into()),
+            size: value.size,
+        }
+    }
+}
+
+/// What makes a particles panel emit.
+#[derive(Default, Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ParticleEmission {
+    /// A steady stream scaled by `Bass::bass`.
+    #[default]
+    Bass,
+    /// Bursts at the detected beats.
+    Beats,
+}
+
+/// A 3D mesh seen through a perspective camera and lit by a single light, with its vertices
+/// pushed along their normals by the audio. Meshes are scaled to a radius of 1.
+#[derive(Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct MeshParameters {
+    pub pattern: Pattern,
+    pub shape: MeshShape,
+    /// The OBJ file of the `obj` shape.
+    pub path: PathBuf,
+    /// The subdivisions of the generated shapes around their longest side.
+    pub detail: u32,
+    pub displacement: MeshDisplacement,
+    /// How far the vertices move at full level, in mesh radii.
+    pub amount: f32,
+    /// The degrees per second the mesh turns around the vertical, and the degrees it is tilted
+    /// towards the camera.
+    pub spin: f32,
+    pub tilt: f32,
+    /// The vertical field of view in degrees.
+    pub field_of_view: f32,
+    /// In mesh radii from the middle of the mesh.
+    pub camera_distance: f32,
+    /// Towards the light, with y going up and z towards the camera.
+    #[schemars(with = "[f32; 3]")]
+    pub light_direction: Vec3,
+    /// The lightness of the sides facing away from the light.
+    pub ambient: f32,
+}
+
+impl Default for MeshParameters {
+    fn default() -> Self {
+        Self {
+            pattern: Default::default(),
+            shape: MeshShape::default(),
+            path: PathBuf::new(),
+            detail: 64,
+            displacement: MeshDisplacement::default(),
+            amount: 0.3,
+            spin: 20.0,
+            tilt: 20.0,
+            field_of_view: 45.0,
+            camera_distance: 3.5,
+            light_direction: vec3(-0.5, 1.0, 1.0),
+            ambient: 0.2,
+        }
+    }
+}
+
+#[derive(Default, Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum MeshShape {
+    #[default]
+    Sphere,
+    /// A square lying flat.
+    Plane,
+    Torus,
+    /// The mesh in the file at `path`.
+    Obj,
+}
+
+/// What pushes the vertices of a mesh panel, spread over the mesh from the seam of its texture
+/// coordinates to their middle.
+#[derive(Default, Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum MeshDisplacement {
+    None,
+    /// The magnitudes of the spectrum, from the lowest frequency to the highest.
+    #[default]
+    Dft,
+    /// A period of the stabilized waveform.
+    Waveform,
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
 pub struct GrayVenueGridnodeParameters {
     pub lightness: f32,
     pub chroma: f32,
@@ -139,9 +516,11 @@ impl From<GrayVenueGridnodeParameters> for shaders::GrayVenueGridnodeParameters
     }
 }
 
-#[derive(Clone, Serialize, Deserialize)]
-#[serde(default)]
+#[derive(Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
 pub struct ImageParameters {
+    /// The name of a texture in `textures`, the image at `image_path` if not set.
+    pub image: Option<String>,
     pub scale_min: f32,
     pub scale_max: f32,
 }
@@ -149,6 +528,7 @@ pub struct ImageParameters {
 impl Default for ImageParameters {
     fn default() -> Self {
         Self {
+            image: None,
             scale_min: 0.5,
             scale_max: 1.0,
         }
@@ -164,9 +544,11 @@ impl From<ImageParameters> for shaders::ImageParameters {
     }
 }
 
-#[derive(Clone, Serialize, Deserialize)]
-#[serde(default)]
+#[derive(Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
 pub struct MaskedPatternParameters {
+    /// The name of a texture in `textures`, the image at `image_path` if not set.
+    pub image: Option<String>,
     pub pattern: Pattern,
     pub scale_min: f32,
     pub scale_max: f32,
@@ -175,6 +557,7 @@ pub struct MaskedPatternParameters {
 impl Default for MaskedPatternParameters {
     fn default() -> Self {
         Self {
+            image: None,
             pattern: Default::default(),
             scale_min: 0.5,
             scale_max: 1.0,
@@ -191,3 +574,189 @@ impl From<MaskedPatternParameters> for shaders::MaskedPatternParameters {
         }
     }
 }
+
+#[derive(Default, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct CustomParameters {
+    /// The fragment shader source, relative to the config file.
+    pub shader: PathBuf,
+    /// Values for the members of the shader's `binding = 10` uniform block, by name.
+    pub uniforms: BTreeMap<String, CustomValue>,
+}
+
+/// A value of a custom shader parameter, matched against the declared member type when packed.
+#[derive(Clone, PartialEq, Serialize, JsonSchema)]
+#[serde(untagged)]
+pub enum CustomValue {
+    /// A `bool`, or a `uint`/`int` set to 0 or 1.
+    Bool(bool),
+    /// An `int`, `uint` or `float`, kept exact until it is packed.
+    Integer(i64),
+    /// A `float`, or an `int`/`uint` if it is a whole number in range.
+    Number(f32),
+    /// A `vec2`, `vec3` or `vec4`; three numbers also fill a `vec4` with an alpha of 1.
+    Vector(Vec<f64>),
+    /// A `#rrggbb` or `#rrggbbaa` sRGB color for a `vec3` or `vec4`.
+    Color(String),
+    /// A struct member, such as a `Pattern`.
+    Struct(BTreeMap<String, CustomValue>),
+}
+
+impl CustomValue {
+    /// Parses a `#rrggbb` or `#rrggbbaa` color into linear RGBA, the space the shaders output.
+    pub fn parse_color(color: &str) -> Option<[f32; 4]> {
+        let hex = color.strip_prefix('#')?;
+        if !matches!(hex.len(), 6 | 8) || !hex.is_ascii() {
+            return None;
+        }
+        let channel = |i: usize| u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).ok();
+        let linear = |c: u8| {
+            let c = c as f32 / 255.0;
+            if c <= 0.04045 {
+                c / 12.92
+            } else {
+                ((c + 0.055) / 1.055).powf(2.4)
+            }
+        };
+        Some([
+            linear(channel(0)?),
+            linear(channel(1)?),
+            linear(channel(2)?),
+            if hex.len() == 8 {
+                channel(3)? as f32 / 255.0
+            } else {
+                1.0
+            },
+        ])
+    }
+}
+
+impl<'de> Deserialize<'de> for CustomValue {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct CustomValueVisitor;
+
+        impl<'de> Visitor<'de> for CustomValueVisitor {
+            type Value = CustomValue;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(
+                    f,
+                    "a bool, a number, 2 to 4 numbers, a `#rrggbb` color or an object of those"
+                )
+            }
+
+            fn visit_bool<E: de::Error>(self, value: bool) -> Result<CustomValue, E> {
+                Ok(CustomValue::Bool(value))
+            }
+
+            fn visit_i64<E: de::Error>(self, value: i64) -> Result<CustomValue, E> {
+                Ok(CustomValue::Integer(value))
+            }
+
+            fn visit_u64<E: de::Error>(self, value: u64) -> Result<CustomValue, E> {
+                i64::try_from(value)
+                    .map(CustomValue::Integer)
+                    .map_err(|_| E::invalid_value(Unexpected::Unsigned(value), &self))
+            }
+
+            fn visit_f64<E: de::Error>(self, value: f64) -> Result<CustomValue, E> {
+                Ok(CustomValue::Number(value as f32))
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<CustomValue, E> {
+                match CustomValue::parse_color(value) {
+                    Some(_) => Ok(CustomValue::Color(value.into())),
+                    None => Err(E::invalid_value(
+                        Unexpected::Str(value),
+                        &"a `#rrggbb` or `#rrggbbaa` color",
+                    )),
+                }
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<CustomValue, A::Error> {
+                let mut components = Vec::new();
+                while let Some(component) = seq.next_element::<f64>()? {
+                    components.push(component);
+                }
+                if !(2..=4).contains(&components.len()) {
+                    return Err(de::Error::invalid_length(
+                        components.len(),
+                        &"2 to 4 numbers",
+                    ));
+                }
+                Ok(CustomValue::Vector(components))
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<CustomValue, A::Error> {
+                let mut members = BTreeMap::new();
+                while let Some((name, value)) = map.next_entry::<String, CustomValue>()? {
+                    members.insert(name, value);
+                }
+                Ok(CustomValue::Struct(members))
+            }
+        }
+
+        deserializer.deserialize_any(CustomValueVisitor)
+    }
+}
+
+#[derive(Default, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct ShadertoyParameters {
+    /// The image shader source defining `mainImage`, relative to the config file.
+    pub shader: PathBuf,
+    /// What `iTime` counts.
+    pub time: ShadertoyTime,
+}
+
+#[derive(Default, Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ShadertoyTime {
+    /// The bass-driven clock the built-in materials animate with.
+    #[default]
+    Chrono,
+    /// Seconds since lava started.
+    Wall,
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct TextParameters {
+    /// Split into lines at `\n`. `{bpm}`, `{fps}` and the names set with
+    /// `VideoEngine::set_text` are replaced by their current values.
+    pub text: String,
+    /// A TTF or OTF file, relative to the config file.
+    pub font: PathBuf,
+    /// The height of a line in fractions of the panel's height.
+    pub size: f32,
+    pub align: TextAlign,
+    /// In line heights.
+    pub line_spacing: f32,
+    pub pattern: Pattern,
+    pub scale_min: f32,
+    pub scale_max: f32,
+}
+
+impl Default for TextParameters {
+    fn default() -> Self {
+        Self {
+            text: String::new(),
+            font: PathBuf::new(),
+            size: 0.25,
+            align: TextAlign::default(),
+            line_spacing: 1.0,
+            pattern: Default::default(),
+            scale_min: 1.0,
+            scale_max: 1.0,
+        }
+    }
+}
+
+#[derive(Default, Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TextAlign {
+    Left,
+    #[default]
+    Center,
+    Right,
+}
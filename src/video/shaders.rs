@@ -8,6 +8,14 @@ vulkano_shaders::shader! {
             ty: "fragment",
             path: "src/video/shaders/waveform.glsl",
         },
+        waveform_lines_vertex: {
+            ty: "vertex",
+            path: "src/video/shaders/waveform_lines_vertex.glsl",
+        },
+        waveform_lines: {
+            ty: "fragment",
+            path: "src/video/shaders/waveform_lines.glsl",
+        },
         simple_pattern: {
             ty: "fragment",
             path: "src/video/shaders/simple_pattern.glsl",
@@ -27,6 +35,78 @@ vulkano_shaders::shader! {
         image: {
             ty: "fragment",
             path: "src/video/shaders/image.glsl",
+        },
+        feedback: {
+            ty: "fragment",
+            path: "src/video/shaders/feedback.glsl",
+        },
+        composite: {
+            ty: "fragment",
+            path: "src/video/shaders/composite.glsl",
+        },
+        blur: {
+            ty: "fragment",
+            path: "src/video/shaders/blur.glsl",
+        },
+        chromatic_aberration: {
+            ty: "fragment",
+            path: "src/video/shaders/chromatic_aberration.glsl",
+        },
+        vignette: {
+            ty: "fragment",
+            path: "src/video/shaders/vignette.glsl",
+        },
+        crt: {
+            ty: "fragment",
+            path: "src/video/shaders/crt.glsl",
+        },
+        film_grain: {
+            ty: "fragment",
+            path: "src/video/shaders/film_grain.glsl",
+        },
+        text: {
+            ty: "fragment",
+            path: "src/video/shaders/text.glsl",
+        },
+        circular_waveform: {
+            ty: "fragment",
+            path: "src/video/shaders/circular_waveform.glsl",
+        },
+        radial_spectrum: {
+            ty: "fragment",
+            path: "src/video/shaders/radial_spectrum.glsl",
+        },
+        bars: {
+            ty: "fragment",
+            path: "src/video/shaders/bars.glsl",
+        },
+        meter: {
+            ty: "fragment",
+            path: "src/video/shaders/meter.glsl",
+        },
+        particles_update: {
+            ty: "compute",
+            path: "src/video/shaders/particles_update.glsl",
+        },
+        particles_vertex: {
+            ty: "vertex",
+            path: "src/video/shaders/particles_vertex.glsl",
+        },
+        particles: {
+            ty: "fragment",
+            path: "src/video/shaders/particles.glsl",
+        },
+        mesh_vertex: {
+            ty: "vertex",
+            path: "src/video/shaders/mesh_vertex.glsl",
+        },
+        mesh: {
+            ty: "fragment",
+            path: "src/video/shaders/mesh.glsl",
+        },
+        dft: {
+            ty: "compute",
+            path: "src/video/shaders/dft.glsl",
         }
     },
 }
@@ -0,0 +1,63 @@
+#version 450
+
+#include "lib/in_out.glsl"
+
+#include "lib/consts.glsl"
+
+#include "structs/pattern.glsl"
+
+#include "uniforms/aspect_ratio.glsl"
+#include "uniforms/bass.glsl"
+#include "uniforms/palette.glsl"
+
+layout(set = 0, binding = 10) uniform BarsParameters {
+    Pattern pattern;
+    uint bar_count;
+    float gap;
+    bool rounded;
+    bool mirrored;
+    bool peaks;
+    uint segments;
+    float segment_gap;
+};
+
+// the level and the peak of each bar, between 0 and 1
+layout(set = 0, binding = 13) readonly buffer BarLevels {
+    vec2 bar_levels[];
+};
+
+// in panel heights
+const float PEAK_THICKNESS = 0.01;
+
+void main() {
+    float bar = min(floor(UV.x * float(bar_count)), float(bar_count) - 1.0);
+    vec2 levels = bar_levels[uint(bar)];
+
+    // across the bar from its center, and up the bar, in panel heights
+    float extent = mirrored ? 0.5 : 1.0;
+    float half_width = (1.0 - gap) * 0.5 / float(bar_count) * aspect_ratio;
+    float x = (fract(UV.x * float(bar_count)) - 0.5) / float(bar_count) * aspect_ratio;
+    float y = mirrored ? abs(UV.y - 0.5) : 1.0 - UV.y;
+
+    float level = levels.x;
+    if (segments > 0u) {
+        level = floor(level * float(segments)) / float(segments);
+    }
+    float top = level * extent;
+
+    bool inside = abs(x) <= half_width;
+    if (rounded) {
+        // a cap as wide as the bar, the bar's height includes it
+        float cap = top - half_width;
+        inside = inside && (y <= cap || length(vec2(x, y - cap)) <= half_width);
+    }
+    inside = inside && y <= top;
+    if (segments > 0u) {
+        inside = inside && fract(y / extent * float(segments)) <= 1.0 - segment_gap;
+    }
+
+    bool peak = peaks && abs(x) <= half_width && abs(y - levels.y * extent) <= PEAK_THICKNESS * 0.5;
+
+    vec3 col = get_palette_color(pattern, UV, aspect_ratio, chrono);
+    COLOR = vec4(col, (inside || peak) ? 1.0 : 0.0);
+}
@@ -0,0 +1,44 @@
+#version 450
+
+#include "lib/in_out.glsl"
+
+#include "uniforms/frame.glsl"
+
+// what the blurred frame is added to
+layout(set = 0, binding = 12) uniform sampler2D base;
+
+layout(set = 0, binding = 10) uniform BlurParameters {
+    // one pixel along the blur, in UVs
+    vec2 direction;
+    // in pixels
+    float radius;
+    // only what is brighter than this gets blurred
+    float threshold;
+    float base_amount;
+    float intensity;
+};
+
+vec4 bright(vec2 uv) {
+    vec4 color = texture(frame, uv);
+    return vec4(max(color.rgb - threshold, 0.0), color.a);
+}
+
+void main() {
+    // a tap per pixel, spread out past 32 taps on each side
+    float sigma = max(radius, 0.001) / 3.0;
+    int taps = int(min(ceil(radius), 32.0));
+    float spacing = radius / max(float(taps), 1.0);
+
+    vec4 sum = bright(UV);
+    float total = 1.0;
+    for (int i = 1; i <= taps; i++) {
+        float x = float(i) * spacing;
+        float weight = exp(-0.5 * x * x / (sigma * sigma));
+        sum += (bright(UV + direction * x) + bright(UV - direction * x)) * weight;
+        total += 2.0 * weight;
+    }
+    vec4 blurred = sum / total;
+
+    COLOR = texture(base, UV) * base_amount
+            + vec4(blurred.rgb * intensity, blurred.a * (1.0 - base_amount));
+}
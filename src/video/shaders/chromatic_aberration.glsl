@@ -0,0 +1,18 @@
+#version 450
+
+#include "lib/in_out.glsl"
+
+#include "uniforms/frame.glsl"
+
+layout(set = 0, binding = 10) uniform ChromaticAberrationParameters {
+    // how far apart red and blue are in the corners, in pixels
+    float amount;
+};
+
+void main() {
+    vec2 shift = (UV - 0.5) * amount / vec2(textureSize(frame, 0));
+    vec4 color = texture(frame, UV);
+    color.r = texture(frame, UV + shift).r;
+    color.b = texture(frame, UV - shift).b;
+    COLOR = color;
+}
@@ -0,0 +1,68 @@
+#version 450
+
+#include "lib/in_out.glsl"
+
+#include "lib/consts.glsl"
+#include "lib/polar.glsl"
+
+#include "structs/pattern.glsl"
+
+#include "uniforms/aspect_ratio.glsl"
+#include "uniforms/palette.glsl"
+#include "uniforms/samples.glsl"
+#include "uniforms/stabilization.glsl"
+#include "uniforms/bass.glsl"
+
+layout(set = 0, binding = 10) uniform CircularWaveformParameters {
+    Pattern pattern;
+    float line_width;
+    float gain;
+    float radius;
+    float amplitude;
+    float breathing;
+    float window;
+};
+
+float get_raw_sample(int sample_index) {
+    sample_index = clamp(sample_index, 0, int(SAMPLE_COUNT) - 1);
+    return samples_data[(uint(sample_index) + samples_start) % SAMPLE_COUNT] * gain;
+}
+
+float get_sample(float sample_index) {
+    return mix(
+        get_raw_sample(int(floor(sample_index))),
+        get_raw_sample(int(ceil(sample_index))),
+        fract(sample_index)
+    );
+}
+
+float fade(float dist) {
+    float x = clamp(dist, 0, 1);
+    return 1.0 - (x * x);
+}
+
+// the ring holds whole periods around the stabilized center, so that its ends meet
+float ring_samples() {
+    return max(round(min(window, SAMPLE_COUNT_F * 0.5) / period), 1.0) * period;
+}
+
+float curve_radius(float turns, float inner, float samples) {
+    return inner + get_sample(center_sample + (turns - 0.5) * samples) * amplitude;
+}
+
+void main() {
+    vec2 point = to_polar(UV, aspect_ratio);
+    float inner = radius + bass * breathing;
+    float samples = ring_samples();
+
+    float curve = curve_radius(point.y, inner, samples);
+    // the radial distance shrunk by the slope of the curve, close to the real distance
+    float delta = 1.0 / samples;
+    float slope = (curve_radius(point.y + delta, inner, samples) - curve_radius(point.y - delta, inner, samples))
+        / (2.0 * delta * TWO_PI * max(curve, 1e-6));
+    float dist = abs(point.x - curve) / sqrt(1.0 + slope * slope);
+
+    float val = fade(dist * 2.0 / line_width);
+    vec3 col = get_palette_color(pattern, UV, aspect_ratio, chrono);
+    COLOR = vec4(col, val);
+}
@@ -0,0 +1,51 @@
+#version 450
+
+#include "lib/in_out.glsl"
+#include "lib/oklab.glsl"
+
+#include "uniforms/frame.glsl"
+
+// the panel whose alpha shows or hides the frame
+layout(set = 0, binding = 12) uniform sampler2D mask;
+
+layout(set = 0, binding = 10) uniform CompositeParameters {
+    float opacity;
+    // 0 ignores the mask, 1 shows the frame where it is drawn, 2 where it isn't
+    uint mask_mode;
+    // 0 keeps the colors above 1 for the layers, 1 only applies the exposure, 2 is Reinhard, 3 ACES
+    uint tonemap;
+    float exposure;
+};
+
+// the colors are drawn sRGB encoded, unlike `srgb_lsrgb` this keeps the ones above 1
+vec3 hdr_lsrgb(vec3 srgb) {
+    srgb = max(srgb, 0.0);
+    vec3 xlo = srgb / 12.92;
+    vec3 xhi = pow((srgb + 0.055) / 1.055, vec3(2.4));
+    return mix(xlo, xhi, step(vec3(0.04045), srgb));
+}
+
+vec3 tonemap_srgb(vec3 srgb) {
+    vec3 x = hdr_lsrgb(srgb) * exposure;
+    if (tonemap == 2) {
+        x = x / (1.0 + x);
+    } else if (tonemap == 3) {
+        x = (x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14);
+    }
+    return lsrgb_srgb(x);
+}
+
+void main() {
+    vec2 uv = gl_FragCoord.xy / vec2(textureSize(frame, 0));
+    float coverage = 1.0;
+    if (mask_mode == 1) {
+        coverage = texture(mask, uv).a;
+    } else if (mask_mode == 2) {
+        coverage = 1.0 - texture(mask, uv).a;
+    }
+    vec4 color = texture(frame, uv);
+    if (tonemap != 0) {
+        color.rgb = tonemap_srgb(color.rgb);
+    }
+    COLOR = color * opacity * coverage;
+}
@@ -0,0 +1,31 @@
+#version 450
+
+#include "lib/in_out.glsl"
+
+#include "lib/oklab.glsl"
+
+#include "uniforms/frame.glsl"
+
+layout(set = 0, binding = 10) uniform CrtParameters {
+    float scanline_intensity;
+    // the height of a scanline in pixels
+    float scanline_size;
+    float curvature;
+};
+
+void main() {
+    // bulges the picture out like a tube screen
+    vec2 position = UV * 2.0 - 1.0;
+    position *= 1.0 + curvature * dot(position, position) * 0.25;
+    vec2 uv = position * 0.5 + 0.5;
+
+    if (any(lessThan(uv, vec2(0.0))) || any(greaterThan(uv, vec2(1.0)))) {
+        COLOR = vec4(0.0, 0.0, 0.0, 1.0);
+        return;
+    }
+
+    vec4 color = texture(frame, uv);
+    float line = uv.y * float(textureSize(frame, 0).y) / scanline_size;
+    color.rgb *= 1.0 - scanline_intensity * (0.5 + 0.5 * cos(line * TWO_PI));
+    COLOR = color;
+}
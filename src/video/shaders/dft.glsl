@@ -0,0 +1,53 @@
+#version 450
+
+#include "lib/consts.glsl"
+
+#include "uniforms/samples.glsl"
+#include "uniforms/dft.glsl"
+
+// one workgroup for each bin
+layout(local_size_x = 64) in;
+
+struct DftBin {
+    uint window_start;
+    // where the bin's terms start in `terms`
+    uint offset;
+    uint window_length;
+    float total_window;
+};
+
+layout(set = 0, binding = 0) readonly buffer DftBins {
+    DftBin bins[];
+};
+
+// the complex exponentials of every bin multiplied by its window, one bin after the other
+layout(set = 0, binding = 1) readonly buffer DftTerms {
+    vec2 terms[];
+};
+
+shared vec2 partial_sums[64];
+
+void main() {
+    uint bin = gl_WorkGroupID.x;
+    uint thread = gl_LocalInvocationID.x;
+    DftBin data = bins[bin];
+
+    vec2 sum = vec2(0.0);
+    for (uint i = thread; i < data.window_length; i += gl_WorkGroupSize.x) {
+        float sample_value = samples_data[(samples_start + data.window_start + i) % SAMPLE_COUNT];
+        sum += terms[data.offset + i] * sample_value;
+    }
+    partial_sums[thread] = sum;
+    barrier();
+
+    for (uint stride = gl_WorkGroupSize.x / 2; stride > 0; stride /= 2) {
+        if (thread < stride) {
+            partial_sums[thread] += partial_sums[thread + stride];
+        }
+        barrier();
+    }
+
+    if (thread == 0) {
+        dft[bin] = partial_sums[0] / data.total_window;
+    }
+}
@@ -0,0 +1,36 @@
+#version 450
+
+#include "lib/in_out.glsl"
+
+#include "lib/oklab.glsl"
+
+#include "uniforms/frame.glsl"
+
+layout(set = 0, binding = 10) uniform FeedbackParameters {
+    vec2 center;
+    vec2 offset;
+    vec2 screen_size;
+    float decay;
+    float zoom;
+    float rotation;
+    float hue_shift;
+};
+
+void main() {
+    // where the pixel was in the previous frame, rotating and zooming in pixels so that the
+    // aspect ratio is kept
+    vec2 pixel = (UV - offset - center) * screen_size;
+    float c = cos(rotation);
+    float s = sin(rotation);
+    pixel = mat2(c, -s, s, c) * pixel / zoom;
+    vec4 previous = texture(frame, center + pixel / screen_size);
+
+    // the layers hold sRGB encoded colors, the hue is turned in linear light
+    if (hue_shift != 0.0 && previous.a > 0.0) {
+        vec3 lsrgb = srgb_lsrgb(previous.rgb / previous.a);
+        previous.rgb = lsrgb_srgb(hueshift(lsrgb, hue_shift)) * previous.a;
+    }
+
+    // the floor makes colors reach black instead of getting stuck on the last 8 bit step
+    COLOR = max(previous * decay - 1.0 / 512.0, 0.0);
+}
@@ -0,0 +1,22 @@
+#version 450
+
+#include "lib/in_out.glsl"
+
+#include "lib/noise.glsl"
+
+#include "uniforms/frame.glsl"
+
+layout(set = 0, binding = 10) uniform FilmGrainParameters {
+    float intensity;
+    // the size of a grain in pixels
+    float size;
+    float time;
+};
+
+void main() {
+    vec2 grain_position = floor(gl_FragCoord.xy / size);
+    float grain = rand(grain_position + fract(time * vec2(0.713, 0.371)) * 1000.0) - 0.5;
+    vec4 color = texture(frame, UV);
+    color.rgb = max(color.rgb + grain * intensity, 0.0);
+    COLOR = color;
+}
@@ -0,0 +1,22 @@
+#ifndef MESH
+#define MESH
+
+#include "../structs/pattern.glsl"
+
+// with y going up and the camera looking at the middle of the mesh from z
+layout(set = 0, binding = 10) uniform MeshParameters {
+    // turns the mesh
+    mat4 model;
+    Pattern pattern;
+    // towards the light
+    vec3 light_direction;
+    float ambient;
+    // 1 / tan of half the vertical field of view
+    float focal_length;
+    float camera_distance;
+    float amount;
+    // 0 for none, 1 for the DFT, 2 for the waveform
+    uint displacement;
+};
+
+#endif
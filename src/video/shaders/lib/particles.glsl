@@ -0,0 +1,13 @@
+#ifndef PARTICLES
+#define PARTICLES
+
+#include "../structs/particle.glsl"
+#include "../structs/pattern.glsl"
+
+layout(set = 0, binding = 10) uniform ParticlesParameters {
+    Pattern pattern;
+    // the radius of the sprites
+    float size;
+};
+
+#endif
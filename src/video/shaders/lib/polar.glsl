@@ -0,0 +1,25 @@
+#ifndef POLAR
+#define POLAR
+
+#ifndef TWO_PI
+#define TWO_PI 6.28318530718
+#endif
+
+// the position from the center of the panel, in panel heights
+vec2 centered(vec2 uv, float aspect_ratio) {
+    return (uv - vec2(0.5, 0.5)) * vec2(aspect_ratio, 1.0);
+}
+
+// the distance from the center of the panel in panel heights, and the angle in turns clockwise
+// from the top
+vec2 to_polar(vec2 uv, float aspect_ratio) {
+    vec2 position = centered(uv, aspect_ratio);
+    return vec2(length(position), fract(atan(position.x, -position.y) / TWO_PI + 1.0));
+}
+
+// the direction at an angle in turns clockwise from the top
+vec2 direction(float turns) {
+    return vec2(sin(turns * TWO_PI), -cos(turns * TWO_PI));
+}
+
+#endif
@@ -0,0 +1,42 @@
+#ifndef WAVEFORM_LINES
+#define WAVEFORM_LINES
+
+#include "consts.glsl"
+
+#include "../structs/pattern.glsl"
+
+#include "../uniforms/samples.glsl"
+#include "../uniforms/stabilization.glsl"
+
+layout(set = 0, binding = 10) uniform WaveformLinesParameters {
+    Pattern pattern;
+    float line_width;
+    float gain;
+    // the sample at the focus, and the samples across the panel
+    float center;
+    float window;
+    vec2 screen_size;
+    // the samples connected by the line
+    uint point_count;
+    bool round_joins;
+};
+
+float get_raw_sample(int sample_index) {
+    if (sample_index < 0) {
+        sample_index += int(period * ceil(float(-sample_index) / period));
+    }
+    if (sample_index >= int(SAMPLE_COUNT)) {
+        sample_index -= int(period * ceil(float(sample_index - int(SAMPLE_COUNT) + 1) / period));
+    }
+    return samples_data[(uint(sample_index) + samples_start) % SAMPLE_COUNT] * gain;
+}
+
+float get_sample(float sample_index) {
+    return mix(
+        get_raw_sample(int(floor(sample_index))),
+        get_raw_sample(int(ceil(sample_index))),
+        fract(sample_index)
+    );
+}
+
+#endif
@@ -8,6 +8,7 @@
 #include "uniforms/image.glsl"
 #include "uniforms/bass.glsl"
 #include "uniforms/aspect_ratio.glsl"
+#include "uniforms/palette.glsl"
 
 layout(set = 0, binding = 10) uniform MaskedPatternParameters {
     Pattern pattern;
@@ -17,6 +18,6 @@ layout(set = 0, binding = 10) uniform MaskedPatternParameters {
 
 void main() {
     vec2 uv = (UV - vec2(0.5, 0.5)) / mix(scale_min, scale_max, bass) + vec2(0.5, 0.5);
-    vec3 col = get_color(pattern, UV, aspect_ratio, chrono);
+    vec3 col = get_palette_color(pattern, UV, aspect_ratio, chrono);
     COLOR = vec4(col, image_color(uv).a);
 }
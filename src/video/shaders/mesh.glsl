@@ -0,0 +1,23 @@
+#version 450
+
+#include "lib/in_out.glsl"
+
+#include "lib/mesh.glsl"
+
+#include "uniforms/aspect_ratio.glsl"
+#include "uniforms/bass.glsl"
+#include "uniforms/palette.glsl"
+
+layout(location = 1) in vec3 NORMAL;
+layout(location = 2) in vec3 WORLD_POSITION;
+
+void main() {
+    // lit on the side facing the camera, for meshes that aren't closed
+    vec3 normal = normalize(NORMAL);
+    if (dot(normal, vec3(0.0, 0.0, camera_distance) - WORLD_POSITION) < 0.0) {
+        normal = -normal;
+    }
+    float diffuse = max(dot(normal, light_direction), 0.0);
+    vec3 col = get_palette_color(pattern, UV, aspect_ratio, chrono);
+    COLOR = vec4(col * mix(ambient, 1.0, diffuse), 1.0);
+}
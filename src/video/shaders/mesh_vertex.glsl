@@ -0,0 +1,69 @@
+#version 450
+
+#include "lib/consts.glsl"
+#include "lib/mesh.glsl"
+
+#include "uniforms/aspect_ratio.glsl"
+#include "uniforms/dft.glsl"
+#include "uniforms/samples.glsl"
+#include "uniforms/stabilization.glsl"
+#include "uniforms/transform.glsl"
+
+struct MeshVertex {
+    vec3 position;
+    float u;
+    vec3 normal;
+    float v;
+};
+
+layout(set = 0, binding = 13) readonly buffer MeshVertices {
+    MeshVertex vertices[];
+};
+
+// three for each triangle
+layout(set = 0, binding = 14) readonly buffer MeshIndices {
+    uint indices[];
+};
+
+layout(location = 0) out vec2 UV;
+layout(location = 1) out vec3 NORMAL;
+layout(location = 2) out vec3 WORLD_POSITION;
+
+const float NEAR = 0.05;
+
+// the level at `t`, from the seam of the texture coordinates at 0 to their middle at 1
+float level(float t) {
+    if (displacement == 1u) {
+        return smooth_magnitude(t * (BIN_COUNT_F - 1.0));
+    }
+    if (displacement == 2u) {
+        float sample_index = clamp(center_sample - t * period, 0.0, SAMPLE_COUNT_F - 1.0);
+        return samples_data[(uint(sample_index) + samples_start) % SAMPLE_COUNT];
+    }
+    return 0.0;
+}
+
+void main() {
+    MeshVertex vertex = vertices[indices[gl_VertexIndex]];
+    UV = vec2(vertex.u, vertex.v);
+
+    // mirrored, so that both sides of the seam move together
+    float t = 1.0 - abs(vertex.u * 2.0 - 1.0);
+    vec3 position = vertex.position + vertex.normal * level(t) * amount;
+    WORLD_POSITION = (model * vec4(position, 1.0)).xyz;
+    NORMAL = mat3(model) * vertex.normal;
+
+    // the depth is 0 at NEAR and 1 at the far side of a mesh fully pushed out
+    vec3 view = WORLD_POSITION - vec3(0.0, 0.0, camera_distance);
+    float depth = -view.z;
+    float far = camera_distance + 1.0 + abs(amount);
+    vec4 clip = vec4(
+        view.x * focal_length / aspect_ratio,
+        -view.y * focal_length,
+        (depth - NEAR) * far / (far - NEAR),
+        depth
+    );
+
+    // the panel's transform is affine, so it applies before the perspective division as well
+    gl_Position = vec4((transform * vec3(clip.xy, clip.w)).xy, clip.zw);
+}
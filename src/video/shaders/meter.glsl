@@ -0,0 +1,41 @@
+#version 450
+
+#include "lib/in_out.glsl"
+
+// positions are along the meter, from 0 at its lowest level to 1 at its highest
+layout(set = 0, binding = 10) uniform MeterParameters {
+    vec3 color;
+    float level;
+    vec3 warning_color;
+    float warning;
+    vec3 danger_color;
+    float danger;
+    float peak;
+    bool horizontal;
+    bool show_peak;
+};
+
+// in fractions of the meter's length
+const float PEAK_THICKNESS = 0.01;
+// of the part of the meter above the level, so that the zones show
+const float UNLIT_ALPHA = 0.15;
+
+vec3 zone_color(float position) {
+    if (position >= danger) {
+        return danger_color;
+    }
+    if (position >= warning) {
+        return warning_color;
+    }
+    return color;
+}
+
+void main() {
+    float position = horizontal ? UV.x : 1.0 - UV.y;
+
+    if (show_peak && abs(position - peak) <= PEAK_THICKNESS * 0.5) {
+        COLOR = vec4(zone_color(peak), 1.0);
+        return;
+    }
+    COLOR = vec4(zone_color(position), position <= level ? 1.0 : UNLIT_ALPHA);
+}
@@ -0,0 +1,22 @@
+#version 450
+
+#include "lib/in_out.glsl"
+
+#include "lib/particles.glsl"
+
+#include "uniforms/aspect_ratio.glsl"
+#include "uniforms/bass.glsl"
+#include "uniforms/palette.glsl"
+
+layout(location = 1) in vec2 CORNER;
+layout(location = 2) flat in float LIFE;
+
+void main() {
+    // particles that flew out of the panel are cut off at its edges
+    if (any(lessThan(UV, vec2(0.0))) || any(greaterThan(UV, vec2(1.0)))) {
+        discard;
+    }
+    float disc = 1.0 - clamp(length(CORNER), 0.0, 1.0);
+    vec3 col = get_palette_color(pattern, UV, aspect_ratio, chrono);
+    COLOR = vec4(col, disc * disc * LIFE);
+}
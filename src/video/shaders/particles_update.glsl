@@ -0,0 +1,65 @@
+#version 450
+
+#include "lib/noise.glsl"
+
+#include "structs/particle.glsl"
+
+// one invocation for each particle
+layout(local_size_x = 64) in;
+
+layout(set = 0, binding = 0) uniform ParticleUpdate {
+    // the particles replaced by new ones, wrapping around the end of the buffer
+    uint emit_start;
+    uint emit_count;
+    float seed;
+    float time_delta;
+    vec2 gravity;
+    vec2 origin;
+    // in radians
+    float direction;
+    float spread;
+    float speed;
+    float lifetime;
+    float curl;
+    float curl_scale;
+    float chrono;
+};
+
+layout(set = 0, binding = 1) buffer Particles {
+    Particle particles[];
+};
+
+// the velocity of the noise's swirls, which is free of divergence so particles don't bunch up
+vec2 swirl(vec2 position) {
+    const float EPSILON = 0.01;
+    vec2 p = position * curl_scale;
+    float dx = fbm(p + vec2(EPSILON, 0.0), chrono) - fbm(p - vec2(EPSILON, 0.0), chrono);
+    float dy = fbm(p + vec2(0.0, EPSILON), chrono) - fbm(p - vec2(0.0, EPSILON), chrono);
+    return vec2(dy, -dx) / (2.0 * EPSILON);
+}
+
+void main() {
+    uint count = particles.length();
+    uint i = gl_GlobalInvocationID.x;
+    if (i >= count) {
+        return;
+    }
+    Particle p = particles[i];
+
+    if ((i + count - emit_start) % count < emit_count) {
+        vec2 r = vec2(float(i) / float(count), seed);
+        float angle = direction + (rand(r) * 2.0 - 1.0) * spread;
+        p.position = origin;
+        p.velocity = vec2(cos(angle), sin(angle)) * speed * mix(0.5, 1.0, rand(r + 0.31));
+        p.lifetime = lifetime * mix(0.5, 1.0, rand(r + 0.67));
+        // spread over the frame, so that a steady stream doesn't come out in clumps
+        p.age = rand(r + 0.83) * time_delta;
+        p.position += p.velocity * p.age;
+    } else if (p.age < p.lifetime) {
+        p.velocity += gravity * time_delta;
+        p.position += (p.velocity + swirl(p.position) * curl) * time_delta;
+        p.age += time_delta;
+    }
+
+    particles[i] = p;
+}
@@ -0,0 +1,39 @@
+#version 450
+
+#include "lib/particles.glsl"
+
+#include "uniforms/aspect_ratio.glsl"
+#include "uniforms/transform.glsl"
+
+layout(set = 0, binding = 13) readonly buffer LiveParticles {
+    Particle live_particles[];
+};
+
+layout(location = 0) out vec2 UV;
+// from -1 to 1 across the sprite
+layout(location = 1) out vec2 CORNER;
+// from 1 when emitted to 0 when dead
+layout(location = 2) flat out float LIFE;
+
+// two triangles for every particle
+const vec2 CORNERS[6] = vec2[](
+    vec2(-1.0, -1.0), vec2(1.0, -1.0), vec2(-1.0, 1.0),
+    vec2(-1.0, 1.0), vec2(1.0, -1.0), vec2(1.0, 1.0)
+);
+
+void main() {
+    Particle p = live_particles[gl_VertexIndex / 6];
+    CORNER = CORNERS[gl_VertexIndex % 6];
+    LIFE = 1.0 - p.age / max(p.lifetime, 1e-6);
+    if (p.age >= p.lifetime) {
+        // every vertex in the same place, so nothing is drawn
+        UV = vec2(0.0);
+        gl_Position = vec4(0.0, 0.0, 0.0, 1.0);
+        return;
+    }
+
+    // the panel's quad goes from -1 to 1 with y going down
+    vec2 position = (p.position + CORNER * size) * vec2(1.0 / aspect_ratio, -1.0);
+    UV = position * 0.5 + 0.5;
+    gl_Position = vec4((transform * vec3(position, 1.0)).xy, 0.0, 1.0);
+}
@@ -0,0 +1,60 @@
+#version 450
+
+#include "lib/in_out.glsl"
+
+#include "lib/consts.glsl"
+#include "lib/polar.glsl"
+
+#include "structs/pattern.glsl"
+
+#include "uniforms/aspect_ratio.glsl"
+#include "uniforms/bass.glsl"
+#include "uniforms/dft.glsl"
+#include "uniforms/palette.glsl"
+
+layout(set = 0, binding = 10) uniform RadialSpectrumParameters {
+    Pattern pattern;
+    float line_width;
+    float gain;
+    float radius;
+    float ray_length;
+    float breathing;
+    uint rays;
+};
+
+float fade(float dist) {
+    float x = clamp(dist, 0, 1);
+    return 1.0 - (x * x);
+}
+
+float segment_distance(vec2 p, vec2 a, vec2 b) {
+    vec2 ab = b - a;
+    float t = clamp(dot(p - a, ab) / max(dot(ab, ab), 1e-12), 0.0, 1.0);
+    return length(p - a - ab * t);
+}
+
+// rays go clockwise from the top, from the lowest frequencies to the highest
+float ray_distance(vec2 position, int ray, float inner) {
+    float rays_f = float(rays);
+    float center = (float(ray) + 0.5) / rays_f;
+    float magnitude = min(smooth_magnitude(center * (BIN_COUNT_F - 1.0)) * gain, 1.0);
+    vec2 dir = direction(center);
+    return segment_distance(position, dir * inner, dir * (inner + magnitude * ray_length));
+}
+
+void main() {
+    vec2 position = centered(UV, aspect_ratio);
+    float inner = radius + bass * breathing;
+
+    // the closest ray and its neighbours, for the lines wider than the space between rays
+    int closest = int(floor(to_polar(UV, aspect_ratio).y * float(rays)));
+    float dist = 100000.0;
+    for (int offset = -1; offset <= 1; offset++) {
+        int ray = (closest + offset + int(rays)) % int(rays);
+        dist = min(dist, ray_distance(position, ray, inner));
+    }
+
+    float val = fade(dist * 2.0 / line_width);
+    vec3 col = get_palette_color(pattern, UV, aspect_ratio, chrono);
+    COLOR = vec4(col, val);
+}
@@ -8,12 +8,13 @@
 
 #include "uniforms/aspect_ratio.glsl"
 #include "uniforms/bass.glsl"
+#include "uniforms/palette.glsl"
 
 layout(set = 0, binding = 10) uniform SimplePatternParameters {
     Pattern pattern;
 };
 
 void main() {
-    vec3 col = get_color(pattern, UV, aspect_ratio, chrono);
+    vec3 col = get_palette_color(pattern, UV, aspect_ratio, chrono);
     COLOR = vec4(col, 1.0);
 }
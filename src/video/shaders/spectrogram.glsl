@@ -9,6 +9,7 @@
 #include "uniforms/aspect_ratio.glsl"
 #include "uniforms/bass.glsl"
 #include "uniforms/dft.glsl"
+#include "uniforms/palette.glsl"
 
 layout(set = 0, binding = 10) uniform SpectrogramParameters {
     Pattern pattern;
@@ -17,6 +18,6 @@ layout(set = 0, binding = 10) uniform SpectrogramParameters {
 
 void main() {
     float val = step(1.0 - UV.y, smooth_magnitude(UV.x * (BIN_COUNT - 1)) * gain);
-    vec3 col = get_color(pattern, UV, aspect_ratio, chrono);
+    vec3 col = get_palette_color(pattern, UV, aspect_ratio, chrono);
     COLOR = vec4(col, val);
 }
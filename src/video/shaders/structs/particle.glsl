@@ -0,0 +1,12 @@
+#ifndef PARTICLE
+#define PARTICLE
+
+// in panel heights with y going up, dead once `age` reaches `lifetime`
+struct Particle {
+    vec2 position;
+    vec2 velocity;
+    float age;
+    float lifetime;
+};
+
+#endif
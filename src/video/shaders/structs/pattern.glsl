@@ -17,9 +17,13 @@ struct Pattern {
     float scroll_speed;
 };
 
+float get_hue(Pattern p, vec2 uv, float aspect_ratio, float chrono) {
+    return fract(fbm3(uv * vec2(aspect_ratio, 1.0) * p.scale, chrono * p.pattern_speed) * p.repeats) + chrono * p.scroll_speed;
+}
+
 vec3 get_color(Pattern p, vec2 uv, float aspect_ratio, float chrono) {
     if (p.use_rainbow) {
-        return lch_srgb(vec3(p.lightness, p.chroma, fract(fbm3(uv * vec2(aspect_ratio, 1.0) * p.scale, chrono * p.pattern_speed) * p.repeats) + chrono * p.scroll_speed));
+        return lch_srgb(vec3(p.lightness, p.chroma, get_hue(p, uv, aspect_ratio, chrono)));
     } else {
         return p.color;
     }
@@ -0,0 +1,69 @@
+#version 450
+
+#include "lib/in_out.glsl"
+
+#include "lib/consts.glsl"
+#include "structs/pattern.glsl"
+
+#include "uniforms/bass.glsl"
+#include "uniforms/aspect_ratio.glsl"
+#include "uniforms/palette.glsl"
+
+layout(set = 0, binding = 10) uniform TextParameters {
+    Pattern pattern;
+    float scale_min;
+    float scale_max;
+    uint glyph_count;
+    uint atlas_width;
+};
+
+struct Glyph {
+    // the corners in UVs of the panel
+    vec4 rect;
+    // the top-left corner and the size in pixels of the atlas
+    vec4 atlas;
+};
+
+layout(set = 0, binding = 13) readonly buffer TextGlyphs {
+    Glyph glyphs[];
+};
+
+// the coverage of the glyphs, bytes packed four to a uint
+layout(set = 0, binding = 14) readonly buffer TextAtlas {
+    uint atlas_pixels[];
+};
+
+float atlas_texel(ivec2 position) {
+    uint index = uint(position.y) * atlas_width + uint(position.x);
+    return float((atlas_pixels[index / 4] >> ((index % 4) * 8)) & 0xffu) / 255.0;
+}
+
+float glyph_coverage(Glyph glyph, vec2 uv) {
+    vec2 local = (uv - glyph.rect.xy) / (glyph.rect.zw - glyph.rect.xy);
+    if (any(lessThan(local, vec2(0.0))) || any(greaterThan(local, vec2(1.0)))) {
+        return 0.0;
+    }
+
+    // bilinear between texel centers, glyphs have an empty texel around them
+    vec2 position = glyph.atlas.xy + local * glyph.atlas.zw - 0.5;
+    ivec2 corner = clamp(
+        ivec2(floor(position)),
+        ivec2(glyph.atlas.xy) - 1,
+        ivec2(glyph.atlas.xy + glyph.atlas.zw) - 1
+    );
+    vec2 t = position - vec2(corner);
+    return mix(
+        mix(atlas_texel(corner), atlas_texel(corner + ivec2(1, 0)), t.x),
+        mix(atlas_texel(corner + ivec2(0, 1)), atlas_texel(corner + ivec2(1, 1)), t.x),
+        t.y
+    );
+}
+
+void main() {
+    vec2 uv = (UV - vec2(0.5, 0.5)) / mix(scale_min, scale_max, bass) + vec2(0.5, 0.5);
+    float coverage = 0.0;
+    for (uint i = 0; i < glyph_count; i++) {
+        coverage = max(coverage, glyph_coverage(glyphs[i], uv));
+    }
+    COLOR = vec4(get_palette_color(pattern, UV, aspect_ratio, chrono), coverage);
+}
@@ -0,0 +1,7 @@
+#ifndef FRAME
+#define FRAME
+
+// an already drawn frame, with premultiplied alpha
+layout(set = 0, binding = 11) uniform sampler2D frame;
+
+#endif
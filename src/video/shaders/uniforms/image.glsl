@@ -1,11 +1,16 @@
 #ifndef IMAGE
 #define IMAGE
 
+// the frames of animated images are the layers
 layout(set = 0, binding = 6) uniform sampler image_sampler;
-layout(set = 0, binding = 7) uniform texture2D image_texture;
+layout(set = 0, binding = 7) uniform texture2DArray image_texture;
+
+layout(set = 0, binding = 9) uniform ImageFrame {
+    uint image_frame;
+};
 
 vec4 image_color(vec2 uv) {
-    return texture(sampler2D(image_texture, image_sampler), uv);
+    return texture(sampler2DArray(image_texture, image_sampler), vec3(uv, float(image_frame)));
 }
 
 #endif
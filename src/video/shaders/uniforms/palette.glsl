@@ -0,0 +1,24 @@
+#ifndef PALETTE
+#define PALETTE
+
+#include "../structs/pattern.glsl"
+
+// transparent when the config has no palette
+layout(set = 0, binding = 15) uniform sampler1D palette;
+
+// `get_color` with the rainbow sweeping through the palette instead of the hues when there is one
+vec3 get_palette_color(Pattern p, vec2 uv, float aspect_ratio, float chrono) {
+    if (p.use_rainbow) {
+        float hue = get_hue(p, uv, aspect_ratio, chrono);
+        // back and forth instead of around, the ends of a palette don't meet like the hues
+        float position = 1.0 - abs(2.0 * fract(hue) - 1.0);
+        float width = float(textureSize(palette, 0));
+        vec4 color = textureLod(palette, (position * (width - 1.0) + 0.5) / width, 0.0);
+        if (color.a > 0.0) {
+            return color.rgb;
+        }
+    }
+    return get_color(p, uv, aspect_ratio, chrono);
+}
+
+#endif
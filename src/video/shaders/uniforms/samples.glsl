@@ -3,7 +3,7 @@
 
 #include "../lib/consts.glsl"
 
-layout(set = 0, binding = 2) buffer Samples {
+layout(set = 0, binding = 2) readonly buffer Samples {
     uint samples_start;
     float samples_data[];
 };
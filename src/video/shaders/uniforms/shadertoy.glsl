@@ -0,0 +1,22 @@
+#ifndef SHADERTOY
+#define SHADERTOY
+
+#include "../lib/consts.glsl"
+
+// 512x2: the spectrum in the first row, the waveform in the second
+layout(set = 0, binding = 8) uniform sampler2D iChannel0;
+
+layout(set = 0, binding = 10) uniform ShadertoyInputs {
+    vec3 iResolution;
+    float iTime;
+    vec4 iMouse;
+    vec4 iDate;
+    vec3 iChannelResolution[4];
+    float iTimeDelta;
+    float iFrameRate;
+    int iFrame;
+};
+
+#define iSampleRate float(SAMPLE_RATE)
+
+#endif
@@ -0,0 +1,19 @@
+#version 450
+
+#include "lib/in_out.glsl"
+
+#include "uniforms/frame.glsl"
+
+layout(set = 0, binding = 10) uniform VignetteParameters {
+    float intensity;
+    // where the darkening starts, 1 at the middle of the edges
+    float radius;
+    float softness;
+};
+
+void main() {
+    float from_center = length(UV * 2.0 - 1.0);
+    vec4 color = texture(frame, UV);
+    color.rgb *= 1.0 - intensity * smoothstep(radius, radius + softness, from_center);
+    COLOR = color;
+}
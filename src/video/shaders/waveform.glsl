@@ -7,6 +7,7 @@
 #include "structs/pattern.glsl"
 
 #include "uniforms/aspect_ratio.glsl"
+#include "uniforms/palette.glsl"
 #include "uniforms/samples.glsl"
 #include "uniforms/stabilization.glsl"
 #include "uniforms/bass.glsl"
@@ -15,6 +16,9 @@ layout(set = 0, binding = 10) uniform WaveformParameters {
     Pattern pattern;
     float line_width;
     float gain;
+    // the sample at the focus, and the samples across the panel
+    float center;
+    float window;
 };
 
 #include "lib/oklab.glsl"
@@ -61,14 +65,16 @@ float point_to_segment(vec2 a, vec2 b, vec2 p) {
 }
 
 vec2 sample_point(float sample_index) {
-    return vec2(sample_index / SAMPLE_COUNT_F * aspect_ratio,
+    return vec2(sample_index / window * aspect_ratio,
         get_sample(sample_index) * 0.5 + 0.5);
 }
 
 float wave_distance(float sample_index, float sample_height) {
-    vec2 target = vec2(sample_index / SAMPLE_COUNT_F * aspect_ratio, sample_height);
-    float start_index = floor(sample_index - line_width);
-    float end_index = ceil(sample_index + line_width);
+    vec2 target = vec2(sample_index / window * aspect_ratio, sample_height);
+    // the samples across the line's width
+    float reach = line_width * window / SAMPLE_COUNT_F;
+    float start_index = floor(sample_index - reach);
+    float end_index = ceil(sample_index + reach);
     float mn = 100000.0;
     vec2 prev = sample_point(start_index);
     for (float index = start_index + 1.0; index <= end_index; index++) {
@@ -80,9 +86,9 @@ float wave_distance(float sample_index, float sample_height) {
 }
 
 void main() {
-    float sample_index = UV.x * SAMPLE_COUNT_F;
-    float dist = wave_distance(sample_index + center_sample - SAMPLE_COUNT_F * focus, 1.0 - UV.y);
+    float sample_index = center + (UV.x - focus) * window;
+    float dist = wave_distance(sample_index, 1.0 - UV.y);
     float val = fade(dist * SAMPLE_COUNT_F / line_width);
-    vec3 col = get_color(pattern, UV, aspect_ratio, chrono);
+    vec3 col = get_palette_color(pattern, UV, aspect_ratio, chrono);
     COLOR = vec4(col, val);
 }
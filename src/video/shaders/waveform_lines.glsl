@@ -0,0 +1,46 @@
+#version 450
+
+#include "lib/in_out.glsl"
+
+#include "lib/consts.glsl"
+#include "lib/waveform_lines.glsl"
+
+#include "uniforms/aspect_ratio.glsl"
+#include "uniforms/bass.glsl"
+#include "uniforms/palette.glsl"
+
+layout(location = 1) in float ACROSS;
+layout(location = 2) in vec2 PIXEL;
+layout(location = 3) flat in float HALF_WIDTH;
+layout(location = 4) flat in uint SEGMENT;
+layout(location = 5) flat in vec2 PREVIOUS_START;
+layout(location = 6) flat in vec2 START;
+layout(location = 7) flat in vec2 END;
+layout(location = 8) flat in vec2 NEXT_END;
+
+float fade(float dist) {
+    float x = clamp(dist, 0, 1);
+    return 1.0 - (x * x);
+}
+
+float point_to_segment(vec2 a, vec2 b, vec2 p) {
+    vec2 ab = b - a;
+    float t = dot(ab, ab) < 1e-12 ? 0.0 : clamp(dot(p - a, ab) / dot(ab, ab), 0.0, 1.0);
+    return length(p - (a + ab * t));
+}
+
+void main() {
+    float dist = abs(ACROSS);
+    if (round_joins) {
+        dist = point_to_segment(START, END, PIXEL);
+        // the overlapping ends of neighboring segments are drawn by only the closest one
+        if (SEGMENT > 0u && point_to_segment(PREVIOUS_START, START, PIXEL) <= dist) {
+            discard;
+        }
+        if (SEGMENT + 2u < point_count && point_to_segment(END, NEXT_END, PIXEL) < dist) {
+            discard;
+        }
+    }
+    vec3 col = get_palette_color(pattern, UV, aspect_ratio, chrono);
+    COLOR = vec4(col, fade(dist / HALF_WIDTH));
+}
@@ -0,0 +1,91 @@
+#version 450
+
+#include "lib/consts.glsl"
+#include "lib/waveform_lines.glsl"
+
+#include "uniforms/transform.glsl"
+
+layout(location = 0) out vec2 UV;
+// the signed pixels from the middle of the line, for miter joins
+layout(location = 1) out float ACROSS;
+layout(location = 2) out vec2 PIXEL;
+layout(location = 3) flat out float HALF_WIDTH;
+// the segment this vertex belongs to and its neighbors in pixels, for round joins
+layout(location = 4) flat out uint SEGMENT;
+layout(location = 5) flat out vec2 PREVIOUS_START;
+layout(location = 6) flat out vec2 START;
+layout(location = 7) flat out vec2 END;
+layout(location = 8) flat out vec2 NEXT_END;
+
+const float MITER_LIMIT = 4.0;
+
+vec2 to_pixels(vec2 uv) {
+    return ((transform * vec3(uv * 2.0 - 1.0, 1.0)).xy * 0.5 + 0.5) * screen_size;
+}
+
+vec2 to_uv(vec2 pixels) {
+    return ((inverse(transform) * vec3(pixels / screen_size * 2.0 - 1.0, 1.0)).xy + 1.0) * 0.5;
+}
+
+vec2 point(int i) {
+    i = clamp(i, 0, int(point_count) - 1);
+    float x = float(i) / float(point_count - 1);
+    float sample_index = center + (x - focus) * window;
+    return to_pixels(vec2(x, 0.5 - get_sample(sample_index) * 0.5));
+}
+
+vec2 direction(vec2 from, vec2 to, vec2 fallback) {
+    vec2 d = to - from;
+    return dot(d, d) > 1e-12 ? normalize(d) : fallback;
+}
+
+vec2 perpendicular(vec2 v) {
+    return vec2(-v.y, v.x);
+}
+
+void emit(vec2 pixel) {
+    PIXEL = pixel;
+    UV = to_uv(pixel);
+    gl_Position = vec4(pixel / screen_size * 2.0 - 1.0, 0.0, 1.0);
+}
+
+void main() {
+    float panel_height = length(transform[1].xy * screen_size);
+    HALF_WIDTH = max(line_width / SAMPLE_COUNT_F * panel_height, 1.0);
+
+    if (!round_joins) {
+        // a triangle strip with two vertices at every point
+        int i = gl_VertexIndex / 2;
+        float side = float(gl_VertexIndex % 2) * 2.0 - 1.0;
+        vec2 current = point(i);
+        vec2 incoming = direction(point(i - 1), current, vec2(1.0, 0.0));
+        vec2 outgoing = direction(current, point(i + 1), incoming);
+        if (i == 0) {
+            incoming = outgoing;
+        }
+        // the bisector of the join, across the line if it turns back on itself
+        vec2 along = direction(vec2(0.0), incoming + outgoing, perpendicular(incoming));
+        vec2 normal = perpendicular(along);
+        float miter = 1.0 / max(dot(normal, perpendicular(incoming)), 1.0 / MITER_LIMIT);
+        ACROSS = side * HALF_WIDTH;
+        emit(current + normal * side * HALF_WIDTH * miter);
+        return;
+    }
+
+    // a triangle list with a quad around every segment, reaching past its ends by the line's width
+    int segment = gl_VertexIndex / 6;
+    const int CORNERS[6] = int[](0, 1, 2, 2, 1, 3);
+    int corner = CORNERS[gl_VertexIndex % 6];
+    SEGMENT = uint(segment);
+    PREVIOUS_START = point(segment - 1);
+    START = point(segment);
+    END = point(segment + 1);
+    NEXT_END = point(segment + 2);
+    ACROSS = 0.0;
+
+    vec2 along = direction(START, END, vec2(1.0, 0.0));
+    vec2 normal = perpendicular(along);
+    vec2 end = corner < 2 ? START - along * HALF_WIDTH : END + along * HALF_WIDTH;
+    float side = float(corner % 2) * 2.0 - 1.0;
+    emit(end + normal * side * HALF_WIDTH);
+}
@@ -0,0 +1,97 @@
+use crate::video::{
+    AudioTexture, PanelTransform,
+    frame_inputs::FrameInputs,
+    shader_types::{ShadertoyParameters, ShadertoyTime},
+};
+
+use glam::{Vec2, vec2};
+use vulkano::buffer::BufferContents;
+
+/// The `ShadertoyInputs` uniform block of `uniforms/shadertoy.glsl`, in std140 layout.
+#[derive(BufferContents, Clone, Copy)]
+#[repr(C)]
+pub struct ShadertoyInputs {
+    pub resolution: [f32; 3],
+    pub time: f32,
+    pub mouse: [f32; 4],
+    pub date: [f32; 4],
+    pub channel_resolution: [[f32; 4]; 4],
+    pub time_delta: f32,
+    pub frame_rate: f32,
+    pub frame: i32,
+    _padding: f32,
+}
+
+const PREFIX: &str = "#version 450
+
+#include <lib/in_out.glsl>
+#include <uniforms/shadertoy.glsl>
+
+#line 1
+";
+
+const SUFFIX: &str = "
+
+void main() {
+    vec4 color = vec4(0.0, 0.0, 0.0, 1.0);
+    mainImage(color, vec2(UV.x, 1.0 - UV.y) * iResolution.xy);
+    COLOR = vec4(color.rgb, 1.0);
+}
+";
+
+/// Turns a Shadertoy image shader, which only defines `mainImage`, into a complete fragment
+/// shader. Line numbers in errors still match the original source.
+pub fn wrap(source: &str) -> String {
+    format!("{PREFIX}{source}{SUFFIX}")
+}
+
+impl ShadertoyInputs {
+    /// The inputs of a panel drawn with `transform` onto a target of `screen_size` pixels.
+    ///
+    /// Like on Shadertoy, `fragCoord` and `iMouse` are in pixels of the panel from its lower-left
+    /// corner, and the signs of `iMouse.zw` tell whether the pointer is held and was just pressed.
+    pub fn new(
+        parameters: &ShadertoyParameters,
+        transform: &PanelTransform,
+        screen_size: Vec2,
+        frame_inputs: &FrameInputs,
+    ) -> Self {
+        let resolution = transform.get_size(screen_size);
+
+        let inverse = transform.get_matrix(screen_size).inverse();
+        let to_panel = |position: Vec2| {
+            let local = inverse.transform_point2(position / screen_size * 2.0 - 1.0);
+            let uv = (local + 1.0) / 2.0;
+            vec2(uv.x, 1.0 - uv.y) * resolution
+        };
+
+        let mouse = &frame_inputs.mouse;
+        let position = to_panel(mouse.position);
+        let click = to_panel(mouse.click);
+        let click = vec2(
+            if mouse.pressed { click.x } else { -click.x },
+            if mouse.clicked { click.y } else { -click.y },
+        );
+
+        let audio_resolution = [AudioTexture::WIDTH as f32, 2.0, 1.0, 0.0];
+
+        Self {
+            resolution: [resolution.x, resolution.y, 1.0],
+            time: match parameters.time {
+                ShadertoyTime::Chrono => frame_inputs.chrono,
+                ShadertoyTime::Wall => frame_inputs.time,
+            },
+            mouse: [position.x, position.y, click.x, click.y],
+            date: FrameInputs::date(),
+            channel_resolution: [audio_resolution, [0.0; 4], [0.0; 4], [0.0; 4]],
+            time_delta: frame_inputs.time_delta,
+            frame_rate: if frame_inputs.time_delta > 0.0 {
+                1.0 / frame_inputs.time_delta
+            } else {
+                0.0
+            },
+            frame: frame_inputs.frame as i32,
+            _padding: 0.0,
+        }
+    }
+}
@@ -0,0 +1,408 @@
+use crate::video::{
+    shader_types::{MeshDisplacement, MeshParameters, MeshShape},
+    shaders::{self, MeshVertex},
+};
+
+use glam::{Mat4, Vec2, Vec3, vec2, vec3};
+use std::{
+    f32::consts::{PI, TAU},
+    fs,
+    path::Path,
+    sync::Arc,
+};
+use vulkano::{
+    buffer::{Buffer, BufferCreateInfo, BufferUsage, Subbuffer},
+    descriptor_set::WriteDescriptorSet,
+    memory::allocator::{AllocationCreateInfo, MemoryTypeFilter, StandardMemoryAllocator},
+    padded::Padded,
+};
+
+/// The triangles of a mesh panel on the GPU, and how far it has turned.
+pub struct MeshState {
+    vertices: Subbuffer<[MeshVertex]>,
+    indices: Subbuffer<[u32]>,
+    /// In radians around the vertical.
+    angle: f32,
+}
+
+impl MeshState {
+    pub fn new(
+        parameters: &MeshParameters,
+        memory_allocator: &Arc<StandardMemoryAllocator>,
+    ) -> Result<Self, String> {
+        let (vertices, indices) = match parameters.shape {
+            MeshShape::Sphere => sphere(parameters.detail),
+            MeshShape::Plane => plane(parameters.detail),
+            MeshShape::Torus => torus(parameters.detail),
+            MeshShape::Obj => load_obj(&parameters.path)?,
+        };
+        if indices.is_empty() {
+            return Err(format!("{} has no faces", parameters.path.display()));
+        }
+
+        let buffer_info = BufferCreateInfo {
+            usage: BufferUsage::STORAGE_BUFFER,
+            ..Default::default()
+        };
+        let allocation_info = AllocationCreateInfo {
+            memory_type_filter: MemoryTypeFilter::PREFER_DEVICE
+                | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+            ..Default::default()
+        };
+        let vertices = Buffer::from_iter(
+            memory_allocator.clone(),
+            buffer_info.clone(),
+            allocation_info.clone(),
+            vertices,
+        )
+        .map_err(|e| format!("uploading the mesh: {e}"))?;
+        let indices = Buffer::from_iter(
+            memory_allocator.clone(),
+            buffer_info,
+            allocation_info,
+            indices,
+        )
+        .map_err(|e| format!("uploading the mesh: {e}"))?;
+
+        Ok(Self {
+            vertices,
+            indices,
+            angle: 0.0,
+        })
+    }
+
+    pub fn update(&mut self, parameters: &MeshParameters, time_delta: f32) {
+        self.angle = (self.angle + parameters.spin.to_radians() * time_delta) % TAU;
+    }
+
+    pub fn parameters(&self, parameters: &MeshParameters) -> shaders::MeshParameters {
+        let model =
+            Mat4::from_rotation_x(parameters.tilt.to_radians()) * Mat4::from_rotation_y(self.angle);
+        shaders::MeshParameters {
+            model: model.to_cols_array_2d(),
+            pattern: Padded(parameters.pattern.clone().into()),
+            light_direction: parameters.light_direction.normalize_or_zero().to_array(),
+            ambient: parameters.ambient,
+            focal_length: 1.0 / (parameters.field_of_view.to_radians() / 2.0).tan(),
+            camera_distance: parameters.camera_distance,
+            amount: parameters.amount,
+            displacement: match parameters.displacement {
+                MeshDisplacement::None => 0,
+                MeshDisplacement::Dft => 1,
+                MeshDisplacement::Waveform => 2,
+            },
+        }
+    }
+
+    pub fn vertex_count(&self) -> u32 {
+        self.indices.len() as u32
+    }
+
+    /// The writes of the vertices and their indices, three for each triangle.
+    pub fn writes(&self) -> Vec<WriteDescriptorSet> {
+        vec![
+            WriteDescriptorSet::buffer(13, self.vertices.clone()),
+            WriteDescriptorSet::buffer(14, self.indices.clone()),
+        ]
+    }
+}
+
+fn vertex(position: Vec3, normal: Vec3, uv: Vec2) -> MeshVertex {
+    MeshVertex {
+        position: position.to_array(),
+        u: uv.x,
+        normal: normal.to_array(),
+        v: uv.y,
+    }
+}
+
+/// A grid of `columns` by `rows` quads with the vertices `point` places at each of its UVs, as
+/// a position and a normal.
+fn grid(
+    columns: u32,
+    rows: u32,
+    point: impl Fn(Vec2) -> (Vec3, Vec3),
+) -> (Vec<MeshVertex>, Vec<u32>) {
+    let mut vertices = Vec::new();
+    for row in 0..=rows {
+        for column in 0..=columns {
+            let uv = vec2(column as f32 / columns as f32, row as f32 / rows as f32);
+            let (position, normal) = point(uv);
+            vertices.push(vertex(position, normal, uv));
+        }
+    }
+
+    let mut indices = Vec::new();
+    for row in 0..rows {
+        for column in 0..columns {
+            let corner = row * (columns + 1) + column;
+            let below = corner + columns + 1;
+            indices.extend([corner, below, corner + 1, corner + 1, below, below + 1]);
+        }
+    }
+    (vertices, indices)
+}
+
+fn sphere(detail: u32) -> (Vec<MeshVertex>, Vec<u32>) {
+    grid(detail.max(3), (detail / 2).max(2), |uv| {
+        let longitude = uv.x * TAU;
+        let latitude = uv.y * PI;
+        let position = vec3(
+            latitude.sin() * longitude.cos(),
+            latitude.cos(),
+            latitude.sin() * longitude.sin(),
+        );
+        (position, position)
+    })
+}
+
+fn plane(detail: u32) -> (Vec<MeshVertex>, Vec<u32>) {
+    let detail = detail.max(1);
+    grid(detail, detail, |uv| {
+        let position = vec3(uv.x * 2.0 - 1.0, 0.0, uv.y * 2.0 - 1.0) / 2f32.sqrt();
+        (position, Vec3::Y)
+    })
+}
+
+fn torus(detail: u32) -> (Vec<MeshVertex>, Vec<u32>) {
+    const TUBE_RADIUS: f32 = 0.3;
+    grid(detail.max(3), (detail / 2).max(3), |uv| {
+        let (around, across) = (uv.x * TAU, uv.y * TAU);
+        let normal = vec3(
+            across.cos() * around.cos(),
+            across.sin(),
+            across.cos() * around.sin(),
+        );
+        let center = vec3(around.cos(), 0.0, around.sin()) * (1.0 - TUBE_RADIUS);
+        (center + normal * TUBE_RADIUS, normal)
+    })
+}
+
+/// Loads the faces of an OBJ file, centered and scaled to a radius of 1. Faces without normals
+/// are flat, and vertices without texture coordinates get them from their direction.
+fn load_obj(path: &Path) -> Result<(Vec<MeshVertex>, Vec<u32>), String> {
+    let source =
+        fs::read_to_string(path).map_err(|e| format!("reading {}: {e}", path.display()))?;
+    parse_obj(&source, path)
+}
+
+/// Parses the source of an OBJ file, `path` only names it in the errors.
+fn parse_obj(source: &str, path: &Path) -> Result<(Vec<MeshVertex>, Vec<u32>), String> {
+    let mut positions = Vec::new();
+    let mut uvs = Vec::new();
+    let mut normals = Vec::new();
+    let mut corners: Vec<(Vec3, Option<Vec2>, Vec3)> = Vec::new();
+    for (line_index, line) in source.lines().enumerate() {
+        let error = |message: &str| format!("{}:{}: {message}", path.display(), line_index + 1);
+        let mut words = line.split_whitespace();
+        let Some(keyword) = words.next() else {
+            continue;
+        };
+        let numbers = |words: std::str::SplitWhitespace| {
+            words
+                .map(|word| word.parse::<f32>())
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| error(&e.to_string()))
+        };
+        match keyword {
+            "v" => match numbers(words)?[..] {
+                [x, y, z, ..] => positions.push(vec3(x, y, z)),
+                _ => return Err(error("a position needs 3 coordinates")),
+            },
+            "vt" => match numbers(words)?[..] {
+                [u] => uvs.push(vec2(u, 0.0)),
+                [u, v, ..] => uvs.push(vec2(u, v)),
+                _ => return Err(error("a texture coordinate needs a value")),
+            },
+            "vn" => match numbers(words)?[..] {
+                [x, y, z] => normals.push(vec3(x, y, z).normalize_or_zero()),
+                _ => return Err(error("a normal needs 3 coordinates")),
+            },
+            "f" => {
+                let face = words
+                    .map(|word| {
+                        let mut indices = word.split('/');
+                        let mut index = |count: usize| -> Result<Option<usize>, String> {
+                            let Some(index) = indices.next().filter(|index| !index.is_empty())
+                            else {
+                                return Ok(None);
+                            };
+                            let index = index.parse::<i64>().map_err(|e| error(&e.to_string()))?;
+                            // indices start at 1, negative ones count back from the latest
+                            let resolved = if index < 0 {
+                                count as i64 + index
+                            } else {
+                                index - 1
+                            };
+                            if (0..count as i64).contains(&resolved) {
+                                Ok(Some(resolved as usize))
+                            } else {
+                                Err(error(&format!("no element {index}")))
+                            }
+                        };
+                        let position = index(positions.len())?
+                            .ok_or_else(|| error("a face corner needs a position"))?;
+                        let uv = index(uvs.len())?;
+                        let normal = index(normals.len())?;
+                        Ok((
+                            positions[position],
+                            uv.map(|i| uvs[i]),
+                            normal.map(|i| normals[i]),
+                        ))
+                    })
+                    .collect::<Result<Vec<_>, String>>()?;
+                if face.len() < 3 {
+                    return Err(error("a face needs 3 corners"));
+                }
+                for i in 1..face.len() - 1 {
+                    let triangle = [face[0], face[i], face[i + 1]];
+                    let flat = (triangle[1].0 - triangle[0].0)
+                        .cross(triangle[2].0 - triangle[0].0)
+                        .normalize_or_zero();
+                    corners.extend(
+                        triangle
+                            .map(|(position, uv, normal)| (position, uv, normal.unwrap_or(flat))),
+                    );
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let (min, max) = corners.iter().fold(
+        (Vec3::splat(f32::INFINITY), Vec3::splat(f32::NEG_INFINITY)),
+        |(min, max), (position, ..)| (min.min(*position), max.max(*position)),
+    );
+    let center = (min + max) / 2.0;
+    let radius = corners
+        .iter()
+        .map(|(position, ..)| position.distance(center))
+        .fold(0.0, f32::max)
+        .max(f32::EPSILON);
+
+    let vertices: Vec<_> = corners
+        .into_iter()
+        .map(|(position, uv, normal)| {
+            let position = (position - center) / radius;
+            let uv = uv.unwrap_or_else(|| {
+                vec2(
+                    position.z.atan2(position.x) / TAU + 0.5,
+                    0.5 - position.y / 2.0,
+                )
+            });
+            vertex(position, normal, uv)
+        })
+        .collect();
+    let indices = (0..vertices.len() as u32).collect();
+    Ok((vertices, indices))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(source: &str) -> Result<(Vec<MeshVertex>, Vec<u32>), String> {
+        parse_obj(source, Path::new("test.obj"))
+    }
+
+    fn positions(vertices: &[MeshVertex]) -> Vec<Vec3> {
+        vertices
+            .iter()
+            .map(|vertex| Vec3::from_array(vertex.position))
+            .collect()
+    }
+
+    const SQUARE: &str = "
+        v -1 -1 0
+        v 1 -1 0
+        v 1 1 0
+        v -1 1 0
+    ";
+
+    #[test]
+    fn splits_a_quad_into_two_triangles() {
+        let (vertices, indices) = parse(&format!("{SQUARE}\nf 1 2 3 4")).unwrap();
+        assert_eq!(indices, [0, 1, 2, 3, 4, 5]);
+        let corners = [
+            vec3(-1.0, -1.0, 0.0),
+            vec3(1.0, -1.0, 0.0),
+            vec3(1.0, 1.0, 0.0),
+        ];
+        let other = [corners[0], corners[2], vec3(-1.0, 1.0, 0.0)];
+        // scaled to a radius of 1
+        let scaled: Vec<_> = corners
+            .iter()
+            .chain(&other)
+            .map(|corner| *corner / 2f32.sqrt())
+            .collect();
+        assert!(
+            positions(&vertices)
+                .iter()
+                .zip(&scaled)
+                .all(|(position, expected)| position.abs_diff_eq(*expected, 1e-6))
+        );
+    }
+
+    #[test]
+    fn counts_negative_indices_back_from_the_latest() {
+        let (negative, _) = parse(&format!("{SQUARE}\nf -4 -3 -2")).unwrap();
+        let (positive, _) = parse(&format!("{SQUARE}\nf 1 2 3")).unwrap();
+        assert_eq!(positions(&negative), positions(&positive));
+    }
+
+    #[test]
+    fn fills_in_missing_normals_and_texture_coordinates() {
+        let source = format!(
+            "{SQUARE}
+            vt 0.25 0.75
+            vn 0 0 -2
+            f 1/1 2//1 3"
+        );
+        let (vertices, _) = parse(&source).unwrap();
+        // the given normal is normalized, the others are the flat one of the face
+        assert_eq!(vertices[0].normal, [0.0, 0.0, 1.0]);
+        assert_eq!(vertices[1].normal, [0.0, 0.0, -1.0]);
+        assert_eq!(vertices[2].normal, [0.0, 0.0, 1.0]);
+        assert_eq!([vertices[0].u, vertices[0].v], [0.25, 0.75]);
+        // from the direction of (1, 1, 0) / sqrt(2)
+        assert!((vertices[2].u - 0.5).abs() < 1e-6);
+        assert!((vertices[2].v - (0.5 - 0.5f32.sqrt() / 2.0)).abs() < 1e-6);
+    }
+
+    #[test]
+    fn rejects_faces_out_of_range() {
+        for (face, message) in [
+            ("f 1 2 5", "test.obj:7: no element 5"),
+            ("f 0 1 2", "test.obj:7: no element 0"),
+            ("f -5 1 2", "test.obj:7: no element -5"),
+            ("f 1/1 2 3", "test.obj:7: no element 1"),
+            ("f 1 2//2 3", "test.obj:7: no element 2"),
+            ("f 1 2", "test.obj:7: a face needs 3 corners"),
+        ] {
+            // the generated vertices aren't Debug, so no unwrap_err
+            assert_eq!(
+                parse(&format!("{SQUARE}\n{face}")).err().as_deref(),
+                Some(message)
+            );
+        }
+    }
+
+    #[test]
+    fn shapes_have_a_quad_per_grid_cell() {
+        for ((vertices, indices), vertex_count, index_count) in [
+            // 8 by 4 quads
+            (sphere(8), 9 * 5, 8 * 4 * 6),
+            // at least 3 by 2 quads
+            (sphere(1), 4 * 3, 3 * 2 * 6),
+            (plane(4), 5 * 5, 4 * 4 * 6),
+            (plane(0), 2 * 2, 6),
+            // 6 by 3 quads
+            (torus(6), 7 * 4, 6 * 3 * 6),
+            (torus(10), 11 * 6, 10 * 5 * 6),
+        ] {
+            assert_eq!(vertices.len(), vertex_count);
+            assert_eq!(indices.len(), index_count);
+            assert!(indices.iter().all(|&index| index < vertex_count as u32));
+        }
+    }
+}
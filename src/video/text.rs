@@ -0,0 +1,403 @@
+use crate::{
+    audio::AudioData,
+    video::{
+        FrameInputs,
+        shader_types::{TextAlign, TextParameters},
+        shaders,
+    },
+};
+
+use ab_glyph::{Font, FontVec, GlyphId, PxScale, ScaleFont, point};
+use glam::{Vec2, vec2};
+use std::collections::HashMap;
+use vulkano::{
+    buffer::allocator::SubbufferAllocator, descriptor_set::WriteDescriptorSet, padded::Padded,
+};
+
+/// The size in pixels of a line the glyphs are rasterized at.
+const RASTER_SIZE: f32 = 48.0;
+pub const ATLAS_WIDTH: u32 = 512;
+/// Between glyphs in the atlas, so that filtering doesn't bleed into the neighbours.
+const PADDING: u32 = 1;
+
+#[derive(Clone, Copy)]
+struct AtlasGlyph {
+    /// Where the glyph is in the atlas, in pixels.
+    position: [u32; 2],
+    size: [u32; 2],
+    /// The top-left corner of the glyph from the pen position on the baseline.
+    offset: Vec2,
+}
+
+/// The glyphs of a font rasterized as they are needed into one coverage image, read by
+/// `text.glsl` as bytes packed in a storage buffer.
+pub struct GlyphAtlas {
+    font: FontVec,
+    pixels: Vec<u8>,
+    /// `None` for glyphs without an outline, like spaces.
+    glyphs: HashMap<GlyphId, Option<AtlasGlyph>>,
+    /// The free space left in the current row, as its top-left corner and height.
+    cursor: [u32; 2],
+    row_height: u32,
+}
+
+/// A glyph placed by `GlyphAtlas::layout`.
+pub struct PlacedGlyph {
+    /// The top-left corner in pixels of the atlas size, from the top-left corner of the text.
+    pub position: Vec2,
+    pub size: Vec2,
+    pub atlas_position: Vec2,
+}
+
+pub struct TextLayout {
+    pub glyphs: Vec<PlacedGlyph>,
+    /// The size of the text in pixels of the atlas size.
+    pub size: Vec2,
+    /// The height of a line of the font, before the spacing.
+    pub line_height: f32,
+}
+
+impl GlyphAtlas {
+    pub fn new(font_data: Vec<u8>) -> Result<Self, String> {
+        let font = FontVec::try_from_vec(font_data).map_err(|e| e.to_string())?;
+        Ok(Self {
+            font,
+            pixels: Vec::new(),
+            glyphs: HashMap::new(),
+            cursor: [PADDING, PADDING],
+            row_height: 0,
+        })
+    }
+
+    /// The atlas as bytes packed four to a `uint`, `ATLAS_WIDTH` pixels wide.
+    pub fn packed_pixels(&self) -> impl ExactSizeIterator<Item = u32> + '_ {
+        self.pixels.chunks(4).map(|chunk| {
+            chunk
+                .iter()
+                .enumerate()
+                .fold(0, |word, (i, byte)| word | ((*byte as u32) << (i * 8)))
+        })
+    }
+
+    /// Finds the glyph in the atlas, rasterizing it on first use.
+    fn glyph(&mut self, id: GlyphId) -> Option<AtlasGlyph> {
+        if let Some(glyph) = self.glyphs.get(&id) {
+            return *glyph;
+        }
+
+        let glyph = self
+            .font
+            .outline_glyph(id.with_scale_and_position(self.scale(), point(0.0, 0.0)))
+            .map(|outline| {
+                let bounds = outline.px_bounds();
+                let size = [bounds.width() as u32, bounds.height() as u32];
+                // shelf packing, rows as high as their highest glyph
+                if self.cursor[0] + size[0] + PADDING > ATLAS_WIDTH {
+                    self.cursor = [PADDING, self.cursor[1] + self.row_height + PADDING];
+                    self.row_height = 0;
+                }
+                let position = self.cursor;
+                self.cursor[0] += size[0] + PADDING;
+                self.row_height = self.row_height.max(size[1]);
+
+                let height = (position[1] + self.row_height + PADDING) as usize;
+                self.pixels.resize(height * ATLAS_WIDTH as usize, 0);
+                outline.draw(|x, y, coverage| {
+                    let index = (position[1] + y) * ATLAS_WIDTH + position[0] + x;
+                    self.pixels[index as usize] = (coverage.clamp(0.0, 1.0) * 255.0) as u8;
+                });
+
+                AtlasGlyph {
+                    position,
+                    size,
+                    offset: vec2(bounds.min.x, bounds.min.y),
+                }
+            });
+        self.glyphs.insert(id, glyph);
+        glyph
+    }
+
+    fn scale(&self) -> PxScale {
+        // `PxScale` is the height of the ascent to the descent, lines also have a gap
+        PxScale::from(RASTER_SIZE)
+    }
+
+    /// Places the glyphs of `text`, with lines `line_spacing` times the font's line height
+    /// apart.
+    pub fn layout(&mut self, text: &str, align: TextAlign, line_spacing: f32) -> TextLayout {
+        let scaled = self.font.as_scaled(self.scale());
+        let ascent = scaled.ascent();
+        let font_line_height = scaled.height() + scaled.line_gap();
+        let line_height = font_line_height * line_spacing;
+
+        // the glyphs of each line with their pen position, and the line's width
+        let mut lines = Vec::new();
+        for line in text.lines() {
+            let mut pen = 0.0;
+            let mut previous = None;
+            let mut glyphs = Vec::new();
+            for c in line.chars() {
+                let id = scaled.glyph_id(c);
+                if let Some(previous) = previous {
+                    pen += scaled.kern(previous, id);
+                }
+                let advance = scaled.h_advance(id);
+                glyphs.push((id, pen));
+                pen += advance;
+                previous = Some(id);
+            }
+            lines.push((glyphs, pen));
+        }
+
+        let width = lines.iter().map(|(_, width)| *width).fold(0.0, f32::max);
+        let mut placed = Vec::new();
+        for (i, (glyphs, line_width)) in lines.into_iter().enumerate() {
+            let start = match align {
+                TextAlign::Left => 0.0,
+                TextAlign::Center => (width - line_width) / 2.0,
+                TextAlign::Right => width - line_width,
+            };
+            let baseline = i as f32 * line_height + ascent;
+            for (id, pen) in glyphs {
+                if let Some(glyph) = self.glyph(id) {
+                    placed.push(PlacedGlyph {
+                        position: vec2(start + pen, baseline) + glyph.offset,
+                        size: vec2(glyph.size[0] as f32, glyph.size[1] as f32),
+                        atlas_position: vec2(glyph.position[0] as f32, glyph.position[1] as f32),
+                    });
+                }
+            }
+        }
+
+        TextLayout {
+            glyphs: placed,
+            size: vec2(width, text.lines().count() as f32 * line_height),
+            line_height: font_line_height,
+        }
+    }
+}
+
+impl TextLayout {
+    /// The glyphs as read by `text.glsl`, in UVs of a panel with `aspect_ratio`, with lines
+    /// `size` panel heights high.
+    pub fn glyphs(&self, align: TextAlign, size: f32, aspect_ratio: f32) -> Vec<shaders::Glyph> {
+        let unit = size / self.line_height;
+        let block = self.size * unit;
+        let start = vec2(
+            match align {
+                TextAlign::Left => 0.0,
+                TextAlign::Center => (aspect_ratio - block.x) / 2.0,
+                TextAlign::Right => aspect_ratio - block.x,
+            },
+            (1.0 - block.y) / 2.0,
+        );
+        let to_uv = |position: Vec2| (start + position * unit) / vec2(aspect_ratio, 1.0);
+
+        self.glyphs
+            .iter()
+            .map(|glyph| {
+                let min = to_uv(glyph.position);
+                let max = to_uv(glyph.position + glyph.size);
+                shaders::Glyph {
+                    rect: [min.x, min.y, max.x, max.y],
+                    atlas: [
+                        glyph.atlas_position.x,
+                        glyph.atlas_position.y,
+                        glyph.size.x,
+                        glyph.size.y,
+                    ],
+                }
+            })
+            .collect()
+    }
+}
+
+/// Replaces the `{name}` slots of `text`: `{bpm}` with the tempo of the detected beats, `{fps}`
+/// with the frame rate and the others with the strings set in `FrameInputs::text`. Unknown
+/// slots are kept as they are.
+pub fn fill_slots(
+    text: &str,
+    frame_inputs: &FrameInputs,
+    audio_data: &AudioData,
+    fps: f32,
+) -> String {
+    let mut filled = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('{') {
+        let Some(length) = rest[start..].find('}') else {
+            break;
+        };
+        filled.push_str(&rest[..start]);
+        let name = &rest[start + 1..start + length];
+        match name {
+            "bpm" if audio_data.beats.interval > 0.0 => {
+                filled.push_str(&format!("{:.0}", 60.0 / audio_data.beats.interval))
+            }
+            "bpm" => filled.push_str("--"),
+            "fps" => filled.push_str(&format!("{fps:.0}")),
+            _ => match frame_inputs.text.get(name) {
+                Some(value) => filled.push_str(value),
+                None => filled.push_str(&rest[start..=start + length]),
+            },
+        }
+        rest = &rest[start + length + 1..];
+    }
+    filled.push_str(rest);
+    filled
+}
+
+/// What a text panel keeps between frames.
+pub struct TextState {
+    atlas: GlyphAtlas,
+    /// Smoothed, for `{fps}` to be readable.
+    fps: f32,
+    glyphs: Vec<shaders::Glyph>,
+}
+
+impl TextState {
+    pub fn new(atlas: GlyphAtlas) -> Self {
+        Self {
+            atlas,
+            fps: 0.0,
+            glyphs: Vec::new(),
+        }
+    }
+
+    /// Lays the text out again for a frame, with the current values in its slots.
+    pub fn update(
+        &mut self,
+        parameters: &TextParameters,
+        frame_inputs: &FrameInputs,
+        audio_data: &AudioData,
+        aspect_ratio: f32,
+    ) {
+        if frame_inputs.time_delta > 0.0 {
+            self.fps += (1.0 / frame_inputs.time_delta - self.fps) * 0.05;
+        }
+        let text = fill_slots(&parameters.text, frame_inputs, audio_data, self.fps);
+        self.glyphs = self
+            .atlas
+            .layout(&text, parameters.align, parameters.line_spacing)
+            .glyphs(parameters.align, parameters.size, aspect_ratio);
+    }
+
+    pub fn parameters(&self, parameters: &TextParameters) -> shaders::TextParameters {
+        shaders::TextParameters {
+            pattern: Padded(parameters.pattern.clone().into()),
+            scale_min: parameters.scale_min,
+            scale_max: parameters.scale_max,
+            glyph_count: self.glyphs.len() as u32,
+            atlas_width: ATLAS_WIDTH,
+        }
+    }
+
+    /// The writes of the glyphs and the atlas. Both are uploaded every frame, they are small
+    /// next to the samples.
+    pub fn writes(&self, storage_buffer_allocator: &SubbufferAllocator) -> Vec<WriteDescriptorSet> {
+        // empty buffers can't be bound
+        let glyphs = storage_buffer_allocator
+            .allocate_slice::<shaders::Glyph>(self.glyphs.len().max(1) as u64)
+            .unwrap();
+        glyphs.write().unwrap()[..self.glyphs.len()].copy_from_slice(&self.glyphs);
+
+        let pixels = self.atlas.packed_pixels();
+        let atlas = storage_buffer_allocator
+            .allocate_slice::<u32>(pixels.len().max(1) as u64)
+            .unwrap();
+        for (word, pixels) in atlas.write().unwrap().iter_mut().zip(pixels) {
+            *word = pixels;
+        }
+
+        vec![
+            WriteDescriptorSet::buffer(13, glyphs),
+            WriteDescriptorSet::buffer(14, atlas),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::assert_near;
+
+    fn atlas() -> GlyphAtlas {
+        // a monospace font, every glyph advances as much and none are kerned
+        GlyphAtlas::new(include_bytes!("../../examples/fonts/DejaVuSansMono.ttf").to_vec()).unwrap()
+    }
+
+    fn fill(text: &str, frame_inputs: &FrameInputs, beat_interval: f32) -> String {
+        let audio_data = AudioData::silence(1024, 16, 48000).with_beat_interval(beat_interval);
+        fill_slots(text, frame_inputs, &audio_data, 59.6)
+    }
+
+    fn positions(layout: &TextLayout) -> Vec<Vec2> {
+        layout.glyphs.iter().map(|glyph| glyph.position).collect()
+    }
+
+    #[test]
+    fn fills_the_tempo_and_frame_rate() {
+        let frame_inputs = FrameInputs::new();
+        assert_eq!(fill("{bpm} BPM", &frame_inputs, 0.5), "120 BPM");
+        // no tempo until two beats were close enough
+        assert_eq!(fill("{bpm} BPM", &frame_inputs, 0.0), "-- BPM");
+        assert_eq!(fill("{fps} fps", &frame_inputs, 0.0), "60 fps");
+    }
+
+    #[test]
+    fn fills_the_strings_set_by_the_application() {
+        let mut frame_inputs = FrameInputs::new();
+        // as `VideoEngine::set_text` sets them
+        frame_inputs
+            .text
+            .insert("track".to_string(), "Song {fps}".to_string());
+        assert_eq!(
+            fill("now: {track}\n{fps}", &frame_inputs, 0.0),
+            "now: Song {fps}\n60"
+        );
+    }
+
+    #[test]
+    fn keeps_unknown_slots() {
+        let frame_inputs = FrameInputs::new();
+        assert_eq!(
+            fill("{track} {} {bpm", &frame_inputs, 0.5),
+            "{track} {} {bpm"
+        );
+        assert_eq!(fill("{{fps}}", &frame_inputs, 0.0), "{{fps}}");
+    }
+
+    #[test]
+    fn breaks_lines_at_newlines() {
+        let mut atlas = atlas();
+        let single = atlas.layout("a", TextAlign::Left, 1.0);
+        let layout = atlas.layout("a a\na", TextAlign::Left, 1.5);
+        // the space has no glyph
+        let [first, second, below] = positions(&layout)[..] else {
+            panic!("expected 3 glyphs, got {}", layout.glyphs.len());
+        };
+        let advance = single.size.x;
+        assert_near(first, single.glyphs[0].position, 1e-3);
+        assert_near(second, first + vec2(2.0 * advance, 0.0), 1e-3);
+        assert_near(below, first + vec2(0.0, 1.5 * layout.line_height), 1e-3);
+        assert_near(
+            layout.size,
+            vec2(3.0 * advance, 2.0 * 1.5 * layout.line_height),
+            1e-3,
+        );
+    }
+
+    #[test]
+    fn aligns_lines_within_the_widest() {
+        let mut atlas = atlas();
+        let advance = atlas.layout("a", TextAlign::Left, 1.0).size.x;
+        for (align, shift) in [
+            (TextAlign::Left, 0.0),
+            (TextAlign::Center, advance),
+            (TextAlign::Right, 2.0 * advance),
+        ] {
+            let layout = atlas.layout("aaa\na", align, 1.0);
+            let glyphs = positions(&layout);
+            assert_near(glyphs[3], glyphs[0] + vec2(shift, layout.line_height), 1e-3);
+        }
+    }
+}
@@ -1,24 +1,244 @@
-use image::ImageReader;
-use std::{path::Path, sync::Arc};
+use crate::{
+    audio::AudioData,
+    error::LavaError,
+    video::{create_write_descriptor_set, shaders::ImageFrame},
+};
+
+use image::{
+    AnimationDecoder, Frames, ImageFormat, ImageReader, RgbaImage,
+    codecs::{gif::GifDecoder, png::PngDecoder},
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::Display,
+    fs::{self, File},
+    io::BufReader,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 use vulkano::{
     DeviceSize,
-    buffer::{Buffer, BufferCreateInfo, BufferUsage},
+    buffer::{Buffer, BufferCreateInfo, BufferUsage, allocator::SubbufferAllocator},
     command_buffer::{
         AutoCommandBufferBuilder, CommandBufferUsage, CopyBufferToImageInfo,
         PrimaryCommandBufferAbstract, allocator::StandardCommandBufferAllocator,
     },
+    descriptor_set::WriteDescriptorSet,
     device::{Device, Queue},
     format::Format,
     image::{
         Image, ImageCreateInfo, ImageType, ImageUsage,
         sampler::{Filter, Sampler, SamplerAddressMode, SamplerCreateInfo},
-        view::ImageView,
+        view::{ImageView, ImageViewCreateInfo, ImageViewType},
     },
     memory::allocator::{AllocationCreateInfo, MemoryTypeFilter, StandardMemoryAllocator},
 };
+
+/// An image file and how it is sampled.
+#[derive(Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct TextureConfig {
+    /// Relative to the config file. A GIF or APNG is played, and so is a directory of numbered
+    /// images.
+    pub path: PathBuf,
+    pub filter: TextureFilter,
+    /// What is sampled outside of the image.
+    pub wrap: TextureWrap,
+    /// How fast animations play, 1 being their own speed.
+    pub speed: f32,
+    /// What animations follow.
+    pub sync: TextureSync,
+    /// The frames per second of a directory of images.
+    pub frame_rate: f32,
+}
+
+impl Default for TextureConfig {
+    fn default() -> Self {
+        Self {
+            path: PathBuf::new(),
+            filter: TextureFilter::default(),
+            wrap: TextureWrap::default(),
+            speed: 1.0,
+            sync: TextureSync::default(),
+            frame_rate: 24.0,
+        }
+    }
+}
+
+#[derive(Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TextureFilter {
+    /// Smooth when scaled.
+    #[default]
+    Linear,
+    /// Keeps the pixels sharp when scaled up.
+    Nearest,
+}
+
+#[derive(Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TextureWrap {
+    /// Stretches the edge pixels.
+    #[default]
+    Clamp,
+    Repeat,
+    /// Repeats the image, flipping every other copy.
+    Mirror,
+    /// Transparent.
+    Border,
+}
+
+#[derive(Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TextureSync {
+    /// Plays at a steady pace.
+    #[default]
+    Time,
+    /// Plays with the bass-driven clock the built-in materials animate with.
+    Chrono,
+    /// Plays once per beat, `speed` times per beat.
+    Beats,
+}
+
+impl TextureConfig {
+    pub fn validate(&self) -> Vec<(String, String)> {
+        let mut issues = Vec::new();
+        if self.frame_rate <= 0.0 {
+            issues.push(("frame_rate".into(), "must be positive".into()));
+        }
+        issues
+    }
+
+    fn sampler_create_info(&self) -> SamplerCreateInfo {
+        let filter = match self.filter {
+            TextureFilter::Linear => Filter::Linear,
+            TextureFilter::Nearest => Filter::Nearest,
+        };
+        let address_mode = match self.wrap {
+            TextureWrap::Clamp => SamplerAddressMode::ClampToEdge,
+            TextureWrap::Repeat => SamplerAddressMode::Repeat,
+            TextureWrap::Mirror => SamplerAddressMode::MirroredRepeat,
+            TextureWrap::Border => SamplerAddressMode::ClampToBorder,
+        };
+        SamplerCreateInfo {
+            mag_filter: filter,
+            min_filter: filter,
+            address_mode: [address_mode; 3],
+            ..Default::default()
+        }
+    }
+}
+
+fn upload_error(path: &Path, error: impl Display) -> LavaError {
+    LavaError::gpu(format!("uploading {}", path.display()), error)
+}
+
+fn decode_still(path: &Path) -> Result<RgbaImage, LavaError> {
+    Ok(ImageReader::open(path)
+        .map_err(|e| LavaError::asset(path, e))?
+        .with_guessed_format()
+        .map_err(|e| LavaError::asset(path, e))?
+        .decode()
+        .map_err(|e| LavaError::asset(path, e))?
+        .to_rgba8())
+}
+
+fn decode_animation(path: &Path, frames: Frames<'_>) -> Result<Vec<(RgbaImage, f32)>, LavaError> {
+    let frames = frames
+        .map(|frame| {
+            let frame = frame.map_err(|e| LavaError::asset(path, e))?;
+            let (numerator, denominator) = frame.delay().numer_denom_ms();
+            let delay = numerator as f32 / denominator as f32 / 1000.0;
+            // like browsers, frames without a delay are shown for a tenth of a second
+            Ok((frame.into_buffer(), if delay > 0.0 { delay } else { 0.1 }))
+        })
+        .collect::<Result<Vec<_>, LavaError>>()?;
+    if frames.is_empty() {
+        return Err(LavaError::asset(path, "the animation has no frames"));
+    }
+    Ok(frames)
+}
+
+/// The images of a directory sorted by their number, shown at `frame_rate`.
+fn decode_sequence(path: &Path, frame_rate: f32) -> Result<Vec<(RgbaImage, f32)>, LavaError> {
+    let mut entries = fs::read_dir(path)
+        .map_err(|e| LavaError::asset(path, e))?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| LavaError::asset(path, e))?;
+    entries.retain(|entry| entry.is_file());
+    // by number first, so that `10.png` comes after `9.png` without zero padding
+    entries.sort_by_key(|entry| {
+        let stem = entry.file_stem().unwrap_or_default().to_string_lossy();
+        let digits = stem.trim_start_matches(|c: char| !c.is_ascii_digit());
+        let number = digits
+            .split(|c: char| !c.is_ascii_digit())
+            .next()
+            .and_then(|number| number.parse::<u64>().ok());
+        (number, entry.clone())
+    });
+    if entries.is_empty() {
+        return Err(LavaError::asset(path, "the directory has no images"));
+    }
+
+    let frames = entries
+        .iter()
+        .map(|entry| Ok((decode_still(entry)?, 1.0 / frame_rate)))
+        .collect::<Result<Vec<_>, LavaError>>()?;
+    if frames
+        .iter()
+        .any(|(frame, _)| frame.dimensions() != frames[0].0.dimensions())
+    {
+        return Err(LavaError::asset(path, "the images have different sizes"));
+    }
+    Ok(frames)
+}
+
+/// The frames of the image at the config's path with how long each is shown in seconds, a
+/// single one for still images.
+fn decode_frames(config: &TextureConfig) -> Result<Vec<(RgbaImage, f32)>, LavaError> {
+    let path = config.path.as_path();
+    if path.is_dir() {
+        return decode_sequence(path, config.frame_rate);
+    }
+
+    let open = || {
+        File::open(path)
+            .map(BufReader::new)
+            .map_err(|e| LavaError::asset(path, e))
+    };
+    let format = ImageReader::open(path)
+        .map_err(|e| LavaError::asset(path, e))?
+        .with_guessed_format()
+        .map_err(|e| LavaError::asset(path, e))?
+        .format();
+    match format {
+        Some(ImageFormat::Gif) => {
+            let decoder = GifDecoder::new(open()?).map_err(|e| LavaError::asset(path, e))?;
+            decode_animation(path, decoder.into_frames())
+        }
+        Some(ImageFormat::Png) => {
+            let decoder = PngDecoder::new(open()?).map_err(|e| LavaError::asset(path, e))?;
+            if decoder.is_apng().map_err(|e| LavaError::asset(path, e))? {
+                let decoder = decoder.apng().map_err(|e| LavaError::asset(path, e))?;
+                decode_animation(path, decoder.into_frames())
+            } else {
+                Ok(vec![(decode_still(path)?, 0.0)])
+            }
+        }
+        _ => Ok(vec![(decode_still(path)?, 0.0)]),
+    }
+}
+
+/// An image, or the frames of an animation as the layers of an array.
 pub struct Texture {
     pub image_view: Arc<ImageView>,
     pub sampler: Arc<Sampler>,
+    /// When each frame ends, in seconds from the start of the animation.
+    frame_ends: Vec<f32>,
+    speed: f32,
+    sync: TextureSync,
 }
 
 impl Texture {
@@ -27,22 +247,38 @@ impl Texture {
         queue: &Arc<Queue>,
         memory_allocator: &Arc<StandardMemoryAllocator>,
         command_buffer_allocator: &Arc<StandardCommandBufferAllocator>,
-        path: &Path,
-    ) -> Self {
+        config: &TextureConfig,
+    ) -> Result<Self, LavaError> {
+        let path = config.path.as_path();
         let mut uploads = AutoCommandBufferBuilder::primary(
             command_buffer_allocator.clone(),
             queue.queue_family_index(),
             CommandBufferUsage::OneTimeSubmit,
         )
-        .unwrap();
+        .map_err(|e| upload_error(path, e))?;
+
+        let frames = decode_frames(config)?;
+        let max_frames = device.physical_device().properties().max_image_array_layers;
+        if frames.len() > max_frames as usize {
+            return Err(LavaError::asset(
+                path,
+                format!(
+                    "the animation has {} frames, but the GPU holds at most {max_frames}",
+                    frames.len()
+                ),
+            ));
+        }
+        let frame_ends = frames
+            .iter()
+            .scan(0.0, |end, (_, duration)| {
+                *end += duration;
+                Some(*end)
+            })
+            .collect();
 
         let image_view = {
-            let rgba = ImageReader::open(path)
-                .unwrap()
-                .decode()
-                .unwrap()
-                .to_rgba32f();
-            let extent = [rgba.width(), rgba.height(), 1];
+            let (width, height) = frames[0].0.dimensions();
+            let extent = [width, height, 1];
 
             let upload_buffer = Buffer::new_slice(
                 memory_allocator.clone(),
@@ -55,53 +291,112 @@ impl Texture {
                         | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
                     ..Default::default()
                 },
-                (extent[0] * extent[1] * 4) as DeviceSize,
+                (width * height * 4) as DeviceSize * frames.len() as DeviceSize,
             )
-            .unwrap();
+            .map_err(|e| upload_error(path, e))?;
 
             let mut guard = upload_buffer.write().unwrap();
-            guard.copy_from_slice(rgba.iter().as_slice());
+            for (chunk, (frame, _)) in guard
+                .chunks_exact_mut((width * height * 4) as usize)
+                .zip(&frames)
+            {
+                chunk.copy_from_slice(frame.as_raw());
+            }
             drop(guard);
 
             let image = Image::new(
                 memory_allocator.clone(),
                 ImageCreateInfo {
                     image_type: ImageType::Dim2d,
-                    format: Format::R32G32B32A32_SFLOAT,
+                    // the values are used as they are, like the shaders' own colors
+                    format: Format::R8G8B8A8_UNORM,
                     extent,
+                    array_layers: frames.len() as u32,
                     usage: ImageUsage::TRANSFER_DST | ImageUsage::SAMPLED,
                     ..Default::default()
                 },
                 AllocationCreateInfo::default(),
             )
-            .unwrap();
+            .map_err(|e| upload_error(path, e))?;
 
             uploads
                 .copy_buffer_to_image(CopyBufferToImageInfo::buffer_image(
                     upload_buffer,
                     image.clone(),
                 ))
-                .unwrap();
+                .map_err(|e| upload_error(path, e))?;
 
-            ImageView::new_default(image).unwrap()
+            // an array even for still images, which is what the shaders sample
+            ImageView::new(
+                image.clone(),
+                ImageViewCreateInfo {
+                    view_type: ImageViewType::Dim2dArray,
+                    ..ImageViewCreateInfo::from_image(&image)
+                },
+            )
+            .map_err(|e| upload_error(path, e))?
         };
 
-        let sampler = Sampler::new(
-            device.clone(),
-            SamplerCreateInfo {
-                mag_filter: Filter::Linear,
-                min_filter: Filter::Linear,
-                address_mode: [SamplerAddressMode::ClampToEdge; 3],
-                ..Default::default()
-            },
-        )
-        .unwrap();
+        let sampler = Sampler::new(device.clone(), config.sampler_create_info())
+            .map_err(|e| upload_error(path, e))?;
 
-        let _ = uploads.build().unwrap().execute(queue.clone()).unwrap();
+        let _ = uploads
+            .build()
+            .map_err(|e| upload_error(path, e))?
+            .execute(queue.clone())
+            .map_err(|e| upload_error(path, e))?;
 
-        Self {
+        Ok(Self {
             image_view,
             sampler,
+            frame_ends,
+            speed: config.speed,
+            sync: config.sync,
+        })
+    }
+
+    /// The frame to show, given the seconds since lava started.
+    pub fn frame(&self, time: f32, audio_data: &AudioData) -> u32 {
+        let duration = *self.frame_ends.last().unwrap();
+        if self.frame_ends.len() == 1 || duration <= 0.0 {
+            return 0;
         }
+        let progress = match self.sync {
+            TextureSync::Time => time * self.speed / duration,
+            TextureSync::Chrono => audio_data.bass.chrono * self.speed / duration,
+            TextureSync::Beats => {
+                let beats = &audio_data.beats;
+                // the part of the current beat that went by, held at its end until the next
+                let part = if beats.interval > 0.0 {
+                    (beats.since_last / beats.interval).min(1.0)
+                } else {
+                    0.0
+                };
+                (beats.count as f32 + part) * self.speed
+            }
+        };
+        let time = progress.rem_euclid(1.0) * duration;
+        self.frame_ends
+            .iter()
+            .position(|end| time < *end)
+            .unwrap_or(self.frame_ends.len() - 1) as u32
+    }
+
+    /// The writes of the sampler, the image and `frame` at the bindings of
+    /// `uniforms/image.glsl`.
+    pub fn writes(
+        &self,
+        uniform_buffer_allocator: &SubbufferAllocator,
+        frame: u32,
+    ) -> [WriteDescriptorSet; 3] {
+        [
+            WriteDescriptorSet::sampler(6, self.sampler.clone()),
+            WriteDescriptorSet::image_view(7, self.image_view.clone()),
+            create_write_descriptor_set(
+                uniform_buffer_allocator,
+                9,
+                ImageFrame { image_frame: frame },
+            ),
+        ]
     }
 }
@@ -0,0 +1,239 @@
+use crate::{
+    audio::AudioData,
+    video::{
+        shader_types::{LineJoin, WaveformParameters, WaveformTrigger},
+        shaders,
+    },
+};
+
+use glam::Vec2;
+use vulkano::padded::Padded;
+
+/// The parameters of a waveform panel, with where it is triggered in the current samples.
+pub fn waveform_parameters(
+    parameters: &WaveformParameters,
+    audio_data: &AudioData,
+) -> shaders::WaveformParameters {
+    let sample_count = audio_data.samples.data.len() as f32;
+    let window = window_samples(parameters, audio_data);
+    let focus = audio_data.stabilization.focus;
+    // the latest sample at the focus that keeps the whole window within the samples
+    let latest = sample_count - 1.0 - window * (1.0 - focus);
+
+    let center = match parameters.trigger {
+        WaveformTrigger::FreeRun => latest,
+        WaveformTrigger::Rising | WaveformTrigger::Falling => {
+            find_edge(parameters, audio_data, window * focus, latest).unwrap_or(latest)
+        }
+        WaveformTrigger::Pitch => audio_data.stabilization.center_sample,
+    };
+
+    shaders::WaveformParameters {
+        pattern: Padded(parameters.pattern.clone().into()),
+        line_width: parameters.line_width,
+        gain: parameters.gain,
+        center,
+        window,
+    }
+}
+
+/// The parameters of a waveform panel drawn as lines on a `screen_size` pixels target.
+pub fn waveform_lines_parameters(
+    parameters: &WaveformParameters,
+    audio_data: &AudioData,
+    screen_size: Vec2,
+) -> shaders::WaveformLinesParameters {
+    let shaders::WaveformParameters {
+        pattern,
+        line_width,
+        gain,
+        center,
+        window,
+    } = waveform_parameters(parameters, audio_data);
+    shaders::WaveformLinesParameters {
+        pattern,
+        line_width,
+        gain,
+        center,
+        window,
+        screen_size: screen_size.to_array(),
+        point_count: point_count(window),
+        round_joins: (parameters.join == LineJoin::Round) as u32,
+    }
+}
+
+/// The vertices of a waveform panel drawn as lines.
+pub fn line_vertex_count(parameters: &WaveformParameters, audio_data: &AudioData) -> u32 {
+    let points = point_count(window_samples(parameters, audio_data));
+    match parameters.join {
+        LineJoin::Miter => points * 2,
+        LineJoin::Round => (points - 1) * 6,
+    }
+}
+
+/// The samples across a waveform panel.
+fn window_samples(parameters: &WaveformParameters, audio_data: &AudioData) -> f32 {
+    let sample_count = audio_data.samples.data.len() as f32;
+    if parameters.window > 0.0 {
+        (parameters.window / 1000.0 * audio_data.sample_rate as f32).min(sample_count)
+    } else {
+        sample_count
+    }
+}
+
+/// The points of the line through a `window` of samples, one at every sample.
+fn point_count(window: f32) -> u32 {
+    (window.ceil() as u32 + 1).max(2)
+}
+
+/// The fractional sample of the latest crossing of the trigger level between `earliest` and
+/// `latest`.
+fn find_edge(
+    parameters: &WaveformParameters,
+    audio_data: &AudioData,
+    earliest: f32,
+    latest: f32,
+) -> Option<f32> {
+    if latest < earliest {
+        return None;
+    }
+    let level = parameters.trigger_level;
+    let sample = |i: usize| audio_data.samples[i] * parameters.gain;
+    let rising = parameters.trigger == WaveformTrigger::Rising;
+
+    (earliest.ceil().max(1.0) as usize..=latest.floor() as usize)
+        .rev()
+        .find_map(|i| {
+            let (before, after) = (sample(i - 1), sample(i));
+            let crossed = if rising {
+                before < level && after >= level
+            } else {
+                before > level && after <= level
+            };
+            crossed.then(|| i as f32 - 1.0 + (level - before) / (after - before))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::assert_near;
+
+    /// 64 samples at 1 kHz, so that windows in milliseconds are in samples, focused in the middle.
+    fn audio_data(sample: impl Fn(usize) -> f32) -> AudioData {
+        AudioData::silence(64, 16, 1000)
+            .with_samples((0..64).map(sample))
+            .with_focus(0.5)
+    }
+
+    /// From -1 to 1 and back every 16 samples.
+    fn triangle(i: usize) -> f32 {
+        let phase = (i % 16) as f32;
+        if phase < 8.0 {
+            phase / 4.0 - 1.0
+        } else {
+            3.0 - phase / 4.0
+        }
+    }
+
+    fn triggered(trigger: WaveformTrigger, window: f32, audio_data: &AudioData) -> f32 {
+        let parameters = WaveformParameters {
+            gain: 1.0,
+            trigger,
+            trigger_level: 0.6,
+            window,
+            ..Default::default()
+        };
+        waveform_parameters(&parameters, audio_data).center
+    }
+
+    #[test]
+    fn finds_the_latest_crossing() {
+        let audio_data = audio_data(triangle);
+        // the focus can go from sample 8 to 55 with a window of 16, the triangle crosses 0.6 going
+        // up 6.4 samples into its periods and going down 9.6 samples into them
+        assert_near(
+            triggered(WaveformTrigger::Rising, 16.0, &audio_data),
+            54.4,
+            1e-4,
+        );
+        assert_near(
+            triggered(WaveformTrigger::Falling, 16.0, &audio_data),
+            41.6,
+            1e-4,
+        );
+        assert_near(
+            triggered(WaveformTrigger::FreeRun, 16.0, &audio_data),
+            55.0,
+            1e-4,
+        );
+    }
+
+    #[test]
+    fn runs_free_without_a_crossing() {
+        let audio_data = audio_data(|_| 0.5);
+        assert_near(
+            triggered(WaveformTrigger::Rising, 16.0, &audio_data),
+            55.0,
+            1e-4,
+        );
+        assert_near(
+            triggered(WaveformTrigger::Falling, 16.0, &audio_data),
+            55.0,
+            1e-4,
+        );
+    }
+
+    #[test]
+    fn clamps_windows_to_the_samples() {
+        let mut audio_data = audio_data(triangle);
+        // the window only fits with the focus in its middle, which leaves no samples to search
+        assert_near(
+            triggered(WaveformTrigger::Rising, 1000.0, &audio_data),
+            31.0,
+            1e-4,
+        );
+
+        // no sample fits the whole window
+        audio_data.stabilization.focus = 0.0;
+        assert_near(
+            triggered(WaveformTrigger::Rising, 0.0, &audio_data),
+            -1.0,
+            1e-4,
+        );
+        let parameters = WaveformParameters {
+            trigger: WaveformTrigger::Rising,
+            ..Default::default()
+        };
+        assert_eq!(find_edge(&parameters, &audio_data, 0.0, -1.0), None);
+    }
+
+    #[test]
+    fn finds_a_crossing_at_the_first_sample() {
+        let audio_data = audio_data(|i| if i == 0 { -1.0 } else { 1.0 });
+        let parameters = WaveformParameters {
+            gain: 1.0,
+            trigger: WaveformTrigger::Rising,
+            ..Default::default()
+        };
+        assert_eq!(find_edge(&parameters, &audio_data, 0.0, 63.0), Some(0.5));
+    }
+
+    #[test]
+    fn counts_a_vertex_pair_per_sample() {
+        let audio_data = audio_data(triangle);
+        let vertices = |window: f32, join: LineJoin| {
+            let parameters = WaveformParameters {
+                window,
+                join,
+                ..Default::default()
+            };
+            line_vertex_count(&parameters, &audio_data)
+        };
+        assert_eq!(vertices(16.0, LineJoin::Miter), 34);
+        assert_eq!(vertices(16.0, LineJoin::Round), 96);
+        assert_eq!(vertices(16.5, LineJoin::Miter), 36);
+        assert_eq!(vertices(1000.0, LineJoin::Miter), 130);
+        assert_eq!(vertices(0.0, LineJoin::Miter), 130);
+    }
+}
@@ -1,24 +1,24 @@
 use crate::{
     audio::AudioData,
     config::Config,
-    video::{Mesh, RenderContext, Texture},
+    error::LavaError,
+    video::{Mouse, RenderContext},
 };
 
-use std::sync::Arc;
+use glam::Vec2;
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 use vulkano::{
     VulkanLibrary,
-    buffer::{
-        BufferUsage,
-        allocator::{SubbufferAllocator, SubbufferAllocatorCreateInfo},
-    },
     command_buffer::allocator::StandardCommandBufferAllocator,
-    descriptor_set::allocator::StandardDescriptorSetAllocator,
     device::{
         Device, DeviceCreateInfo, DeviceExtensions, Queue, QueueCreateInfo, QueueFlags,
         physical::PhysicalDeviceType,
     },
     instance::{Instance, InstanceCreateFlags, InstanceCreateInfo},
-    memory::allocator::{MemoryTypeFilter, StandardMemoryAllocator},
+    memory::allocator::StandardMemoryAllocator,
     swapchain::Surface,
 };
 use winit::{dpi::PhysicalSize, event_loop::EventLoop, window::Window};
@@ -28,20 +28,21 @@ pub struct VideoEngine {
     pub device: Arc<Device>,
     pub queue: Arc<Queue>,
     pub memory_allocator: Arc<StandardMemoryAllocator>,
-    pub descriptor_set_allocator: Arc<StandardDescriptorSetAllocator>,
     pub command_buffer_allocator: Arc<StandardCommandBufferAllocator>,
-    pub uniform_buffer_allocator: SubbufferAllocator,
-    pub storage_buffer_allocator: SubbufferAllocator,
-    pub mesh: Mesh,
-    pub texture: Option<Texture>,
 
     pub context: Option<RenderContext>,
+
+    pub shader_directory: Option<PathBuf>,
+    /// Whether frames are presented without waiting for the display's refresh.
+    pub uncapped: bool,
 }
 
 impl VideoEngine {
-    pub fn new(event_loop: &EventLoop, config: &Config) -> Self {
-        let library = VulkanLibrary::new().unwrap();
-        let required_extensions = Surface::required_extensions(event_loop).unwrap();
+    pub fn new(event_loop: &EventLoop) -> Result<Self, LavaError> {
+        let library =
+            VulkanLibrary::new().map_err(|e| LavaError::gpu("loading the Vulkan library", e))?;
+        let required_extensions = Surface::required_extensions(event_loop)
+            .map_err(|e| LavaError::gpu("querying surface extensions", e))?;
         let instance = Instance::new(
             library,
             InstanceCreateInfo {
@@ -50,7 +51,7 @@ impl VideoEngine {
                 ..Default::default()
             },
         )
-        .unwrap();
+        .map_err(|e| LavaError::gpu("creating the Vulkan instance", e))?;
 
         let device_extensions = DeviceExtensions {
             khr_swapchain: true,
@@ -58,7 +59,7 @@ impl VideoEngine {
         };
         let (physical_device, queue_family_index) = instance
             .enumerate_physical_devices()
-            .unwrap()
+            .map_err(|e| LavaError::gpu("enumerating physical devices", e))?
             .filter(|p| p.supported_extensions().contains(&device_extensions))
             .filter_map(|p| {
                 p.queue_family_properties()
@@ -66,7 +67,8 @@ impl VideoEngine {
                     .enumerate()
                     .position(|(i, q)| {
                         q.queue_flags.intersects(QueueFlags::GRAPHICS)
-                            && p.presentation_support(i as u32, event_loop).unwrap()
+                            && p.presentation_support(i as u32, event_loop)
+                                .unwrap_or(false)
                     })
                     .map(|i| (p, i as u32))
             })
@@ -78,7 +80,12 @@ impl VideoEngine {
                 PhysicalDeviceType::Other => 4,
                 _ => 5,
             })
-            .unwrap();
+            .ok_or_else(|| {
+                LavaError::gpu(
+                    "selecting a video device",
+                    "no device supports graphics, presentation and swapchains",
+                )
+            })?;
 
         println!(
             "Using video device: {} (type: {:?})",
@@ -97,95 +104,146 @@ impl VideoEngine {
                 ..Default::default()
             },
         )
-        .unwrap();
+        .map_err(|e| LavaError::gpu("creating the logical device", e))?;
 
         let queue = queues.next().unwrap();
 
         let memory_allocator = Arc::new(StandardMemoryAllocator::new_default(device.clone()));
-        let descriptor_set_allocator = Arc::new(StandardDescriptorSetAllocator::new(
-            device.clone(),
-            Default::default(),
-        ));
         let command_buffer_allocator = Arc::new(StandardCommandBufferAllocator::new(
             device.clone(),
             Default::default(),
         ));
 
-        let uniform_buffer_allocator = SubbufferAllocator::new(
-            memory_allocator.clone(),
-            SubbufferAllocatorCreateInfo {
-                buffer_usage: BufferUsage::UNIFORM_BUFFER,
-                memory_type_filter: MemoryTypeFilter::PREFER_DEVICE
-                    | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
-                ..Default::default()
-            },
-        );
-
-        let storage_buffer_allocator = SubbufferAllocator::new(
-            memory_allocator.clone(),
-            SubbufferAllocatorCreateInfo {
-                buffer_usage: BufferUsage::STORAGE_BUFFER,
-                memory_type_filter: MemoryTypeFilter::PREFER_DEVICE
-                    | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
-                ..Default::default()
-            },
-        );
-
-        let mesh = Mesh::new(&memory_allocator);
-
-        let texture = match &config.image_path {
-            Some(path) => Some(Texture::new(
-                &device,
-                &queue,
-                &memory_allocator,
-                &command_buffer_allocator,
-                &path,
-            )),
-            None => None,
-        };
-
-        Self {
+        Ok(Self {
             instance,
             device,
             queue,
             memory_allocator,
-            descriptor_set_allocator,
             command_buffer_allocator,
-            uniform_buffer_allocator,
-            storage_buffer_allocator,
-            mesh,
-            texture,
             context: None,
-        }
+            shader_directory: None,
+            uncapped: false,
+        })
     }
 
-    pub fn init(&mut self, window: &Arc<Box<dyn Window>>, config: &Config) {
+    pub fn init(
+        &mut self,
+        window: &Arc<Box<dyn Window>>,
+        config: &Config,
+    ) -> Result<(), LavaError> {
         self.context = Some(RenderContext::new(
             &self.instance,
             &self.device,
+            &self.queue,
             &self.memory_allocator,
+            &self.command_buffer_allocator,
             &window,
             &config,
-        ));
+        )?);
+
+        if let (Some(context), Some(directory)) = (&mut self.context, &self.shader_directory) {
+            context.renderer.use_shader_sources(directory, config)?;
+        }
+        if let Some(context) = &mut self.context
+            && self.uncapped
+            && !context.uncap_frame_rate(&self.device)?
+        {
+            eprintln!("the display only presents at its refresh rate, frame times are capped");
+        }
+        Ok(())
+    }
+
+    /// Compiles the built-in shaders from `directory` once initialized, see
+    /// [`Renderer::use_shader_sources`](crate::video::Renderer::use_shader_sources).
+    pub fn use_shader_sources(&mut self, directory: &Path) {
+        self.shader_directory = Some(directory.to_path_buf());
+    }
+
+    /// Presents without waiting for the display's refresh once initialized, see
+    /// [`RenderContext::uncap_frame_rate`].
+    pub fn uncap_frame_rate(&mut self) {
+        self.uncapped = true;
+    }
+
+    pub fn update_shaders(&mut self, config: &Config) {
+        if let Some(context) = &mut self.context {
+            context.renderer.update_shaders(config);
+        }
+    }
+
+    /// The pointer state the shaders see, once initialized.
+    pub fn mouse(&mut self) -> Option<&mut Mouse> {
+        self.context
+            .as_mut()
+            .map(|context| &mut context.renderer.frame_inputs.mouse)
+    }
+
+    /// Shows `value` in place of `{name}` in the text panels, once initialized.
+    pub fn set_text(&mut self, name: &str, value: &str) {
+        if let Some(context) = &mut self.context {
+            context
+                .renderer
+                .frame_inputs
+                .text
+                .insert(name.to_string(), value.to_string());
+        }
+    }
+
+    /// See [`Renderer::create_gpu_dft`](crate::video::Renderer::create_gpu_dft).
+    pub fn create_gpu_dft(&mut self, config: &Config) -> Result<(), LavaError> {
+        match &mut self.context {
+            Some(context) => context.renderer.create_gpu_dft(&self.queue, config),
+            None => Ok(()),
+        }
+    }
+
+    /// The latest DFT computed on the GPU since the last call, with the position of its samples,
+    /// see [`GpuDft::take_result`](crate::video::GpuDft::take_result).
+    pub fn take_gpu_dft(&mut self) -> Option<(Vec<Vec2>, u64)> {
+        self.context
+            .as_mut()?
+            .renderer
+            .gpu_dft
+            .as_mut()?
+            .take_result()
+    }
+
+    /// Whether `init` succeeded, there is nothing to draw with before.
+    pub fn is_initialized(&self) -> bool {
+        self.context.is_some()
     }
 
     pub fn resize(&mut self) {
-        self.context.as_mut().unwrap().recreate_swapchain = true;
+        if let Some(context) = &mut self.context {
+            context.recreate_swapchain = true;
+        }
+    }
+
+    pub fn reload(&mut self, old: &Config, new: &Config) -> Result<(), LavaError> {
+        match self.context.as_mut() {
+            Some(context) => {
+                context
+                    .renderer
+                    .apply_config(&self.queue, &self.command_buffer_allocator, old, new)
+            }
+            None => Ok(()),
+        }
     }
 
-    pub fn redraw(&mut self, window_size: &PhysicalSize<u32>, audio_data: &AudioData) {
-        self.context.as_mut().unwrap().redraw(
+    pub fn redraw(
+        &mut self,
+        window_size: &PhysicalSize<u32>,
+        audio_data: &AudioData,
+    ) -> Result<(), LavaError> {
+        let Some(context) = &mut self.context else {
+            return Ok(());
+        };
+        context.redraw(
             &self.device,
             &self.queue,
-            &self.memory_allocator,
-            &self.descriptor_set_allocator,
             &self.command_buffer_allocator,
-            &self.uniform_buffer_allocator,
-            &self.storage_buffer_allocator,
-            &self.mesh,
-            &self.texture,
             &window_size,
             &audio_data,
-        );
+        )
     }
 }
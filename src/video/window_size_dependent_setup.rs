@@ -1,7 +1,9 @@
-use super::{Position, Uv};
+use super::{Position, Uv, material::MaterialGeometry};
+use crate::error::LavaError;
+
 use std::sync::Arc;
 use vulkano::{
-    device::{Device, DeviceOwned},
+    device::Device,
     format::Format,
     image::{Image, ImageCreateInfo, ImageType, ImageUsage, view::ImageView},
     memory::allocator::{AllocationCreateInfo, StandardMemoryAllocator},
@@ -9,12 +11,14 @@ use vulkano::{
         GraphicsPipeline, PipelineLayout, PipelineShaderStageCreateInfo,
         graphics::{
             GraphicsPipelineCreateInfo,
-            color_blend::{AttachmentBlend, ColorBlendAttachmentState, ColorBlendState},
+            color_blend::{
+                AttachmentBlend, BlendFactor, ColorBlendAttachmentState, ColorBlendState,
+            },
             depth_stencil::{DepthState, DepthStencilState},
-            input_assembly::InputAssemblyState,
+            input_assembly::{InputAssemblyState, PrimitiveTopology},
             multisample::MultisampleState,
             rasterization::RasterizationState,
-            vertex_input::{Vertex, VertexDefinition},
+            vertex_input::{Vertex, VertexDefinition, VertexInputState},
             viewport::{Viewport, ViewportState},
         },
         layout::PipelineDescriptorSetLayoutCreateInfo,
@@ -24,16 +28,76 @@ use vulkano::{
 };
 use winit::dpi::PhysicalSize;
 
-fn create_pipeline(
+/// Alpha blending that also accumulates coverage in the alpha channel, so that what is drawn over
+/// a transparent target ends up with premultiplied alpha.
+pub fn alpha_blend() -> AttachmentBlend {
+    AttachmentBlend {
+        src_alpha_blend_factor: BlendFactor::One,
+        ..AttachmentBlend::alpha()
+    }
+}
+
+/// Like [`alpha_blend`], but the colors add up instead of covering each other.
+pub fn additive_blend() -> AttachmentBlend {
+    AttachmentBlend {
+        dst_color_blend_factor: BlendFactor::One,
+        ..alpha_blend()
+    }
+}
+
+/// Creates a pipeline drawing the quad of `Mesh`.
+pub fn create_pipeline(
     device: &Arc<Device>,
     window_size: PhysicalSize<u32>,
     render_pass: &Arc<RenderPass>,
     vs: &EntryPoint,
     fs: &EntryPoint,
-) -> Arc<GraphicsPipeline> {
+    blend: Option<AttachmentBlend>,
+) -> Result<Arc<GraphicsPipeline>, LavaError> {
     let vertex_input_state = [Position::per_vertex(), Uv::per_vertex()]
         .definition(vs)
-        .unwrap();
+        .map_err(|e| LavaError::gpu("creating a pipeline", e))?;
+    build_pipeline(
+        device,
+        window_size,
+        render_pass,
+        [vs, fs],
+        vertex_input_state,
+        None,
+        blend,
+    )
+}
+
+/// Creates a pipeline drawing the primitives `vs` generates without vertex buffers.
+pub fn create_geometry_pipeline(
+    device: &Arc<Device>,
+    window_size: PhysicalSize<u32>,
+    render_pass: &Arc<RenderPass>,
+    vs: &EntryPoint,
+    fs: &EntryPoint,
+    geometry: &MaterialGeometry,
+    blend: Option<AttachmentBlend>,
+) -> Result<Arc<GraphicsPipeline>, LavaError> {
+    build_pipeline(
+        device,
+        window_size,
+        render_pass,
+        [vs, fs],
+        VertexInputState::new(),
+        Some(geometry),
+        blend,
+    )
+}
+
+fn build_pipeline(
+    device: &Arc<Device>,
+    window_size: PhysicalSize<u32>,
+    render_pass: &Arc<RenderPass>,
+    [vs, fs]: [&EntryPoint; 2],
+    vertex_input_state: VertexInputState,
+    geometry: Option<&MaterialGeometry>,
+    blend: Option<AttachmentBlend>,
+) -> Result<Arc<GraphicsPipeline>, LavaError> {
     let stages = [
         PipelineShaderStageCreateInfo::new(vs.clone()),
         PipelineShaderStageCreateInfo::new(fs.clone()),
@@ -42,9 +106,9 @@ fn create_pipeline(
         device.clone(),
         PipelineDescriptorSetLayoutCreateInfo::from_stages(&stages)
             .into_pipeline_layout_create_info(device.clone())
-            .unwrap(),
+            .map_err(|e| LavaError::gpu("creating a pipeline", e))?,
     )
-    .unwrap();
+    .map_err(|e| LavaError::gpu("creating a pipeline", e))?;
     let subpass = Subpass::from(render_pass.clone(), 0).unwrap();
 
     GraphicsPipeline::new(
@@ -53,7 +117,12 @@ fn create_pipeline(
         GraphicsPipelineCreateInfo {
             stages: stages.into_iter().collect(),
             vertex_input_state: Some(vertex_input_state),
-            input_assembly_state: Some(InputAssemblyState::default()),
+            input_assembly_state: Some(InputAssemblyState {
+                topology: geometry.map_or(PrimitiveTopology::TriangleList, |geometry| {
+                    geometry.topology
+                }),
+                ..Default::default()
+            }),
             viewport_state: Some(ViewportState {
                 viewports: [Viewport {
                     offset: [0.0, 0.0],
@@ -65,15 +134,22 @@ fn create_pipeline(
                 ..Default::default()
             }),
             rasterization_state: Some(RasterizationState::default()),
-            depth_stencil_state: Some(DepthStencilState {
-                depth: Some(DepthState::default()),
-                ..Default::default()
-            }),
+            depth_stencil_state: subpass
+                .subpass_desc()
+                .depth_stencil_attachment
+                .is_some()
+                .then(|| DepthStencilState {
+                    depth: Some(match geometry {
+                        Some(geometry) if geometry.depth_test => DepthState::simple(),
+                        _ => DepthState::default(),
+                    }),
+                    ..Default::default()
+                }),
             multisample_state: Some(MultisampleState::default()),
             color_blend_state: Some(ColorBlendState::with_attachment_states(
                 subpass.num_color_attachments(),
                 ColorBlendAttachmentState {
-                    blend: Some(AttachmentBlend::alpha()),
+                    blend,
                     ..Default::default()
                 },
             )),
@@ -81,55 +157,50 @@ fn create_pipeline(
             ..GraphicsPipelineCreateInfo::layout(layout)
         },
     )
-    .unwrap()
+    .map_err(|e| LavaError::gpu("creating a pipeline", e))
 }
 
-pub fn window_size_dependent_setup(
-    window_size: PhysicalSize<u32>,
-    images: &[Arc<Image>],
-    render_pass: &Arc<RenderPass>,
+/// Creates an image of `extent` to render into.
+pub fn create_attachment(
     memory_allocator: &Arc<StandardMemoryAllocator>,
-    vs: &EntryPoint,
-    fs: &Vec<EntryPoint>,
-) -> (Vec<Arc<Framebuffer>>, Vec<Arc<GraphicsPipeline>>) {
-    let device = memory_allocator.device();
-
-    let depth_buffer = ImageView::new_default(
-        Image::new(
-            memory_allocator.clone(),
-            ImageCreateInfo {
-                image_type: ImageType::Dim2d,
-                format: Format::D16_UNORM,
-                extent: images[0].extent(),
-                usage: ImageUsage::DEPTH_STENCIL_ATTACHMENT | ImageUsage::TRANSIENT_ATTACHMENT,
-                ..Default::default()
-            },
-            AllocationCreateInfo::default(),
-        )
-        .unwrap(),
+    format: Format,
+    extent: PhysicalSize<u32>,
+    usage: ImageUsage,
+) -> Result<Arc<ImageView>, LavaError> {
+    let image = Image::new(
+        memory_allocator.clone(),
+        ImageCreateInfo {
+            image_type: ImageType::Dim2d,
+            format,
+            extent: [extent.width, extent.height, 1],
+            usage,
+            ..Default::default()
+        },
+        AllocationCreateInfo::default(),
     )
-    .unwrap();
+    .map_err(|e| LavaError::gpu("creating framebuffers", e))?;
+    ImageView::new_default(image).map_err(|e| LavaError::gpu("creating framebuffers", e))
+}
 
-    let framebuffers = images
+/// Creates the framebuffers of `images` for `output_render_pass`.
+pub fn window_size_dependent_setup(
+    images: &[Arc<Image>],
+    output_render_pass: &Arc<RenderPass>,
+) -> Result<Vec<Arc<Framebuffer>>, LavaError> {
+    images
         .iter()
         .map(|image| {
-            let view = ImageView::new_default(image.clone()).unwrap();
+            let view = ImageView::new_default(image.clone())
+                .map_err(|e| LavaError::gpu("creating framebuffers", e))?;
 
             Framebuffer::new(
-                render_pass.clone(),
+                output_render_pass.clone(),
                 FramebufferCreateInfo {
-                    attachments: vec![view, depth_buffer.clone()],
+                    attachments: vec![view],
                     ..Default::default()
                 },
             )
-            .unwrap()
+            .map_err(|e| LavaError::gpu("creating framebuffers", e))
         })
-        .collect::<Vec<_>>();
-
-    (
-        framebuffers,
-        fs.iter()
-            .map(|fs| create_pipeline(device, window_size, render_pass, vs, fs))
-            .collect(),
-    )
+        .collect()
 }